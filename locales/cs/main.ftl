@@ -0,0 +1,54 @@
+confirmation-email-subject = Přihlášení k odběru newsletteru
+confirmation-email-body =
+    Vítejte v našem newsletteru <br> Klikněte <a href="{ $link }">zde</a> pro potvrzení odběru
+subscription-confirmed = Váš odběr byl potvrzen. Děkujeme!
+subscription-token-invalid = Tento potvrzovací odkaz není platný.
+subscription-already-confirmed = Váš odběr už je potvrzený.
+subscription-token-expired = Platnost tohoto potvrzovacího odkazu vypršela. Přihlaste se prosím znovu a získáte nový.
+page-title = Newsletter
+subscribe-success = Děkujeme za přihlášení! Odběr prosím potvrďte odkazem ve vaší schránce.
+subscribe-duplicate = Tato e-mailová adresa je už přihlášená.
+subscribe-invalid = Zadejte prosím platné jméno a e-mailovou adresu.
+subscribe-failed = Něco se pokazilo, zkuste to prosím později.
+confirm-prompt = Potvrďte prosím, že chcete dostávat náš newsletter.
+confirm-button = Potvrdit odběr
+preferences-email-subject = Správa odběru newsletteru
+preferences-email-body =
+    Klikněte <a href="{ $link }">zde</a> pro správu odběru newsletteru. Platnost odkazu brzy vyprší, vždy si ale můžete vyžádat nový.
+preferences-request-prompt = Zadejte svou e-mailovou adresu a pošleme vám odkaz pro správu odběru.
+preferences-email-label = E-mail
+preferences-request-button = Poslat odkaz
+preferences-link-sent = Pokud je tato adresa přihlášená, odkaz pro správu odběru je na cestě.
+preferences-link-invalid = Tento odkaz není platný.
+preferences-link-expired = Platnost tohoto odkazu vypršela. Vyžádejte si prosím nový.
+preferences-name-label = Jméno
+preferences-topics-label = Témata
+preferences-frequency-label = Jak často
+frequency-daily = Denně
+frequency-weekly = Týdně
+frequency-monthly = Měsíčně
+preferences-pause-label = Prozatím pozastavit newsletter
+preferences-save-button = Uložit
+preferences-saved = Vaše nastavení bylo uloženo.
+preferences-invalid = Zadejte prosím platné jméno a vyberte, jak často od nás chcete zprávy dostávat.
+unsubscribe-button = Odhlásit vše
+unsubscribed = Odběr byl zrušen. Mrzí nás, že odcházíte.
+preferences-lists-label = Vaše seznamy
+membership-confirmed = přihlášeno
+membership-pending = čeká na potvrzení
+membership-unsubscribed = odhlášeno
+unsubscribe-list-button = Opustit tento seznam
+unsubscribed-list = Ze seznamu jste se odhlásili. Ostatní odběry zůstávají beze změny.
+email-change-button = Změnit e-mail
+email-change-invalid = Zadejte prosím platnou e-mailovou adresu, která se liší od současné.
+email-change-taken = Tato e-mailová adresa je už přihlášená.
+email-change-sent = Na novou adresu jsme poslali odkaz. Změna začne platit, jakmile ji tam potvrdíte.
+email-change-verify-subject = Potvrďte svou novou e-mailovou adresu
+email-change-verify-body =
+    Klikněte <a href="{ $link }">zde</a> a náš newsletter budete nadále dostávat na tuto adresu.
+email-change-prompt = Potvrďte prosím, že chcete náš newsletter nadále dostávat na tuto adresu.
+email-change-confirm-button = Potvrdit novou adresu
+email-change-confirmed = Vaše e-mailová adresa byla změněna.
+email-change-notice-subject = E-mailová adresa pro newsletter byla změněna
+email-change-notice-body =
+    Odběr newsletteru pro tuto adresu byl přesunut na { $email }. Pokud jste o to nežádali, kontaktujte nás prosím.
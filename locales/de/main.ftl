@@ -0,0 +1,54 @@
+confirmation-email-subject = Newsletter-Anmeldung
+confirmation-email-body =
+    Willkommen bei unserem Newsletter <br> Klicken Sie <a href="{ $link }">hier</a>, um Ihre Anmeldung zu bestätigen
+subscription-confirmed = Ihre Anmeldung wurde bestätigt. Vielen Dank!
+subscription-token-invalid = Dieser Bestätigungslink ist ungültig.
+subscription-already-confirmed = Ihre Anmeldung ist bereits bestätigt.
+subscription-token-expired = Dieser Bestätigungslink ist abgelaufen. Bitte melden Sie sich erneut an, um einen neuen zu erhalten.
+page-title = Newsletter
+subscribe-success = Vielen Dank für Ihre Anmeldung! Bitte bestätigen Sie sie über den Link in Ihrem Posteingang.
+subscribe-duplicate = Diese E-Mail-Adresse ist bereits angemeldet.
+subscribe-invalid = Bitte geben Sie einen gültigen Namen und eine gültige E-Mail-Adresse ein.
+subscribe-failed = Etwas ist schiefgelaufen, bitte versuchen Sie es später erneut.
+confirm-prompt = Bitte bestätigen Sie, dass Sie unseren Newsletter erhalten möchten.
+confirm-button = Anmeldung bestätigen
+preferences-email-subject = Ihr Newsletter-Abonnement verwalten
+preferences-email-body =
+    Klicken Sie <a href="{ $link }">hier</a>, um Ihr Newsletter-Abonnement zu verwalten. Der Link läuft bald ab, Sie können jederzeit einen neuen anfordern.
+preferences-request-prompt = Geben Sie Ihre E-Mail-Adresse ein und wir senden Ihnen einen Link, um Ihr Abonnement zu verwalten.
+preferences-email-label = E-Mail
+preferences-request-button = Link senden
+preferences-link-sent = Falls diese Adresse angemeldet ist, ist ein Link zur Verwaltung des Abonnements unterwegs.
+preferences-link-invalid = Dieser Link ist ungültig.
+preferences-link-expired = Dieser Link ist abgelaufen. Bitte fordern Sie einen neuen an.
+preferences-name-label = Name
+preferences-topics-label = Themen
+preferences-frequency-label = Wie oft
+frequency-daily = Täglich
+frequency-weekly = Wöchentlich
+frequency-monthly = Monatlich
+preferences-pause-label = Newsletter vorerst pausieren
+preferences-save-button = Speichern
+preferences-saved = Ihre Einstellungen wurden gespeichert.
+preferences-invalid = Bitte geben Sie einen gültigen Namen ein und wählen Sie, wie oft Sie von uns hören möchten.
+unsubscribe-button = Von allen abmelden
+unsubscribed = Sie wurden abgemeldet. Schade, dass Sie gehen.
+preferences-lists-label = Ihre Listen
+membership-confirmed = abonniert
+membership-pending = wartet auf Bestätigung
+membership-unsubscribed = abgemeldet
+unsubscribe-list-button = Diese Liste verlassen
+unsubscribed-list = Sie haben die Liste verlassen. Ihre anderen Abonnements bleiben bestehen.
+email-change-button = E-Mail-Adresse ändern
+email-change-invalid = Bitte geben Sie eine gültige E-Mail-Adresse ein, die sich von der aktuellen unterscheidet.
+email-change-taken = Diese E-Mail-Adresse ist bereits angemeldet.
+email-change-sent = Wir haben einen Link an die neue Adresse gesendet. Die Änderung wird wirksam, sobald Sie sie dort bestätigen.
+email-change-verify-subject = Bestätigen Sie Ihre neue E-Mail-Adresse
+email-change-verify-body =
+    Klicken Sie <a href="{ $link }">hier</a>, um unseren Newsletter künftig an diese Adresse zu erhalten.
+email-change-prompt = Bitte bestätigen Sie, dass Sie unseren Newsletter künftig an diese Adresse erhalten möchten.
+email-change-confirm-button = Neue Adresse bestätigen
+email-change-confirmed = Ihre E-Mail-Adresse wurde geändert.
+email-change-notice-subject = Ihre Newsletter-E-Mail-Adresse wurde geändert
+email-change-notice-body =
+    Das Newsletter-Abonnement dieser Adresse wurde auf { $email } übertragen. Falls Sie das nicht veranlasst haben, kontaktieren Sie uns bitte.
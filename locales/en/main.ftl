@@ -0,0 +1,54 @@
+confirmation-email-subject = Newsletter subscription
+confirmation-email-body =
+    Welcome to our newsletter <br> Click <a href="{ $link }">here</a> to confirm the subscription
+subscription-confirmed = Your subscription has been confirmed. Thank you!
+subscription-token-invalid = This confirmation link is not valid.
+subscription-already-confirmed = Your subscription is already confirmed.
+subscription-token-expired = This confirmation link has expired. Please subscribe again to get a new one.
+page-title = Newsletter
+subscribe-success = Thanks for subscribing! Please check your inbox to confirm the subscription.
+subscribe-duplicate = This email address is already subscribed.
+subscribe-invalid = Please enter a valid name and email address.
+subscribe-failed = Something went wrong, please try again later.
+confirm-prompt = Please confirm that you want to receive our newsletter.
+confirm-button = Confirm subscription
+preferences-email-subject = Manage your newsletter subscription
+preferences-email-body =
+    Click <a href="{ $link }">here</a> to manage your newsletter subscription. The link expires soon, you can always request a new one.
+preferences-request-prompt = Enter your email address and we will send you a link to manage your subscription.
+preferences-email-label = Email
+preferences-request-button = Send link
+preferences-link-sent = If that address is subscribed, a link to manage the subscription is on its way.
+preferences-link-invalid = This link is not valid.
+preferences-link-expired = This link has expired. Please request a new one.
+preferences-name-label = Name
+preferences-topics-label = Topics
+preferences-frequency-label = How often
+frequency-daily = Daily
+frequency-weekly = Weekly
+frequency-monthly = Monthly
+preferences-pause-label = Pause the newsletter for now
+preferences-save-button = Save
+preferences-saved = Your preferences have been saved.
+preferences-invalid = Please enter a valid name and pick how often you want to hear from us.
+unsubscribe-button = Unsubscribe from all
+unsubscribed = You have been unsubscribed. We are sorry to see you go.
+preferences-lists-label = Your lists
+membership-confirmed = subscribed
+membership-pending = waiting for confirmation
+membership-unsubscribed = unsubscribed
+unsubscribe-list-button = Leave this list
+unsubscribed-list = You have left the list. Your other subscriptions are unchanged.
+email-change-button = Change email
+email-change-invalid = Please enter a valid email address that differs from the current one.
+email-change-taken = This email address is already subscribed.
+email-change-sent = We sent a link to the new address. The change takes effect once you confirm it there.
+email-change-verify-subject = Confirm your new email address
+email-change-verify-body =
+    Click <a href="{ $link }">here</a> to receive our newsletter at this address from now on.
+email-change-prompt = Please confirm that you want to receive our newsletter at this address from now on.
+email-change-confirm-button = Confirm new address
+email-change-confirmed = Your email address has been changed.
+email-change-notice-subject = Your newsletter email address was changed
+email-change-notice-body =
+    The newsletter subscription for this address was moved to { $email }. If you did not ask for this, please contact us.
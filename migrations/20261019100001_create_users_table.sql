@@ -0,0 +1,7 @@
+CREATE TABLE users(
+    user_id uuid NOT NULL,
+    PRIMARY KEY (user_id),
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
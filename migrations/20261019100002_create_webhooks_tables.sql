@@ -0,0 +1,27 @@
+CREATE TABLE webhook_endpoints(
+    endpoint_id uuid NOT NULL,
+    PRIMARY KEY (endpoint_id),
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    events TEXT[] NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at timestamptz NOT NULL
+);
+CREATE TABLE webhook_deliveries(
+    delivery_id uuid NOT NULL,
+    PRIMARY KEY (delivery_id),
+    endpoint_id uuid NOT NULL
+        REFERENCES webhook_endpoints(endpoint_id) ON DELETE CASCADE,
+    event TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    status TEXT NOT NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at timestamptz NOT NULL,
+    last_response_status INT,
+    last_error TEXT,
+    created_at timestamptz NOT NULL,
+    delivered_at timestamptz
+);
+CREATE INDEX webhook_deliveries_due ON webhook_deliveries(next_attempt_at)
+    WHERE status = 'pending';
+CREATE INDEX webhook_deliveries_endpoint ON webhook_deliveries(endpoint_id, created_at);
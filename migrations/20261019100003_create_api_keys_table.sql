@@ -0,0 +1,12 @@
+CREATE TABLE api_keys(
+    key_id uuid NOT NULL,
+    PRIMARY KEY (key_id),
+    name TEXT NOT NULL,
+    prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    rate_limit_per_minute INT NOT NULL,
+    created_at timestamptz NOT NULL,
+    last_used_at timestamptz,
+    revoked_at timestamptz
+);
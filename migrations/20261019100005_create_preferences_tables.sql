@@ -0,0 +1,20 @@
+CREATE TABLE topics (
+    slug TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
+
+CREATE TABLE subscriber_preferences (
+    subscriber_id uuid PRIMARY KEY REFERENCES subscriptions (id) ON DELETE CASCADE,
+    frequency TEXT NOT NULL,
+    paused_at timestamptz,
+    updated_at timestamptz NOT NULL
+);
+
+CREATE TABLE subscriber_topics (
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    topic TEXT NOT NULL REFERENCES topics (slug) ON DELETE CASCADE,
+    PRIMARY KEY (subscriber_id, topic)
+);
+
+ALTER TABLE subscriptions ADD COLUMN unsubscribed_at timestamptz;
@@ -0,0 +1,8 @@
+CREATE TABLE email_changes (
+    token TEXT PRIMARY KEY,
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    new_email TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    confirmed_at timestamptz
+);
+CREATE INDEX email_changes_subscriber_id_idx ON email_changes (subscriber_id);
@@ -0,0 +1,32 @@
+CREATE TABLE lists (
+    list_id uuid PRIMARY KEY,
+    slug TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
+
+-- Everyone subscribed so far is on the one list there was.
+INSERT INTO lists (list_id, slug, name, created_at)
+VALUES (gen_random_uuid(), 'newsletter', 'Newsletter', now());
+
+CREATE TABLE list_memberships (
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    list_id uuid NOT NULL REFERENCES lists (list_id) ON DELETE CASCADE,
+    status TEXT NOT NULL,
+    subscribed_at timestamptz NOT NULL,
+    confirmed_at timestamptz,
+    unsubscribed_at timestamptz,
+    PRIMARY KEY (subscriber_id, list_id)
+);
+CREATE INDEX list_memberships_list_id_status_idx ON list_memberships (list_id, status);
+
+INSERT INTO list_memberships
+    (subscriber_id, list_id, status, subscribed_at, confirmed_at, unsubscribed_at)
+SELECT s.id, l.list_id, s.status, s.subscribed_at,
+    CASE WHEN s.status = 'confirmed' THEN s.subscribed_at END, s.unsubscribed_at
+FROM subscriptions s, lists l
+WHERE l.slug = 'newsletter';
+
+ALTER TABLE subscription_tokens ADD COLUMN list_id uuid REFERENCES lists (list_id) ON DELETE CASCADE;
+UPDATE subscription_tokens SET list_id = (SELECT list_id FROM lists WHERE slug = 'newsletter');
+ALTER TABLE subscription_tokens ALTER COLUMN list_id SET NOT NULL;
@@ -0,0 +1,28 @@
+-- Publications are defined in the configuration. Everything stored so far
+-- belongs to the one publication the deployment served.
+ALTER TABLE subscriptions ADD COLUMN publication_id TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE subscriptions ALTER COLUMN publication_id DROP DEFAULT;
+ALTER TABLE subscriptions DROP CONSTRAINT subscriptions_email_key;
+ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_publication_id_email_key
+    UNIQUE (publication_id, email);
+
+ALTER TABLE lists ADD COLUMN publication_id TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE lists ALTER COLUMN publication_id DROP DEFAULT;
+ALTER TABLE lists DROP CONSTRAINT lists_slug_key;
+ALTER TABLE lists ADD CONSTRAINT lists_publication_id_slug_key UNIQUE (publication_id, slug);
+
+ALTER TABLE subscriber_topics DROP CONSTRAINT subscriber_topics_topic_fkey;
+ALTER TABLE topics ADD COLUMN publication_id TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE topics ALTER COLUMN publication_id DROP DEFAULT;
+ALTER TABLE topics DROP CONSTRAINT topics_pkey;
+ALTER TABLE topics ADD PRIMARY KEY (publication_id, slug);
+ALTER TABLE subscriber_topics ADD COLUMN publication_id TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE subscriber_topics ALTER COLUMN publication_id DROP DEFAULT;
+ALTER TABLE subscriber_topics ADD FOREIGN KEY (publication_id, topic)
+    REFERENCES topics (publication_id, slug) ON DELETE CASCADE;
+
+ALTER TABLE api_keys ADD COLUMN publication_id TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE api_keys ALTER COLUMN publication_id DROP DEFAULT;
+
+ALTER TABLE webhook_endpoints ADD COLUMN publication_id TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE webhook_endpoints ALTER COLUMN publication_id DROP DEFAULT;
@@ -0,0 +1,12 @@
+ALTER TABLE subscriptions ADD COLUMN attributes JSONB NOT NULL DEFAULT '{}';
+CREATE INDEX subscriptions_attributes_idx ON subscriptions USING GIN (attributes jsonb_path_ops);
+
+CREATE TABLE attribute_definitions (
+    publication_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    required BOOLEAN NOT NULL,
+    allowed_values TEXT[],
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY (publication_id, name)
+);
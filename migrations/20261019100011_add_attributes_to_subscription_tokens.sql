@@ -0,0 +1,2 @@
+-- Attributes from a signup only reach the subscriber once its token is confirmed.
+ALTER TABLE subscription_tokens ADD COLUMN attributes JSONB NOT NULL DEFAULT '{}';
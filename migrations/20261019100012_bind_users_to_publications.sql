@@ -0,0 +1,9 @@
+-- Admins manage only the publications they are bound to. Existing admins
+-- keep the one publication the deployment served before.
+CREATE TABLE user_publications (
+    user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    publication_id TEXT NOT NULL,
+    PRIMARY KEY (user_id, publication_id)
+);
+INSERT INTO user_publications (user_id, publication_id)
+SELECT user_id, 'default' FROM users;
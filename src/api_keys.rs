@@ -0,0 +1,275 @@
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+pub const DEFAULT_RATE_LIMIT_PER_MINUTE: i32 = 60;
+
+const KEY_PREFIX: &str = "nl_";
+// Enough of the key to tell keys apart in listings without weakening them.
+const DISPLAYED_PREFIX_LENGTH: usize = KEY_PREFIX.len() + 6;
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyScope {
+    /// Create and confirm subscriptions
+    Subscribe,
+    /// Read subscribers
+    Read,
+    /// Everything the other scopes allow
+    Admin,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Subscribe => "subscribe",
+            Self::Read => "read",
+            Self::Admin => "admin",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "subscribe" => Some(Self::Subscribe),
+            "read" => Some(Self::Read),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema, Debug)]
+pub struct ApiKey {
+    pub key_id: Uuid,
+    pub name: String,
+    /// The start of the key, to recognise it by
+    #[schema(example = "nl_a1b2c3")]
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub rate_limit_per_minute: i32,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// A key that was presented with a request and is not revoked.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedKey {
+    pub key_id: Uuid,
+    pub publication_id: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub rate_limit_per_minute: i32,
+}
+
+impl AuthenticatedKey {
+    pub fn allows(&self, scope: ApiKeyScope) -> bool {
+        self.scopes
+            .iter()
+            .any(|s| *s == scope || *s == ApiKeyScope::Admin)
+    }
+}
+
+/// Creates a key and returns it in plain text. Only its hash is stored, so
+/// this is the only time it can be shown.
+#[tracing::instrument(name = "Creating an API key", skip(connection))]
+pub async fn create_key(
+    connection: &PgPool,
+    publication_id: &str,
+    name: &str,
+    scopes: &[ApiKeyScope],
+    rate_limit_per_minute: i32,
+) -> Result<(ApiKey, Secret<String>), String> {
+    if name.trim().is_empty() {
+        return Err(String::from("Name must not be empty"));
+    }
+    if scopes.is_empty() {
+        return Err(String::from("At least one scope is required"));
+    }
+    if rate_limit_per_minute <= 0 {
+        return Err(String::from("Rate limit must be positive"));
+    }
+
+    let secret: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(40)
+        .map(char::from)
+        .collect();
+    let key = Secret::new(format!("{}{}", KEY_PREFIX, secret));
+    let mut scopes: Vec<String> = scopes.iter().map(|s| s.as_str().to_owned()).collect();
+    scopes.sort();
+    scopes.dedup();
+    let api_key = ApiKey {
+        key_id: Uuid::new_v4(),
+        name: name.to_owned(),
+        prefix: key.expose_secret()[..DISPLAYED_PREFIX_LENGTH].to_owned(),
+        scopes,
+        rate_limit_per_minute,
+        created_at: Utc::now(),
+        last_used_at: None,
+        revoked_at: None,
+    };
+    sqlx::query!(
+        r#"INSERT INTO api_keys
+            (key_id, publication_id, name, prefix, key_hash, scopes, rate_limit_per_minute,
+                created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+        api_key.key_id,
+        publication_id,
+        api_key.name,
+        api_key.prefix,
+        hash_key(key.expose_secret()),
+        &api_key.scopes,
+        api_key.rate_limit_per_minute,
+        api_key.created_at
+    )
+    .execute(connection)
+    .await
+    .map_err(|e| format!("Failed to create API key: {}", e))?;
+
+    Ok((api_key, key))
+}
+
+#[tracing::instrument(name = "Listing API keys", skip(connection))]
+pub async fn list_keys(
+    connection: &PgPool,
+    publication_id: &str,
+) -> Result<Vec<ApiKey>, sqlx::Error> {
+    sqlx::query_as!(
+        ApiKey,
+        r#"SELECT key_id, name, prefix, scopes, rate_limit_per_minute, created_at,
+            last_used_at, revoked_at
+        FROM api_keys WHERE publication_id = $1 ORDER BY created_at"#,
+        publication_id
+    )
+    .fetch_all(connection)
+    .await
+}
+
+#[tracing::instrument(name = "Revoking an API key", skip(connection))]
+pub async fn revoke_key(
+    connection: &PgPool,
+    publication_id: &str,
+    key_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE api_keys SET revoked_at = $2
+        WHERE key_id = $1 AND publication_id = $3 AND revoked_at IS NULL"#,
+        key_id,
+        Utc::now(),
+        publication_id
+    )
+    .execute(connection)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Looks up an active key and records that it was used.
+#[tracing::instrument(name = "Authenticating an API key", skip_all)]
+pub async fn authenticate(
+    connection: &PgPool,
+    key: &Secret<String>,
+) -> Result<Option<AuthenticatedKey>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"UPDATE api_keys SET last_used_at = $2
+        WHERE key_hash = $1 AND revoked_at IS NULL
+        RETURNING key_id, publication_id, scopes, rate_limit_per_minute"#,
+        hash_key(key.expose_secret()),
+        Utc::now()
+    )
+    .fetch_optional(connection)
+    .await?;
+
+    Ok(row.map(|row| AuthenticatedKey {
+        key_id: row.key_id,
+        publication_id: row.publication_id,
+        scopes: row
+            .scopes
+            .iter()
+            .filter_map(|s| ApiKeyScope::parse(s))
+            .collect(),
+        rate_limit_per_minute: row.rate_limit_per_minute,
+    }))
+}
+
+// Keys are long and random, so a fast unsalted hash is enough and allows
+// looking them up directly.
+fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Fixed window request counter per key. Limits are per instance.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter(Arc<Mutex<HashMap<Uuid, (Instant, i32)>>>);
+
+impl RateLimiter {
+    /// Counts a request, returning how long to wait if the key is over its
+    /// limit.
+    pub fn check(&self, key: &AuthenticatedKey) -> Result<(), Duration> {
+        let mut windows = self.0.lock().expect("Rate limiter lock is poisoned");
+        let now = Instant::now();
+        windows.retain(|_, (started, _)| now.duration_since(*started) < RATE_LIMIT_WINDOW);
+
+        let (started, count) = windows.entry(key.key_id).or_insert((now, 0));
+        if *count >= key.rate_limit_per_minute {
+            return Err(RATE_LIMIT_WINDOW.saturating_sub(now.duration_since(*started)));
+        }
+        *count += 1;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api_keys::{hash_key, ApiKeyScope, AuthenticatedKey, RateLimiter};
+    use uuid::Uuid;
+
+    fn key(scopes: Vec<ApiKeyScope>, rate_limit_per_minute: i32) -> AuthenticatedKey {
+        AuthenticatedKey {
+            key_id: Uuid::new_v4(),
+            publication_id: String::from("default"),
+            scopes,
+            rate_limit_per_minute,
+        }
+    }
+
+    #[test]
+    fn admin_keys_have_every_scope() {
+        let subscribe = key(vec![ApiKeyScope::Subscribe], 1);
+        let admin = key(vec![ApiKeyScope::Admin], 1);
+
+        assert!(subscribe.allows(ApiKeyScope::Subscribe));
+        assert!(!subscribe.allows(ApiKeyScope::Read));
+        assert!(admin.allows(ApiKeyScope::Read));
+    }
+
+    #[test]
+    fn requests_over_the_limit_are_rejected_per_key() {
+        let limiter = RateLimiter::default();
+        let limited = key(vec![ApiKeyScope::Read], 2);
+        let other = key(vec![ApiKeyScope::Read], 2);
+
+        assert!(limiter.check(&limited).is_ok());
+        assert!(limiter.check(&limited).is_ok());
+        let retry_after = limiter.check(&limited).unwrap_err();
+        assert!(retry_after.as_secs() <= 60);
+        assert!(limiter.check(&other).is_ok());
+    }
+
+    #[test]
+    fn keys_are_hashed() {
+        assert_eq!(hash_key("nl_key").len(), 64);
+        assert_ne!(hash_key("nl_key"), hash_key("nl_kez"));
+    }
+}
@@ -0,0 +1,283 @@
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+
+/// Custom fields stored with a subscriber, by attribute name.
+pub type Attributes = Map<String, Value>;
+
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeType {
+    Text,
+    Number,
+    Boolean,
+}
+
+impl AttributeType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Number => "number",
+            Self::Boolean => "boolean",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "text" => Some(Self::Text),
+            "number" => Some(Self::Number),
+            "boolean" => Some(Self::Boolean),
+            _ => None,
+        }
+    }
+}
+
+/// An attribute the publication's subscribers may or must have.
+#[derive(serde::Serialize, utoipa::ToSchema, Debug, Clone)]
+pub struct AttributeDefinition {
+    #[schema(example = "country")]
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: AttributeType,
+    pub required: bool,
+    /// The only values a text attribute accepts, any when absent
+    #[schema(example = json!(["CZ", "DE"]))]
+    pub allowed_values: Option<Vec<String>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AttributeDefinition {
+    /// Forms and CSV files only carry text, so numbers and booleans are
+    /// accepted in their text form as well.
+    fn check(&self, value: Value) -> Result<Value, String> {
+        let value = match (self.kind, value) {
+            (AttributeType::Text, Value::String(s)) => Value::String(s),
+            (AttributeType::Number, Value::Number(n)) => Value::Number(n),
+            (AttributeType::Number, Value::String(s)) => s
+                .parse::<i64>()
+                .map(Value::from)
+                .ok()
+                .or_else(|| {
+                    s.parse::<f64>()
+                        .ok()
+                        .and_then(|f| f.is_finite().then_some(f.into()))
+                })
+                .ok_or_else(|| format!("{} must be a number", self.name))?,
+            (AttributeType::Boolean, Value::Bool(b)) => Value::Bool(b),
+            (AttributeType::Boolean, Value::String(s)) => match s.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                _ => return Err(format!("{} must be true or false", self.name)),
+            },
+            _ => return Err(format!("{} must be {}", self.name, self.kind.as_str())),
+        };
+        if let (Some(allowed), Value::String(s)) = (&self.allowed_values, &value) {
+            if !allowed.contains(s) {
+                return Err(format!(
+                    "{} must be one of {}",
+                    self.name,
+                    allowed.join(", ")
+                ));
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+/// Checks attributes against the publication's definitions, converting
+/// text values to the defined types. Empty values count as missing.
+pub fn validate(
+    definitions: &[AttributeDefinition],
+    attributes: Attributes,
+) -> Result<Attributes, String> {
+    let mut valid = Attributes::new();
+    for (name, value) in attributes {
+        if value.is_null() || value == "" {
+            continue;
+        }
+        let definition = definitions
+            .iter()
+            .find(|d| d.name == name)
+            .ok_or_else(|| format!("Unknown attribute {}", name))?;
+        valid.insert(name, definition.check(value)?);
+    }
+    if let Some(missing) = definitions
+        .iter()
+        .find(|d| d.required && !valid.contains_key(&d.name))
+    {
+        return Err(format!("{} is required", missing.name));
+    }
+
+    Ok(valid)
+}
+
+/// Like [`validate`], but for filtering, where required attributes may be
+/// left out.
+pub fn validate_filter(
+    definitions: &[AttributeDefinition],
+    attributes: Attributes,
+) -> Result<Attributes, String> {
+    attributes
+        .into_iter()
+        .map(|(name, value)| {
+            let definition = definitions
+                .iter()
+                .find(|d| d.name == name)
+                .ok_or_else(|| format!("Unknown attribute {}", name))?;
+            Ok((name, definition.check(value)?))
+        })
+        .collect()
+}
+
+#[tracing::instrument(name = "Listing attribute definitions", skip(executor))]
+pub async fn definitions<'c>(
+    executor: impl sqlx::PgExecutor<'c>,
+    publication_id: &str,
+) -> Result<Vec<AttributeDefinition>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT name, kind, required, allowed_values, created_at FROM attribute_definitions
+        WHERE publication_id = $1 ORDER BY name"#,
+        publication_id
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            Some(AttributeDefinition {
+                name: row.name,
+                kind: AttributeType::parse(&row.kind)?,
+                required: row.required,
+                allowed_values: row.allowed_values,
+                created_at: row.created_at,
+            })
+        })
+        .collect())
+}
+
+#[tracing::instrument(name = "Defining an attribute", skip(connection))]
+pub async fn create_definition(
+    connection: &PgPool,
+    publication_id: &str,
+    name: &str,
+    kind: AttributeType,
+    required: bool,
+    allowed_values: Option<Vec<String>>,
+) -> Result<AttributeDefinition, String> {
+    let valid_name = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid_name {
+        return Err(String::from(
+            "Name must be lowercase letters, digits and underscores",
+        ));
+    }
+    if let Some(allowed) = &allowed_values {
+        if kind != AttributeType::Text {
+            return Err(String::from(
+                "Only text attributes can limit their allowed values",
+            ));
+        }
+        if allowed.is_empty() || allowed.iter().any(|v| v.is_empty()) {
+            return Err(String::from("Allowed values must not be empty"));
+        }
+    }
+
+    let created_at = Utc::now();
+    let inserted = sqlx::query!(
+        r#"INSERT INTO attribute_definitions
+            (publication_id, name, kind, required, allowed_values, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (publication_id, name) DO NOTHING"#,
+        publication_id,
+        name,
+        kind.as_str(),
+        required,
+        allowed_values.as_deref(),
+        created_at
+    )
+    .execute(connection)
+    .await
+    .map_err(|e| format!("Failed to define attribute: {}", e))?;
+    if inserted.rows_affected() == 0 {
+        return Err(format!("Attribute {} already exists", name));
+    }
+
+    Ok(AttributeDefinition {
+        name: name.to_owned(),
+        kind,
+        required,
+        allowed_values,
+        created_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::attributes::{validate, validate_filter, AttributeDefinition, AttributeType};
+    use chrono::Utc;
+    use serde_json::json;
+
+    fn definitions() -> Vec<AttributeDefinition> {
+        let definition =
+            |name: &str, kind, required, allowed: Option<&[&str]>| AttributeDefinition {
+                name: name.to_owned(),
+                kind,
+                required,
+                allowed_values: allowed.map(|a| a.iter().map(|v| v.to_string()).collect()),
+                created_at: Utc::now(),
+            };
+        vec![
+            definition("country", AttributeType::Text, true, Some(&["CZ", "DE"])),
+            definition("seats", AttributeType::Number, false, None),
+            definition("trial", AttributeType::Boolean, false, None),
+        ]
+    }
+
+    fn attributes(value: serde_json::Value) -> super::Attributes {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn text_values_are_converted_to_the_defined_type() {
+        let valid = validate(
+            &definitions(),
+            attributes(json!({"country": "DE", "seats": "12", "trial": "true"})),
+        );
+
+        assert_eq!(
+            valid,
+            Ok(attributes(
+                json!({"country": "DE", "seats": 12, "trial": true})
+            ))
+        );
+    }
+
+    #[test]
+    fn invalid_attributes_are_rejected() {
+        for invalid in [
+            json!({"seats": 3}),
+            json!({"country": "US"}),
+            json!({"country": "DE", "seats": "many"}),
+            json!({"country": "DE", "trial": 1}),
+            json!({"country": "DE", "plan": "pro"}),
+            json!({"country": ""}),
+        ] {
+            assert!(
+                validate(&definitions(), attributes(invalid.clone())).is_err(),
+                "{} was accepted",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn filters_can_leave_out_required_attributes() {
+        let filter = validate_filter(&definitions(), attributes(json!({"seats": "3"})));
+
+        assert_eq!(filter, Ok(attributes(json!({"seats": 3}))));
+    }
+}
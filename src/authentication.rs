@@ -0,0 +1,160 @@
+use crate::telemetry::describe_db_error;
+use argon2::{
+    password_hash::{rand_core::OsRng, SaltString},
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+};
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const MIN_PASSWORD_LENGTH: usize = 12;
+
+// Verified against when the user does not exist, so unknown usernames take as
+// long to reject as wrong passwords.
+const FALLBACK_PASSWORD_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$\
+    gZiV/M1gPc22ElAH/Jh1Hw$CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno";
+
+#[derive(Debug)]
+pub enum AuthError {
+    InvalidCredentials,
+    Unexpected(String),
+}
+
+pub fn hash_password(password: &Secret<String>) -> Result<Secret<String>, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.expose_secret().as_bytes(), &salt)
+        .map(|hash| Secret::new(hash.to_string()))
+        .map_err(|e| format!("Failed to hash password: {}", e))
+}
+
+#[tracing::instrument(name = "Validating credentials", skip(connection, password))]
+pub async fn validate_credentials(
+    connection: &PgPool,
+    username: &str,
+    password: Secret<String>,
+) -> Result<Uuid, AuthError> {
+    let stored = sqlx::query!(
+        "SELECT user_id, password_hash FROM users WHERE username = $1",
+        username
+    )
+    .fetch_optional(connection)
+    .await
+    .map_err(|e| AuthError::Unexpected(describe_db_error(&e)))?;
+
+    let (user_id, password_hash) = match stored {
+        Some(row) => (Some(row.user_id), Secret::new(row.password_hash)),
+        None => (None, Secret::new(FALLBACK_PASSWORD_HASH.to_owned())),
+    };
+    let current_span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || {
+        current_span.in_scope(|| verify_password_hash(&password_hash, &password))
+    })
+    .await
+    .map_err(|e| AuthError::Unexpected(e.to_string()))??;
+
+    user_id.ok_or(AuthError::InvalidCredentials)
+}
+
+fn verify_password_hash(
+    password_hash: &Secret<String>,
+    password: &Secret<String>,
+) -> Result<(), AuthError> {
+    let password_hash = PasswordHash::new(password_hash.expose_secret())
+        .map_err(|e| AuthError::Unexpected(e.to_string()))?;
+    Argon2::default()
+        .verify_password(password.expose_secret().as_bytes(), &password_hash)
+        .map_err(|_| AuthError::InvalidCredentials)
+}
+
+/// Whether the user administers the publication.
+#[tracing::instrument(name = "Checking publication access", skip(connection))]
+pub async fn manages_publication(
+    connection: &PgPool,
+    user_id: Uuid,
+    publication_id: &str,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM user_publications WHERE user_id = $1 AND publication_id = $2
+        ) AS "exists!""#,
+        user_id,
+        publication_id
+    )
+    .fetch_one(connection)
+    .await
+}
+
+/// Creates an admin of the given publications.
+#[tracing::instrument(name = "Creating a user", skip(connection, password))]
+pub async fn create_user(
+    connection: &PgPool,
+    username: &str,
+    password: &Secret<String>,
+    publications: &[&str],
+) -> Result<Uuid, String> {
+    if username.trim().is_empty() {
+        return Err(String::from("Username must not be empty"));
+    }
+    if publications.is_empty() {
+        return Err(String::from("An admin needs at least one publication"));
+    }
+    if password.expose_secret().chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(format!(
+            "Password must be at least {} characters long",
+            MIN_PASSWORD_LENGTH
+        ));
+    }
+
+    let user_id = Uuid::new_v4();
+    let password_hash = hash_password(password)?;
+    let mut transaction = connection
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to create user: {}", e))?;
+    sqlx::query!(
+        r#"INSERT INTO users (user_id, username, password_hash, created_at)
+        VALUES ($1, $2, $3, $4)"#,
+        user_id,
+        username,
+        password_hash.expose_secret(),
+        Utc::now()
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => {
+            format!("User {} already exists", username)
+        }
+        e => format!("Failed to create user: {}", e),
+    })?;
+    for publication_id in publications {
+        sqlx::query!(
+            r#"INSERT INTO user_publications (user_id, publication_id) VALUES ($1, $2)
+            ON CONFLICT DO NOTHING"#,
+            user_id,
+            publication_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| format!("Failed to create user: {}", e))?;
+    }
+    transaction
+        .commit()
+        .await
+        .map_err(|e| format!("Failed to create user: {}", e))?;
+
+    Ok(user_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::authentication::FALLBACK_PASSWORD_HASH;
+    use argon2::PasswordHash;
+
+    #[test]
+    fn fallback_hash_is_well_formed() {
+        assert!(PasswordHash::new(FALLBACK_PASSWORD_HASH).is_ok());
+    }
+}
@@ -0,0 +1,264 @@
+mod subscribers;
+
+use crate::{
+    authentication::create_user,
+    config::Settings,
+    domain::Email,
+    metrics::Metrics,
+    publications::DEFAULT_PUBLICATION,
+    shutdown::{trigger_on_signal, ShutdownHandle},
+    startup::{get_connection_pool, run_migrations, Application},
+    workers::{Heartbeats, Workers},
+};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use std::{fs::File, io::BufRead, path::PathBuf, time::Duration};
+
+pub(crate) use subscribers::csv_reader;
+pub use subscribers::{export_subscribers, import_subscribers, ImportSummary, SubscriberStatus};
+
+#[derive(clap::Parser, Debug)]
+#[command(
+    name = "newsletter",
+    version,
+    about = "Newsletter service and operational tasks"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Same as the `check-config` subcommand
+    #[arg(long, hide = true)]
+    check_config: bool,
+}
+
+impl Cli {
+    pub fn command(self) -> Command {
+        match self.command {
+            _ if self.check_config => Command::CheckConfig,
+            Some(command) => command,
+            None => Command::Serve,
+        }
+    }
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum Command {
+    /// Run the HTTP server together with the background workers (default)
+    Serve,
+    /// Apply pending database migrations
+    Migrate,
+    /// Create an admin user, generating a password unless one is piped in
+    CreateAdmin {
+        #[arg(long)]
+        username: String,
+        /// Read the password from the first line of stdin
+        #[arg(long)]
+        password_stdin: bool,
+        /// Publications the admin manages, repeatable
+        #[arg(long = "publication", default_value = DEFAULT_PUBLICATION)]
+        publications: Vec<String>,
+    },
+    /// Import subscribers from a CSV file with `email,name[,locale]` columns,
+    /// further columns being attributes
+    ImportSubscribers {
+        file: PathBuf,
+        #[arg(long, value_enum, default_value = "confirmed")]
+        status: SubscriberStatus,
+        #[arg(long, default_value = DEFAULT_PUBLICATION)]
+        publication: String,
+    },
+    /// Export subscribers as CSV
+    ExportSubscribers {
+        /// Write to a file instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+        #[arg(long, value_enum)]
+        status: Option<SubscriberStatus>,
+        #[arg(long, default_value = DEFAULT_PUBLICATION)]
+        publication: String,
+    },
+    /// Send an email through a publication's provider
+    SendTestEmail {
+        to: String,
+        #[arg(long, default_value = DEFAULT_PUBLICATION)]
+        publication: String,
+    },
+    /// Validate the configuration and exit
+    CheckConfig,
+    /// Run the background workers without the HTTP server
+    Worker,
+}
+
+impl Command {
+    /// Long running commands log to the configured sink, one-off commands keep
+    /// stdout free for their output.
+    pub fn is_long_running(&self) -> bool {
+        matches!(self, Self::Serve | Self::Worker)
+    }
+}
+
+pub async fn run(command: Command, config: Settings) -> Result<(), String> {
+    match command {
+        Command::Serve => serve(config).await,
+        Command::Migrate => migrate(config).await,
+        Command::CreateAdmin {
+            username,
+            password_stdin,
+            publications,
+        } => create_admin(config, &username, password_stdin, &publications).await,
+        Command::ImportSubscribers {
+            file,
+            status,
+            publication,
+        } => {
+            check_publication(&config, &publication)?;
+            let input = File::open(&file)
+                .map_err(|e| format!("Failed to open {}: {}", file.display(), e))?;
+            let pool = get_connection_pool(&config.database);
+            let summary = import_subscribers(&pool, &publication, input, status).await?;
+            for (line, error) in &summary.errors {
+                eprintln!("line {}: {}", line, error);
+            }
+            println!(
+                "Imported {} subscribers, skipped {} duplicates and {} invalid rows",
+                summary.imported,
+                summary.duplicates,
+                summary.errors.len()
+            );
+            Ok(())
+        }
+        Command::ExportSubscribers {
+            output,
+            status,
+            publication,
+        } => {
+            check_publication(&config, &publication)?;
+            let pool = get_connection_pool(&config.database);
+            let exported = match output {
+                Some(path) => {
+                    let file = File::create(&path)
+                        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+                    export_subscribers(&pool, &publication, file, status).await?
+                }
+                None => export_subscribers(&pool, &publication, std::io::stdout(), status).await?,
+            };
+            eprintln!("Exported {} subscribers", exported);
+            Ok(())
+        }
+        Command::SendTestEmail { to, publication } => {
+            send_test_email(config, to, &publication).await
+        }
+        Command::CheckConfig => {
+            println!("Configuration is valid");
+            Ok(())
+        }
+        Command::Worker => worker(config).await,
+    }
+}
+
+async fn serve(config: Settings) -> Result<(), String> {
+    let app = Application::build(config)
+        .await
+        .map_err(|e| e.to_string())?;
+    tokio::spawn(trigger_on_signal(app.shutdown_handle()));
+
+    app.run_until_stopped().await.map_err(|e| e.to_string())
+}
+
+async fn migrate(config: Settings) -> Result<(), String> {
+    let pool = get_connection_pool(&config.database);
+    run_migrations(&pool).await?;
+    println!("Database is up to date");
+
+    Ok(())
+}
+
+async fn create_admin(
+    config: Settings,
+    username: &str,
+    password_stdin: bool,
+    publications: &[String],
+) -> Result<(), String> {
+    for publication in publications {
+        check_publication(&config, publication)?;
+    }
+    let (password, generated) = if password_stdin {
+        let mut line = String::new();
+        std::io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| format!("Failed to read password: {}", e))?;
+        (
+            Secret::new(line.trim_end_matches(['\r', '\n']).to_owned()),
+            false,
+        )
+    } else {
+        let password: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(24)
+            .map(char::from)
+            .collect();
+        (Secret::new(password), true)
+    };
+
+    let pool = get_connection_pool(&config.database);
+    let publications: Vec<_> = publications.iter().map(String::as_str).collect();
+    create_user(&pool, username, &password, &publications).await?;
+    println!("Created admin {}", username);
+    if generated {
+        println!("Password: {}", password.expose_secret());
+    }
+
+    Ok(())
+}
+
+fn check_publication(config: &Settings, publication: &str) -> Result<(), String> {
+    if publication != DEFAULT_PUBLICATION && !config.publications.contains_key(publication) {
+        return Err(format!("Unknown publication {}", publication));
+    }
+    Ok(())
+}
+
+async fn send_test_email(config: Settings, to: String, publication: &str) -> Result<(), String> {
+    let recipient = Email::parse(to)?;
+    let email_client = match config.publications.get(publication) {
+        Some(publication) => &publication.email_client,
+        None if publication == DEFAULT_PUBLICATION => &config.email_client,
+        None => return Err(format!("Unknown publication {}", publication)),
+    };
+    email_client
+        .client()?
+        .send_email(
+            recipient,
+            "Test email",
+            "This is a test email sent from the newsletter CLI.",
+        )
+        .await
+        .map_err(|e| format!("Failed to send email: {}", e))?;
+    println!("Email sent");
+
+    Ok(())
+}
+
+async fn worker(config: Settings) -> Result<(), String> {
+    let pool = get_connection_pool(&config.database);
+    let shutdown = ShutdownHandle::default();
+    let workers = Workers::spawn(
+        &pool,
+        &config.webhooks,
+        &Metrics::new(),
+        &Heartbeats::default(),
+        &shutdown,
+    );
+    tracing::info!("Workers started");
+
+    trigger_on_signal(shutdown).await;
+    workers
+        .wait(Duration::from_secs(
+            config.application.shutdown_timeout_secs,
+        ))
+        .await;
+    tracing::info!("Shutdown complete");
+
+    Ok(())
+}
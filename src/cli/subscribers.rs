@@ -0,0 +1,188 @@
+use crate::attributes;
+use crate::domain::{Email, Locale, SubscriberName};
+use crate::lists::{self, DEFAULT_LIST};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::io::{Read, Write};
+use uuid::Uuid;
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum SubscriberStatus {
+    Confirmed,
+    PendingConfirmation,
+}
+
+impl SubscriberStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Confirmed => "confirmed",
+            Self::PendingConfirmation => "pending_confirmation",
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    pub imported: u64,
+    pub duplicates: u64,
+    /// The skipped rows, by line number, and what's wrong with them
+    pub errors: Vec<(usize, String)>,
+}
+
+#[derive(serde::Deserialize)]
+struct ImportRow {
+    email: String,
+    name: String,
+    locale: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct ExportRow {
+    email: String,
+    name: String,
+    locale: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    /// Separated by spaces, which tags can't contain
+    tags: String,
+}
+
+/// Reads CSV with a header row, as imports and uploads come from spreadsheets.
+pub(crate) fn csv_reader<R: Read>(input: R) -> csv::Reader<R> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(input)
+}
+
+/// Imports `email,name[,locale]` rows, any further columns being attributes.
+/// Invalid rows are skipped and listed in the summary, existing emails are left untouched.
+#[tracing::instrument(name = "Importing subscribers", skip(connection, input))]
+pub async fn import_subscribers(
+    connection: &PgPool,
+    publication_id: &str,
+    input: impl Read,
+    status: SubscriberStatus,
+) -> Result<ImportSummary, String> {
+    let mut reader = csv_reader(input);
+    let mut summary = ImportSummary::default();
+    let mut transaction = connection.begin().await.map_err(|e| e.to_string())?;
+    lists::ensure_default_list(&mut *transaction, publication_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let definitions = attributes::definitions(&mut *transaction, publication_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+
+    for (line, record) in reader.records().enumerate() {
+        // Line 1 holds the headers.
+        let line = line + 2;
+        let parsed = record.map_err(|e| e.to_string()).and_then(|record| {
+            let row: ImportRow = record
+                .deserialize(Some(&headers))
+                .map_err(|e| e.to_string())?;
+            let email = Email::parse(row.email)?;
+            let name = SubscriberName::parse(row.name)?;
+            let locale = match row.locale.filter(|l| !l.is_empty()) {
+                Some(locale) => Locale::parse(locale)?,
+                None => Locale::default(),
+            };
+            let attributes = headers
+                .iter()
+                .zip(record.iter())
+                .filter(|(column, _)| !["email", "name", "locale"].contains(column))
+                .map(|(column, value)| (column.to_owned(), Value::String(value.to_owned())))
+                .collect();
+            let attributes = attributes::validate(&definitions, attributes)?;
+            Ok((email, name, locale, attributes))
+        });
+        let (email, name, locale, attributes) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                summary.errors.push((line, e));
+                continue;
+            }
+        };
+
+        // Imported subscribers land on the default list with the same status.
+        let result = sqlx::query!(
+            r#"WITH subscriber AS (
+                INSERT INTO subscriptions
+                    (id, publication_id, email, name, subscribed_at, status, locale, attributes)
+                VALUES ($1, $8, $2, $3, $4, $5, $6, $9)
+                ON CONFLICT (publication_id, email) DO NOTHING
+                RETURNING id, subscribed_at, status
+            )
+            INSERT INTO list_memberships
+                (subscriber_id, list_id, status, subscribed_at, confirmed_at)
+            SELECT s.id, l.list_id, s.status, s.subscribed_at,
+                CASE WHEN s.status = 'confirmed' THEN s.subscribed_at END
+            FROM subscriber s, lists l WHERE l.publication_id = $8 AND l.slug = $7"#,
+            Uuid::new_v4(),
+            email.as_ref(),
+            name.as_ref(),
+            Utc::now(),
+            status.as_str(),
+            locale.as_ref(),
+            DEFAULT_LIST,
+            publication_id,
+            Value::Object(attributes)
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| format!("line {}: {}", line, e))?;
+
+        if result.rows_affected() == 0 {
+            summary.duplicates += 1;
+        } else {
+            summary.imported += 1;
+        }
+    }
+
+    transaction.commit().await.map_err(|e| e.to_string())?;
+
+    Ok(summary)
+}
+
+#[tracing::instrument(name = "Exporting subscribers", skip(connection, output))]
+pub async fn export_subscribers(
+    connection: &PgPool,
+    publication_id: &str,
+    output: impl Write,
+    status: Option<SubscriberStatus>,
+) -> Result<u64, String> {
+    let rows = sqlx::query!(
+        r#"SELECT s.email, s.name, s.locale, s.status, s.subscribed_at,
+            ARRAY(SELECT t.tag FROM subscriber_tags t WHERE t.subscriber_id = s.id ORDER BY t.tag)
+                AS "tags!"
+        FROM subscriptions s
+        WHERE s.publication_id = $2 AND ($1::TEXT IS NULL OR s.status = $1)
+        ORDER BY s.subscribed_at"#,
+        status.map(|s| s.as_str()),
+        publication_id
+    )
+    .fetch_all(connection)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut writer = csv::Writer::from_writer(output);
+    let mut exported = 0;
+    for row in rows {
+        writer
+            .serialize(ExportRow {
+                email: row.email,
+                name: row.name,
+                locale: row.locale,
+                status: row.status,
+                subscribed_at: row.subscribed_at,
+                tags: row.tags.join(" "),
+            })
+            .map_err(|e| e.to_string())?;
+        exported += 1;
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+
+    Ok(exported)
+}
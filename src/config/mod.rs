@@ -0,0 +1,523 @@
+mod validation;
+
+use crate::domain::Email;
+use crate::email_client::{EmailClient, SenderIdentity};
+use crate::telemetry::Redaction;
+use actix_cors::Cors;
+use chrono::{DateTime, Utc};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions, PgSslMode};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+pub use validation::{validate, ConfigProblem};
+
+#[derive(serde::Deserialize, Clone)]
+pub struct Settings {
+    pub database: DatabaseSettings,
+    pub application: ApplicationSettings,
+    pub email_client: EmailClientSettings,
+    pub health: HealthSettings,
+    pub metrics: MetricsSettings,
+    pub telemetry: TelemetrySettings,
+    pub api_docs: ApiDocsSettings,
+    #[serde(default)]
+    pub api: ApiSettings,
+    pub webhooks: WebhookSettings,
+    pub cors: CorsSettings,
+    pub confirmation: ConfirmationSettings,
+    pub preferences: PreferenceSettings,
+    /// Further publications served by this deployment, by id. The
+    /// `application.base_url` and `email_client` settings make up the default
+    /// one.
+    #[serde(default)]
+    pub publications: BTreeMap<String, PublicationSettings>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct DatabaseSettings {
+    pub username: String,
+    pub password: String,
+    pub port: u16,
+    pub host: String,
+    pub database_name: String,
+    pub require_ssl: bool,
+    pub ssl_root_cert: Option<String>,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout_secs: u64,
+    pub idle_timeout_secs: Option<u64>,
+    pub statement_timeout_ms: Option<u64>,
+    pub connect_eagerly: bool,
+    pub migrate_on_startup: bool,
+    pub connect_retries: u32,
+    pub connect_backoff_ms: u64,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ApplicationSettings {
+    pub host: String,
+    pub port: u16,
+    pub base_url: String,
+    pub shutdown_timeout_secs: u64,
+    /// IP addresses
+    pub trusted_proxies: Vec<String>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct EmailClientSettings {
+    pub url: String,
+    pub sender: String,
+    pub sender_name: Option<String>,
+    pub reply_to: Option<String>,
+    pub auth_token: Secret<String>,
+}
+
+/// A publication hosted for a client, with its own domain and sender.
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct PublicationSettings {
+    /// Hosts its pages and forms are served on, without the port
+    pub hosts: Vec<String>,
+    pub base_url: String,
+    pub email_client: EmailClientSettings,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct HealthSettings {
+    pub check_email_provider: bool,
+    pub probe_timeout_ms: u64,
+    pub worker_heartbeat_timeout_secs: u64,
+}
+
+/// `/metrics` is served on its own listener, separate from the application.
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct MetricsSettings {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ApiDocsSettings {
+    pub swagger_ui: bool,
+}
+
+/// Cross-origin access to the public subscription routes, for signup forms
+/// embedded on other sites.
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct CorsSettings {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub max_age_secs: usize,
+}
+
+impl CorsSettings {
+    pub fn middleware(&self) -> Cors {
+        let mut cors = Cors::default()
+            .allowed_methods(self.allowed_methods.iter().map(String::as_str))
+            .allowed_headers(self.allowed_headers.iter().map(String::as_str))
+            .max_age(self.max_age_secs);
+        for origin in &self.allowed_origins {
+            cors = cors.allowed_origin(origin);
+        }
+
+        cors
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct WebhookSettings {
+    pub poll_interval_ms: u64,
+    pub timeout_ms: u64,
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every further attempt
+    pub retry_backoff_ms: u64,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ConfirmationSettings {
+    pub token_ttl_hours: u64,
+    /// Submit the confirmation form with JavaScript instead of waiting for
+    /// the button. Scanners that run scripts would confirm again.
+    pub auto_submit: bool,
+    #[serde(default)]
+    pub redirects: PageRedirects,
+}
+
+impl ConfirmationSettings {
+    pub fn token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.token_ttl_hours as i64)
+    }
+}
+
+/// Magic links to the preference center. Changing the key invalidates every
+/// link sent so far.
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct PreferenceSettings {
+    pub signing_key: Secret<String>,
+    pub link_ttl_hours: u64,
+}
+
+impl PreferenceSettings {
+    pub fn link_ttl(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.link_ttl_hours as i64)
+    }
+}
+
+/// Where browsers are sent instead of the built-in page for each outcome,
+/// e.g. a page on the marketing site. Unset outcomes render the page.
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+pub struct PageRedirects {
+    pub confirmed: Option<String>,
+    pub already_confirmed: Option<String>,
+    pub token_expired: Option<String>,
+    pub token_invalid: Option<String>,
+    pub subscribed: Option<String>,
+    pub already_subscribed: Option<String>,
+    pub subscribe_invalid: Option<String>,
+    pub subscribe_failed: Option<String>,
+}
+
+/// Announces the retirement of `/api/v1` through `Deprecation` and `Sunset`
+/// headers once the dates are set.
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+pub struct ApiSettings {
+    pub v1_deprecated_at: Option<DateTime<Utc>>,
+    pub v1_sunset: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct TelemetrySettings {
+    pub service_name: String,
+    pub log_level: String,
+    pub format: LogFormat,
+    pub sink: LogSink,
+    pub log_directory: String,
+    pub redaction: Redaction,
+    pub otlp_endpoint: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Bunyan,
+    Pretty,
+}
+
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogSink {
+    Stdout,
+    File,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Enviroment {
+    Local,
+    Test,
+    Staging,
+    Production,
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Environment(String),
+    Load(config::ConfigError),
+    Invalid(Vec<ConfigProblem>),
+}
+
+impl DatabaseSettings {
+    pub fn without_db(&self) -> PgConnectOptions {
+        let ssl_mode = match (self.require_ssl, &self.ssl_root_cert) {
+            (true, Some(_)) => PgSslMode::VerifyFull,
+            (true, None) => PgSslMode::Require,
+            (false, _) => PgSslMode::Prefer,
+        };
+
+        let mut options = PgConnectOptions::new()
+            .host(&self.host)
+            .port(self.port)
+            .username(&self.username)
+            .password(&self.password)
+            .ssl_mode(ssl_mode);
+        if let Some(cert) = &self.ssl_root_cert {
+            options = options.ssl_root_cert(cert);
+        }
+        if let Some(timeout) = self.statement_timeout_ms {
+            options = options.options([("statement_timeout", format!("{}ms", timeout))]);
+        }
+
+        options
+    }
+
+    pub fn with_db(&self) -> PgConnectOptions {
+        self.without_db().database(&self.database_name)
+    }
+
+    pub fn pool_options(&self) -> PgPoolOptions {
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(Duration::from_secs(self.acquire_timeout_secs))
+            .idle_timeout(self.idle_timeout_secs.map(Duration::from_secs))
+    }
+}
+
+impl EmailClientSettings {
+    pub fn sender(&self) -> Result<Email, String> {
+        Email::parse(self.sender.clone())
+    }
+
+    pub fn reply_to(&self) -> Result<Option<Email>, String> {
+        self.reply_to.clone().map(Email::parse).transpose()
+    }
+
+    pub fn sender_identity(&self) -> Result<SenderIdentity, String> {
+        let name = match self.sender_name.as_deref().map(str::trim) {
+            Some("") => return Err(String::from("sender_name must not be empty")),
+            name => name.map(String::from),
+        };
+
+        Ok(SenderIdentity {
+            email: self.sender()?,
+            name,
+            reply_to: self.reply_to()?,
+        })
+    }
+
+    pub fn client(&self) -> Result<EmailClient, String> {
+        if self.auth_token.expose_secret().trim().is_empty() {
+            return Err(String::from("auth_token must not be empty"));
+        }
+
+        Ok(EmailClient::new(
+            self.url.clone(),
+            self.sender_identity()?,
+            self.auth_token.clone(),
+        ))
+    }
+}
+
+impl Enviroment {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Enviroment::Local => "local",
+            Enviroment::Test => "test",
+            Enviroment::Staging => "staging",
+            Enviroment::Production => "production",
+        }
+    }
+}
+
+impl std::fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingsError::Environment(e) => write!(f, "Invalid APP_ENVIRONMENT: {}", e),
+            SettingsError::Load(e) => write!(f, "Failed to load configuration: {}", e),
+            SettingsError::Invalid(problems) => {
+                writeln!(f, "Found {} configuration problem(s):", problems.len())?;
+                for problem in problems {
+                    writeln!(f, "  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl TryFrom<String> for Enviroment {
+    type Error = String;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "local" => Ok(Self::Local),
+            "test" => Ok(Self::Test),
+            "staging" => Ok(Self::Staging),
+            "production" => Ok(Self::Production),
+            other => Err(format!("{} is not a valid enviroment", other)),
+        }
+    }
+}
+
+const EMBEDDED_CONFIG: [(&str, &str); 5] = [
+    ("base", include_str!("../../configuration/base.yaml")),
+    ("local", include_str!("../../configuration/local.yaml")),
+    ("test", include_str!("../../configuration/test.yaml")),
+    ("staging", include_str!("../../configuration/staging.yaml")),
+    (
+        "production",
+        include_str!("../../configuration/production.yaml"),
+    ),
+];
+
+pub fn get_config() -> Result<Settings, SettingsError> {
+    let config_dir = match std::env::var("APP_CONFIG_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => std::env::current_dir()
+            .map_err(|e| SettingsError::Load(config::ConfigError::Foreign(Box::new(e))))?
+            .join("configuration"),
+    };
+    let enviroment: Enviroment = std::env::var("APP_ENVIRONMENT")
+        .unwrap_or_else(|_| "local".into())
+        .try_into()
+        .map_err(SettingsError::Environment)?;
+
+    get_config_from_dir(&config_dir, enviroment)
+}
+
+pub fn get_config_from_dir(
+    config_dir: &Path,
+    enviroment: Enviroment,
+) -> Result<Settings, SettingsError> {
+    let env_file = format!("{}.yaml", enviroment.as_str());
+    let override_file = format!("{}.override.yaml", enviroment.as_str());
+    let builder = config::Config::builder()
+        .add_source(config::File::from(config_dir.join("base.yaml")))
+        .add_source(config::File::from(config_dir.join(env_file)))
+        .add_source(config::File::from(config_dir.join(override_file)).required(false));
+
+    load(builder, enviroment)
+}
+
+pub fn get_embedded_config(enviroment: Enviroment) -> Result<Settings, SettingsError> {
+    let embedded = |name: &str| {
+        let (_, contents) = EMBEDDED_CONFIG
+            .iter()
+            .find(|(file, _)| *file == name)
+            .expect("Every enviroment has an embedded configuration file");
+        config::File::from_str(contents, config::FileFormat::Yaml)
+    };
+    let builder = config::Config::builder()
+        .add_source(embedded("base"))
+        .add_source(embedded(enviroment.as_str()));
+
+    load(builder, enviroment)
+}
+
+fn load(
+    builder: config::ConfigBuilder<config::builder::DefaultState>,
+    enviroment: Enviroment,
+) -> Result<Settings, SettingsError> {
+    let settings = builder
+        .add_source(
+            config::Environment::with_prefix("APP")
+                .prefix_separator("_")
+                .separator("__")
+                .try_parsing(true)
+                .list_separator(",")
+                .with_list_parse_key("application.trusted_proxies")
+                .with_list_parse_key("cors.allowed_origins")
+                .with_list_parse_key("cors.allowed_methods")
+                .with_list_parse_key("cors.allowed_headers"),
+        )
+        .build()
+        .map_err(SettingsError::Load)?;
+
+    let problems = validate(&settings, enviroment);
+    if !problems.is_empty() {
+        return Err(SettingsError::Invalid(problems));
+    }
+
+    settings
+        .try_deserialize::<Settings>()
+        .map_err(SettingsError::Load)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{
+        get_config_from_dir, get_embedded_config, EmailClientSettings, Enviroment, SettingsError,
+    };
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn email_client_settings() -> EmailClientSettings {
+        EmailClientSettings {
+            url: String::from("http://127.0.0.1"),
+            sender: String::from("test@email.com"),
+            sender_name: Some(String::from("Newsletter")),
+            reply_to: None,
+            auth_token: Secret::new(String::from("token")),
+        }
+    }
+
+    #[test]
+    fn valid_email_client_settings_build_a_client() {
+        assert!(email_client_settings().client().is_ok());
+    }
+
+    #[test]
+    fn empty_auth_token_is_rejected() {
+        let settings = EmailClientSettings {
+            auth_token: Secret::new(String::from("  ")),
+            ..email_client_settings()
+        };
+        assert!(settings.client().is_err());
+    }
+
+    #[test]
+    fn invalid_sender_or_reply_to_is_rejected() {
+        let settings = EmailClientSettings {
+            sender: String::from("not-an-email"),
+            ..email_client_settings()
+        };
+        assert!(settings.client().is_err());
+
+        let settings = EmailClientSettings {
+            reply_to: Some(String::from("not-an-email")),
+            ..email_client_settings()
+        };
+        assert!(settings.client().is_err());
+    }
+
+    #[test]
+    fn embedded_local_and_test_configuration_is_valid() {
+        assert!(get_embedded_config(Enviroment::Local).is_ok());
+        let settings = get_embedded_config(Enviroment::Test).expect("Failed to load config");
+        assert_eq!(settings.application.port, 0);
+    }
+
+    #[test]
+    fn embedded_production_configuration_requires_secrets() {
+        let result = get_embedded_config(Enviroment::Production);
+        assert!(matches!(result, Err(SettingsError::Invalid(_))));
+    }
+
+    #[test]
+    fn override_file_takes_precedence_over_environment_file() {
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&dir).unwrap();
+        let base = include_str!("../../configuration/base.yaml");
+        let local = include_str!("../../configuration/local.yaml");
+        std::fs::write(dir.join("base.yaml"), base).unwrap();
+        std::fs::write(dir.join("local.yaml"), local).unwrap();
+        std::fs::write(
+            dir.join("local.override.yaml"),
+            "application:\n  port: 9999\n",
+        )
+        .unwrap();
+
+        let settings = get_config_from_dir(&dir, Enviroment::Local);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            settings.expect("Failed to load config").application.port,
+            9999
+        );
+    }
+
+    #[test]
+    fn enviroment_names_round_trip() {
+        for env in [
+            Enviroment::Local,
+            Enviroment::Test,
+            Enviroment::Staging,
+            Enviroment::Production,
+        ] {
+            assert_eq!(Enviroment::try_from(env.as_str().to_owned()), Ok(env));
+        }
+    }
+}
@@ -0,0 +1,810 @@
+use crate::config::Enviroment;
+use crate::domain::Email;
+use crate::publications::DEFAULT_PUBLICATION;
+use config::{Config, Value};
+
+const ENVIRONMENT_ORIGIN: &str = "the environment";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigProblem {
+    pub key: String,
+    pub source: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}): {}", self.key, self.source, self.message)
+    }
+}
+
+type Check = fn(&str) -> Result<(), String>;
+
+struct Validator<'a> {
+    config: &'a Config,
+    problems: Vec<ConfigProblem>,
+    /// Every key looked at, so tests can compare them with `Settings`
+    checked: Vec<String>,
+}
+
+pub fn validate(config: &Config, enviroment: Enviroment) -> Vec<ConfigProblem> {
+    run(config, enviroment).problems
+}
+
+fn run(config: &Config, enviroment: Enviroment) -> Validator<'_> {
+    let mut validator = Validator {
+        config,
+        problems: vec![],
+        checked: vec![],
+    };
+    let secret: Check = if enviroment == Enviroment::Production {
+        non_empty
+    } else {
+        any
+    };
+
+    validator.required("application.host", non_empty);
+    validator.required("application.port", port);
+    validator.required("application.base_url", url);
+    validator.required("application.shutdown_timeout_secs", positive_number);
+    validator.list("application.trusted_proxies", ip_address);
+    validator.required("database.host", non_empty);
+    validator.required("database.port", non_zero_port);
+    validator.required("database.username", non_empty);
+    validator.required("database.password", secret);
+    validator.required("database.database_name", non_empty);
+    validator.required("database.require_ssl", boolean);
+    validator.optional("database.ssl_root_cert", existing_file);
+    validator.required("database.max_connections", positive_number);
+    validator.required("database.min_connections", number);
+    validator.required("database.acquire_timeout_secs", positive_number);
+    validator.optional("database.idle_timeout_secs", positive_number);
+    validator.optional("database.statement_timeout_ms", positive_number);
+    validator.required("database.connect_eagerly", boolean);
+    validator.required("database.migrate_on_startup", boolean);
+    validator.required("database.connect_retries", number);
+    validator.required("database.connect_backoff_ms", number);
+    validator.email_client("email_client", secret);
+    validator.required("health.check_email_provider", boolean);
+    validator.required("health.probe_timeout_ms", positive_number);
+    validator.required("health.worker_heartbeat_timeout_secs", positive_number);
+    validator.required("metrics.enabled", boolean);
+    validator.required("metrics.host", non_empty);
+    validator.required("metrics.port", port);
+    validator.required("telemetry.service_name", non_empty);
+    validator.required("telemetry.log_level", non_empty);
+    validator.required("telemetry.format", log_format);
+    validator.required("telemetry.sink", log_sink);
+    validator.required("telemetry.log_directory", non_empty);
+    validator.required("telemetry.redaction", redaction);
+    validator.optional("telemetry.otlp_endpoint", url);
+    validator.required("api_docs.swagger_ui", boolean);
+    validator.required("webhooks.poll_interval_ms", positive_number);
+    validator.required("webhooks.timeout_ms", positive_number);
+    validator.required("webhooks.max_attempts", positive_number);
+    validator.required("webhooks.retry_backoff_ms", positive_number);
+    validator.list("cors.allowed_origins", origin);
+    validator.list("cors.allowed_methods", http_method);
+    validator.list("cors.allowed_headers", header_name);
+    validator.required("cors.max_age_secs", number);
+    validator.optional("api.v1_deprecated_at", timestamp);
+    validator.optional("api.v1_sunset", timestamp);
+    validator.required("confirmation.token_ttl_hours", positive_number);
+    validator.required("confirmation.auto_submit", boolean);
+    validator.required("preferences.signing_key", secret);
+    validator.required("preferences.link_ttl_hours", positive_number);
+    for outcome in [
+        "confirmed",
+        "already_confirmed",
+        "token_expired",
+        "token_invalid",
+        "subscribed",
+        "already_subscribed",
+        "subscribe_invalid",
+        "subscribe_failed",
+    ] {
+        validator.optional(&format!("confirmation.redirects.{}", outcome), url);
+    }
+    let publications = config
+        .get_table("publications")
+        .map(|table| table.into_keys().collect())
+        .unwrap_or_else(|_| vec![]);
+    for id in publications {
+        let key = format!("publications.{}", id);
+        if let Err(message) = publication_id(&id) {
+            validator.problem(&key, None, message);
+        }
+        validator.list(&format!("{}.hosts", key), host);
+        validator.required(&format!("{}.base_url", key), url);
+        validator.email_client(&format!("{}.email_client", key), secret);
+    }
+
+    validator
+}
+
+impl Validator<'_> {
+    fn email_client(&mut self, section: &str, secret: Check) {
+        self.required(&format!("{}.url", section), url);
+        self.required(&format!("{}.sender", section), email);
+        self.optional(&format!("{}.sender_name", section), non_empty);
+        self.optional(&format!("{}.reply_to", section), email);
+        self.required(&format!("{}.auth_token", section), secret);
+    }
+
+    fn required(&mut self, key: &str, check: Check) {
+        self.check(key, true, check)
+    }
+
+    fn optional(&mut self, key: &str, check: Check) {
+        self.check(key, false, check)
+    }
+
+    /// Checks every item of a required list.
+    fn list(&mut self, key: &str, check: Check) {
+        let Some(value) = self.value(key, true) else {
+            return;
+        };
+        let items = match value.clone().into_array() {
+            Ok(items) => items,
+            Err(e) => {
+                self.problem(key, Some(&value), e.to_string());
+                return;
+            }
+        };
+        for item in items {
+            let result = item
+                .clone()
+                .into_string()
+                .map_err(|e| e.to_string())
+                .and_then(|raw| check(&raw));
+            if let Err(message) = result {
+                self.problem(key, Some(&item), message);
+            }
+        }
+    }
+
+    fn check(&mut self, key: &str, required: bool, check: Check) {
+        let Some(value) = self.value(key, required) else {
+            return;
+        };
+
+        let result = value
+            .clone()
+            .into_string()
+            .map_err(|e| e.to_string())
+            .and_then(|raw| check(&raw));
+        if let Err(message) = result {
+            self.problem(key, Some(&value), message);
+        }
+    }
+
+    fn value(&mut self, key: &str, required: bool) -> Option<Value> {
+        self.checked.push(key.to_owned());
+        // Tables keep the origin of their values, `Config::get` does not.
+        let (section, field) = key.rsplit_once('.').expect("Keys are namespaced");
+        let value = match self.config.get_table(section) {
+            Ok(table) => table.get(field).cloned(),
+            Err(config::ConfigError::NotFound(_)) => None,
+            Err(e) => {
+                self.problem(key, None, e.to_string());
+                return None;
+            }
+        };
+        if value.is_none() && required {
+            self.problem(key, None, String::from("missing value"));
+        }
+
+        value
+    }
+
+    fn problem(&mut self, key: &str, value: Option<&Value>, message: String) {
+        self.problems.push(ConfigProblem {
+            key: key.to_owned(),
+            source: describe_source(key, value),
+            message,
+        });
+    }
+}
+
+fn describe_source(key: &str, value: Option<&Value>) -> String {
+    let env_var = format!("APP_{}", key.replace('.', "__").to_uppercase());
+    match value.map(|v| v.origin()) {
+        None => format!("not set in any configuration file or {}", env_var),
+        Some(Some(ENVIRONMENT_ORIGIN)) => env_var,
+        Some(Some(origin)) => origin.to_owned(),
+        Some(None) => String::from("embedded configuration"),
+    }
+}
+
+fn any(_: &str) -> Result<(), String> {
+    Ok(())
+}
+
+fn non_empty(value: &str) -> Result<(), String> {
+    if value.trim().is_empty() {
+        return Err(String::from("must not be empty"));
+    }
+    Ok(())
+}
+
+fn port(value: &str) -> Result<(), String> {
+    value
+        .parse::<u16>()
+        .map(|_| ())
+        .map_err(|_| format!("{} is not a valid port", value))
+}
+
+fn non_zero_port(value: &str) -> Result<(), String> {
+    match value.parse::<u16>() {
+        Ok(port) if port > 0 => Ok(()),
+        _ => Err(format!("{} is not a valid port", value)),
+    }
+}
+
+fn number(value: &str) -> Result<(), String> {
+    value
+        .parse::<u64>()
+        .map(|_| ())
+        .map_err(|_| format!("{} is not a non-negative number", value))
+}
+
+fn positive_number(value: &str) -> Result<(), String> {
+    match value.parse::<u64>() {
+        Ok(number) if number > 0 => Ok(()),
+        _ => Err(format!("{} is not a positive number", value)),
+    }
+}
+
+fn existing_file(value: &str) -> Result<(), String> {
+    if !std::path::Path::new(value).is_file() {
+        return Err(format!("{} does not exist", value));
+    }
+    Ok(())
+}
+
+fn url(value: &str) -> Result<(), String> {
+    match reqwest::Url::parse(value) {
+        Ok(url) if ["http", "https"].contains(&url.scheme()) => Ok(()),
+        Ok(_) => Err(format!("{} is not an http(s) url", value)),
+        Err(e) => Err(format!("{} is not a valid url: {}", value, e)),
+    }
+}
+
+fn origin(value: &str) -> Result<(), String> {
+    match reqwest::Url::parse(value) {
+        Ok(url) if url.origin().ascii_serialization() == value => Ok(()),
+        _ => Err(format!(
+            "{} is not an origin like https://example.com",
+            value
+        )),
+    }
+}
+
+fn http_method(value: &str) -> Result<(), String> {
+    actix_web::http::Method::from_bytes(value.as_bytes())
+        .map(|_| ())
+        .map_err(|_| format!("{} is not an HTTP method", value))
+}
+
+fn header_name(value: &str) -> Result<(), String> {
+    actix_web::http::header::HeaderName::from_bytes(value.as_bytes())
+        .map(|_| ())
+        .map_err(|_| format!("{} is not a header name", value))
+}
+
+fn boolean(value: &str) -> Result<(), String> {
+    value
+        .parse::<bool>()
+        .map(|_| ())
+        .map_err(|_| format!("{} is not a boolean", value))
+}
+
+fn timestamp(value: &str) -> Result<(), String> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|_| ())
+        .map_err(|_| format!("{} is not an RFC 3339 timestamp", value))
+}
+
+fn publication_id(value: &str) -> Result<(), String> {
+    if value == DEFAULT_PUBLICATION {
+        return Err(format!("{} is reserved for the default publication", value));
+    }
+    if !value
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        return Err(format!(
+            "{} is not made of lowercase letters, digits and dashes",
+            value
+        ));
+    }
+    Ok(())
+}
+
+fn ip_address(value: &str) -> Result<(), String> {
+    value
+        .parse::<std::net::IpAddr>()
+        .map(|_| ())
+        .map_err(|_| format!("{} is not an IP address", value))
+}
+
+fn host(value: &str) -> Result<(), String> {
+    let valid = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.');
+    if !valid {
+        return Err(format!(
+            "{} is not a lowercase host name without scheme or port",
+            value
+        ));
+    }
+    Ok(())
+}
+
+fn email(value: &str) -> Result<(), String> {
+    Email::parse(value.to_owned()).map(|_| ())
+}
+
+fn one_of(value: &str, allowed: &[&str]) -> Result<(), String> {
+    if !allowed.contains(&value) {
+        return Err(format!("{} is not one of: {}", value, allowed.join(", ")));
+    }
+    Ok(())
+}
+
+fn log_format(value: &str) -> Result<(), String> {
+    one_of(value, &["bunyan", "pretty"])
+}
+
+fn log_sink(value: &str) -> Result<(), String> {
+    one_of(value, &["stdout", "file", "none"])
+}
+
+fn redaction(value: &str) -> Result<(), String> {
+    one_of(value, &["none", "mask", "hash"])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{validate, validation::run, Enviroment, PublicationSettings, Settings};
+    use config::{Config, Environment, File, FileFormat};
+    use serde::de::{self, value::Error, Deserializer, IntoDeserializer, MapAccess, Visitor};
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    const VALID: &str = r#"
+application:
+  host: "127.0.0.1"
+  port: 8000
+  base_url: "http://127.0.0.1"
+  shutdown_timeout_secs: 30
+  trusted_proxies: ["10.0.0.1"]
+database:
+  host: "127.0.0.1"
+  port: 5432
+  username: "postgres"
+  password: "password"
+  database_name: "newsletter"
+  require_ssl: false
+  max_connections: 10
+  min_connections: 0
+  acquire_timeout_secs: 5
+  connect_eagerly: false
+  migrate_on_startup: false
+  connect_retries: 0
+  connect_backoff_ms: 100
+email_client:
+  url: "https://api.sendgrid.com/v3"
+  sender: "test@email.com"
+  auth_token: "token"
+health:
+  check_email_provider: false
+  probe_timeout_ms: 1000
+  worker_heartbeat_timeout_secs: 60
+metrics:
+  enabled: true
+  host: "127.0.0.1"
+  port: 9000
+telemetry:
+  service_name: "newsletter"
+  log_level: "info"
+  format: "bunyan"
+  sink: "stdout"
+  log_directory: "logs"
+  redaction: "mask"
+api_docs:
+  swagger_ui: false
+webhooks:
+  poll_interval_ms: 1000
+  timeout_ms: 5000
+  max_attempts: 8
+  retry_backoff_ms: 30000
+cors:
+  allowed_origins: ["https://www.example.com"]
+  allowed_methods: ["GET", "POST"]
+  allowed_headers: ["Content-Type"]
+  max_age_secs: 3600
+confirmation:
+  token_ttl_hours: 72
+  auto_submit: false
+preferences:
+  signing_key: "key"
+  link_ttl_hours: 24
+"#;
+
+    fn config(env: &[(&str, &str)]) -> Config {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Config::builder()
+            .add_source(File::from_str(VALID, FileFormat::Yaml))
+            .add_source(
+                Environment::with_prefix("APP")
+                    .prefix_separator("_")
+                    .separator("__")
+                    .try_parsing(true)
+                    .list_separator(",")
+                    .with_list_parse_key("application.trusted_proxies")
+                    .with_list_parse_key("cors.allowed_origins")
+                    .with_list_parse_key("publications.acme.hosts")
+                    .source(Some(env)),
+            )
+            .build()
+            .expect("Failed to build config")
+    }
+
+    #[test]
+    fn valid_configuration_has_no_problems() {
+        assert!(validate(&config(&[]), Enviroment::Local).is_empty());
+        assert!(validate(&config(&[]), Enviroment::Production).is_empty());
+    }
+
+    #[test]
+    fn all_problems_are_reported_at_once() {
+        let config = config(&[
+            ("APP_APPLICATION__BASE_URL", "not a url"),
+            ("APP_DATABASE__PORT", "99999"),
+            ("APP_EMAIL_CLIENT__SENDER", "nope"),
+        ]);
+
+        let problems = validate(&config, Enviroment::Local);
+
+        let keys: Vec<&str> = problems.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "application.base_url",
+                "database.port",
+                "email_client.sender"
+            ]
+        );
+    }
+
+    #[test]
+    fn publications_are_validated_like_the_default_one() {
+        let config = config(&[
+            (
+                "APP_PUBLICATIONS__ACME__HOSTS",
+                "news.acme.com,https://acme.com",
+            ),
+            ("APP_PUBLICATIONS__ACME__BASE_URL", "https://news.acme.com"),
+            (
+                "APP_PUBLICATIONS__ACME__EMAIL_CLIENT__URL",
+                "https://api.sendgrid.com/v3",
+            ),
+            ("APP_PUBLICATIONS__ACME__EMAIL_CLIENT__SENDER", "nope"),
+        ]);
+
+        let problems = validate(&config, Enviroment::Production);
+
+        let keys: Vec<&str> = problems.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "publications.acme.hosts",
+                "publications.acme.email_client.sender",
+                "publications.acme.email_client.auth_token"
+            ]
+        );
+    }
+
+    #[test]
+    fn problems_name_the_env_var_they_came_from() {
+        let config = config(&[("APP_EMAIL_CLIENT__SENDER", "nope")]);
+
+        let problems = validate(&config, Enviroment::Local);
+
+        assert_eq!(problems[0].source, "APP_EMAIL_CLIENT__SENDER");
+    }
+
+    #[test]
+    fn missing_values_are_reported() {
+        let config = Config::builder()
+            .add_source(File::from_str(
+                "application:\n  port: 8000",
+                FileFormat::Yaml,
+            ))
+            .build()
+            .unwrap();
+
+        let problems = validate(&config, Enviroment::Local);
+
+        assert!(problems
+            .iter()
+            .any(|p| p.key == "database.host" && p.message == "missing value"));
+        assert!(!problems.iter().any(|p| p.key == "email_client.reply_to"));
+    }
+
+    #[test]
+    fn pool_settings_must_be_sensible() {
+        let config = config(&[
+            ("APP_DATABASE__MAX_CONNECTIONS", "0"),
+            ("APP_DATABASE__ACQUIRE_TIMEOUT_SECS", "-1"),
+            ("APP_DATABASE__SSL_ROOT_CERT", "/does/not/exist.crt"),
+        ]);
+
+        let problems = validate(&config, Enviroment::Local);
+
+        let keys: Vec<&str> = problems.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "database.ssl_root_cert",
+                "database.max_connections",
+                "database.acquire_timeout_secs"
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_secrets_are_rejected_in_production_only() {
+        let config = config(&[
+            ("APP_DATABASE__PASSWORD", ""),
+            ("APP_EMAIL_CLIENT__AUTH_TOKEN", ""),
+        ]);
+
+        assert!(validate(&config, Enviroment::Local).is_empty());
+        assert_eq!(validate(&config, Enviroment::Production).len(), 2);
+    }
+
+    #[test]
+    fn telemetry_options_must_be_known() {
+        let config = config(&[
+            ("APP_TELEMETRY__FORMAT", "xml"),
+            ("APP_TELEMETRY__REDACTION", "scramble"),
+        ]);
+
+        let problems = validate(&config, Enviroment::Local);
+
+        let keys: Vec<&str> = problems.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(keys, ["telemetry.format", "telemetry.redaction"]);
+        assert_eq!(problems[0].message, "xml is not one of: bunyan, pretty");
+    }
+
+    #[test]
+    fn api_deprecation_dates_must_be_timestamps() {
+        let config = config(&[
+            ("APP_API__V1_DEPRECATED_AT", "2027-01-01T00:00:00Z"),
+            ("APP_API__V1_SUNSET", "next summer"),
+        ]);
+
+        let problems = validate(&config, Enviroment::Local);
+
+        let keys: Vec<&str> = problems.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(keys, ["api.v1_sunset"]);
+    }
+
+    #[test]
+    fn confirmation_redirects_must_be_urls() {
+        let config = config(&[
+            (
+                "APP_CONFIRMATION__REDIRECTS__CONFIRMED",
+                "https://www.example.com/welcome",
+            ),
+            ("APP_CONFIRMATION__REDIRECTS__TOKEN_EXPIRED", "/expired"),
+        ]);
+
+        let problems = validate(&config, Enviroment::Local);
+
+        let keys: Vec<&str> = problems.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(keys, ["confirmation.redirects.token_expired"]);
+    }
+
+    #[test]
+    fn cors_origins_are_checked_one_by_one() {
+        let config = config(&[(
+            "APP_CORS__ALLOWED_ORIGINS",
+            "https://www.example.com,https://example.com/signup",
+        )]);
+
+        let problems = validate(&config, Enviroment::Local);
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].key, "cors.allowed_origins");
+        assert_eq!(problems[0].source, "APP_CORS__ALLOWED_ORIGINS");
+        assert!(
+            problems[0]
+                .message
+                .starts_with("https://example.com/signup"),
+            "{}",
+            problems[0].message
+        );
+    }
+
+    #[test]
+    fn trusted_proxies_must_be_ip_addresses() {
+        let config = config(&[("APP_APPLICATION__TRUSTED_PROXIES", "10.0.0.1,proxy.local")]);
+
+        let problems = validate(&config, Enviroment::Local);
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].key, "application.trusted_proxies");
+        assert_eq!(problems[0].message, "proxy.local is not an IP address");
+    }
+
+    #[test]
+    fn every_setting_is_validated() {
+        let config = config(&[("APP_PUBLICATIONS__ACME__BASE_URL", "https://acme.com")]);
+        let checked = run(&config, Enviroment::Local).checked;
+
+        let publication_keys = keys_of::<PublicationSettings>()
+            .into_iter()
+            .map(|key| format!("publications.acme.{}", key));
+        let unchecked: Vec<String> = keys_of::<Settings>()
+            .into_iter()
+            .filter(|key| key != "publications")
+            .chain(publication_keys)
+            .filter(|key| !checked.contains(key))
+            .collect();
+
+        assert!(unchecked.is_empty(), "Not validated: {:?}", unchecked);
+    }
+
+    /// The keys `T` is deserialized from. Lists, maps, options and enums are
+    /// reported as they are, structs by their fields.
+    fn keys_of<T: de::DeserializeOwned>() -> Vec<String> {
+        let keys = RefCell::new(vec![]);
+        T::deserialize(Keys {
+            path: String::new(),
+            keys: &keys,
+        })
+        .expect("Failed to walk the settings");
+
+        keys.into_inner()
+    }
+
+    struct Keys<'a> {
+        path: String,
+        keys: &'a RefCell<Vec<String>>,
+    }
+
+    impl Keys<'_> {
+        fn record(&self) {
+            self.keys.borrow_mut().push(self.path.clone());
+        }
+    }
+
+    impl<'de> Deserializer<'de> for Keys<'_> {
+        type Error = Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.record();
+            visitor.visit_unit()
+        }
+
+        fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.record();
+            visitor.visit_bool(false)
+        }
+
+        fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.deserialize_u64(visitor)
+        }
+
+        fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.deserialize_u64(visitor)
+        }
+
+        fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.record();
+            visitor.visit_u64(0)
+        }
+
+        fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.record();
+            visitor.visit_str("")
+        }
+
+        fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.deserialize_str(visitor)
+        }
+
+        fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.record();
+            visitor.visit_none()
+        }
+
+        fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.record();
+            visitor.visit_seq(Vec::<String>::new().into_deserializer())
+        }
+
+        fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.record();
+            visitor.visit_map(HashMap::<String, String>::new().into_deserializer())
+        }
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            variants: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            self.record();
+            visitor.visit_enum(variants[0].into_deserializer())
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            fields: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            visitor.visit_map(Fields {
+                parent: self,
+                fields: fields.iter(),
+                current: "",
+            })
+        }
+
+        fn deserialize_newtype_struct<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            visitor.visit_newtype_struct(self)
+        }
+
+        serde::forward_to_deserialize_any! {
+            i8 i16 i32 i64 i128 u8 u128 f32 f64 char bytes byte_buf unit unit_struct
+            tuple tuple_struct identifier ignored_any
+        }
+    }
+
+    struct Fields<'a> {
+        parent: Keys<'a>,
+        fields: std::slice::Iter<'static, &'static str>,
+        current: &'static str,
+    }
+
+    impl<'de> MapAccess<'de> for Fields<'_> {
+        type Error = Error;
+
+        fn next_key_seed<K: de::DeserializeSeed<'de>>(
+            &mut self,
+            seed: K,
+        ) -> Result<Option<K::Value>, Error> {
+            let Some(field) = self.fields.next() else {
+                return Ok(None);
+            };
+            self.current = field;
+            seed.deserialize(field.into_deserializer()).map(Some)
+        }
+
+        fn next_value_seed<V: de::DeserializeSeed<'de>>(
+            &mut self,
+            seed: V,
+        ) -> Result<V::Value, Error> {
+            let path = match self.parent.path.as_str() {
+                "" => self.current.to_owned(),
+                parent => format!("{}.{}", parent, self.current),
+            };
+            seed.deserialize(Keys {
+                path,
+                keys: self.parent.keys,
+            })
+        }
+    }
+}
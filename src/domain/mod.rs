@@ -1,7 +1,9 @@
 mod subscriber;
 mod subscriber_email;
+mod subscriber_locale;
 mod subscriber_name;
 
 pub use subscriber::Subscriber;
 pub use subscriber_email::Email;
+pub use subscriber_locale::{Locale, DEFAULT_LOCALE};
 pub use subscriber_name::SubscriberName;
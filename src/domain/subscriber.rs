@@ -1,6 +1,10 @@
-use crate::domain::{Email, SubscriberName};
+use crate::attributes::Attributes;
+use crate::domain::{Email, Locale, SubscriberName};
 
 pub struct Subscriber {
     pub name: SubscriberName,
     pub email: Email,
+    pub locale: Locale,
+    /// Not yet checked against the publication's attribute definitions
+    pub attributes: Attributes,
 }
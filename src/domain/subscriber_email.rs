@@ -1,18 +1,26 @@
+use crate::telemetry::redact_email;
 use validator::validate_email;
 
-#[derive(Debug)]
 pub struct Email(String);
 
 impl Email {
     pub fn parse(s: String) -> Result<Self, String> {
         if !validate_email(&s) {
-            return Err(format!("{} is not a valid email", s));
+            return Err(String::from("Not a valid email address"));
         }
 
         Ok(Self(s))
     }
 }
 
+impl std::fmt::Debug for Email {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Email")
+            .field(&redact_email(&self.0))
+            .finish()
+    }
+}
+
 impl AsRef<str> for Email {
     fn as_ref(&self) -> &str {
         &self.0
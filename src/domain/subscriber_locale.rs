@@ -0,0 +1,61 @@
+use unic_langid::LanguageIdentifier;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locale(String);
+
+impl Locale {
+    pub fn parse(s: String) -> Result<Self, String> {
+        match s.parse::<LanguageIdentifier>() {
+            Ok(langid) => Ok(Self(langid.to_string())),
+            Err(_) => Err(format!("{} is not a valid locale", s)),
+        }
+    }
+
+    pub fn language_identifier(&self) -> LanguageIdentifier {
+        self.0
+            .parse()
+            .expect("A parsed locale is always a valid language identifier")
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self(String::from(DEFAULT_LOCALE))
+    }
+}
+
+impl From<&LanguageIdentifier> for Locale {
+    fn from(value: &LanguageIdentifier) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl AsRef<str> for Locale {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::Locale;
+
+    #[test]
+    fn a_valid_locale_is_parsed_successfully() {
+        let locale = Locale::parse(String::from("de-AT")).expect("Failed to parse locale");
+        assert_eq!(locale.as_ref(), "de-AT");
+    }
+
+    #[test]
+    fn a_locale_is_normalized() {
+        let locale = Locale::parse(String::from("en_us")).expect("Failed to parse locale");
+        assert_eq!(locale.as_ref(), "en-US");
+    }
+
+    #[test]
+    fn an_invalid_locale_is_rejected() {
+        assert!(Locale::parse(String::from("not a locale")).is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::telemetry::redact_name;
 use unicode_segmentation::UnicodeSegmentation;
 
 pub struct SubscriberName(String);
@@ -10,15 +11,29 @@ impl SubscriberName {
         let forbidden_characters = ['/', '(', ')', '"', '<', '>', '\\', '{', '}'];
         let contains_invalid_chars = s.chars().any(|g| forbidden_characters.contains(&g));
 
-        println!("name: {}, isempty{}", s, is_empty);
+        tracing::debug!(
+            subscriber_name = %s,
+            is_empty,
+            is_too_long,
+            contains_invalid_chars,
+            "Parsing subscriber name"
+        );
         if is_empty || is_too_long || contains_invalid_chars {
-            return Err(format!("{} is not a valid subscriber name", s));
+            return Err(String::from("Not a valid subscriber name"));
         }
 
         Ok(Self(s))
     }
 }
 
+impl std::fmt::Debug for SubscriberName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SubscriberName")
+            .field(&redact_name(&self.0))
+            .finish()
+    }
+}
+
 impl AsRef<str> for SubscriberName {
     fn as_ref(&self) -> &str {
         &self.0
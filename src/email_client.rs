@@ -1,13 +1,28 @@
+use opentelemetry::global;
 use reqwest::Client;
+use secrecy::{ExposeSecret, Secret};
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::domain::Email;
+use crate::metrics::Metrics;
 
 #[derive(Debug)]
 pub struct EmailClient {
     client: Client,
     url: String,
-    sender: Email,
-    auth_code: String,
+    provider: String,
+    sender: SenderIdentity,
+    auth_token: Secret<String>,
+    metrics: Option<Metrics>,
+}
+
+#[derive(Debug)]
+pub struct SenderIdentity {
+    pub email: Email,
+    pub name: Option<String>,
+    pub reply_to: Option<Email>,
 }
 
 #[derive(serde::Serialize)]
@@ -23,6 +38,13 @@ struct To {
 #[derive(serde::Serialize)]
 struct From {
     email: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct ReplyTo {
+    email: String,
 }
 
 #[derive(serde::Serialize)]
@@ -35,20 +57,41 @@ struct Content {
 struct SendEmailPayload {
     personalizations: [Personalizations; 1],
     from: From,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to: Option<ReplyTo>,
     subject: String,
     content: [Content; 1],
 }
 
 impl EmailClient {
-    pub fn new(url: String, sender: Email, auth_code: String) -> Self {
+    pub fn new(url: String, sender: SenderIdentity, auth_token: Secret<String>) -> Self {
+        let provider = reqwest::Url::parse(&url)
+            .ok()
+            .and_then(|url| url.host_str().map(String::from))
+            .unwrap_or_else(|| String::from("unknown"));
+
         Self {
             client: Client::new(),
             url,
+            provider,
             sender,
-            auth_code,
+            auth_token,
+            metrics: None,
         }
     }
 
+    pub fn with_metrics(self, metrics: Metrics) -> Self {
+        Self {
+            metrics: Some(metrics),
+            ..self
+        }
+    }
+
+    #[tracing::instrument(
+        name = "Sending an email",
+        skip_all,
+        fields(provider = %self.provider, subject = %subject)
+    )]
     pub async fn send_email(
         &self,
         recipient: Email,
@@ -63,33 +106,73 @@ impl EmailClient {
                 }],
             }],
             from: From {
-                email: self.sender.as_ref().to_owned(),
+                email: self.sender.email.as_ref().to_owned(),
+                name: self.sender.name.clone(),
             },
+            reply_to: self.sender.reply_to.as_ref().map(|email| ReplyTo {
+                email: email.as_ref().to_owned(),
+            }),
             subject: subject.to_owned(),
             content: [Content {
                 value: body.to_owned(),
                 r#type: String::from("text/html"),
             }],
         };
-        let bearer_token = format!("Bearer {}", self.auth_code);
+        let bearer_token = format!("Bearer {}", self.auth_token.expose_secret());
 
-        self.client
+        let mut request = self
+            .client
             .post(url)
             .header("Authorization", bearer_token)
-            .json(&body)
+            .json(&body);
+        for (name, value) in trace_context_headers() {
+            request = request.header(name, value);
+        }
+
+        let result = request
             .send()
-            .await?
-            .error_for_status()?;
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ());
+        if let Some(metrics) = &self.metrics {
+            metrics.email_sent(&self.provider, &result);
+        }
 
-        Ok(())
+        result
+    }
+
+    pub async fn probe(&self, timeout: Duration) -> Result<(), reqwest::Error> {
+        self.client
+            .head(&self.url)
+            .timeout(timeout)
+            .send()
+            .await
+            .map(|_| ())
     }
 }
 
+fn trace_context_headers() -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&tracing::Span::current().context(), &mut headers)
+    });
+
+    headers
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{domain::Email, email_client::EmailClient};
+    use crate::{
+        domain::Email,
+        email_client::{EmailClient, SenderIdentity},
+    };
+    use opentelemetry::{global, trace::TracerProvider};
+    use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider};
+    use secrecy::Secret;
+    use tracing::Instrument;
+    use tracing_subscriber::layer::SubscriberExt;
     use wiremock::{
-        matchers::{header, header_exists, method, path},
+        matchers::{body_partial_json, header, header_exists, method, path},
         Match, Mock, MockServer, ResponseTemplate,
     };
 
@@ -110,6 +193,20 @@ mod tests {
         }
     }
 
+    fn sender() -> SenderIdentity {
+        SenderIdentity {
+            email: Email::parse(String::from("test@email.com")).expect("Failed to parse email"),
+            name: Some(String::from("Test Newsletter")),
+            reply_to: Some(
+                Email::parse(String::from("reply@email.com")).expect("Failed to parse email"),
+            ),
+        }
+    }
+
+    fn email_client(url: String) -> EmailClient {
+        EmailClient::new(url, sender(), Secret::new(String::from("123authcode")))
+    }
+
     #[tokio::test]
     async fn send_email_fires_a_request_to_base_url() {
         let server = MockServer::start().await;
@@ -123,9 +220,7 @@ mod tests {
             .mount(&server)
             .await;
 
-        let sender = Email::parse(String::from("test@email.com")).expect("Failed to parse email");
-        let auth_code = String::from("123authcode");
-        let client = EmailClient::new(server.uri(), sender, auth_code);
+        let client = email_client(server.uri());
         let recipient =
             Email::parse(String::from("test12@email.com")).expect("Failed to parse email");
 
@@ -146,9 +241,7 @@ mod tests {
             .mount(&server)
             .await;
 
-        let sender = Email::parse(String::from("test@email.com")).expect("Failed to parse email");
-        let auth_code = String::from("123authcode");
-        let client = EmailClient::new(server.uri(), sender, auth_code);
+        let client = email_client(server.uri());
         let recipient =
             Email::parse(String::from("test12@email.com")).expect("Failed to parse email");
 
@@ -156,4 +249,60 @@ mod tests {
 
         assert!(res.is_err());
     }
+
+    #[tokio::test]
+    async fn send_email_uses_the_configured_sender_identity() {
+        let server = MockServer::start().await;
+        Mock::given(header("Authorization", "Bearer 123authcode"))
+            .and(body_partial_json(serde_json::json!({
+                "from": { "email": "test@email.com", "name": "Test Newsletter" },
+                "reply_to": { "email": "reply@email.com" }
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = email_client(server.uri());
+        let recipient =
+            Email::parse(String::from("test12@email.com")).expect("Failed to parse email");
+
+        let res = client.send_email(recipient, "test email", "testing").await;
+
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn debug_output_does_not_leak_the_auth_token() {
+        let client = email_client(String::from("http://127.0.0.1"));
+
+        assert!(!format!("{:?}", client).contains("123authcode"));
+    }
+
+    #[tokio::test]
+    async fn send_email_propagates_the_trace_context() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = SdkTracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let server = MockServer::start().await;
+        Mock::given(header_exists("traceparent"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = email_client(server.uri());
+        let recipient =
+            Email::parse(String::from("test12@email.com")).expect("Failed to parse email");
+
+        let res = client
+            .send_email(recipient, "test email", "testing")
+            .instrument(tracing::info_span!("signup"))
+            .await;
+
+        assert!(res.is_ok());
+    }
 }
@@ -0,0 +1,224 @@
+use crate::domain::{Locale, DEFAULT_LOCALE};
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource};
+use fluent_langneg::{accepted_languages, negotiate_languages, NegotiationStrategy};
+use unic_langid::LanguageIdentifier;
+
+const RESOURCES: [(&str, &str); 3] = [
+    ("en", include_str!("../locales/en/main.ftl")),
+    ("de", include_str!("../locales/de/main.ftl")),
+    ("cs", include_str!("../locales/cs/main.ftl")),
+];
+
+pub struct Translations {
+    bundles: Vec<(LanguageIdentifier, FluentBundle<FluentResource>)>,
+    default: LanguageIdentifier,
+}
+
+impl std::fmt::Debug for Translations {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Translations")
+            .field("locales", &self.available())
+            .field("default", &self.default)
+            .finish()
+    }
+}
+
+impl Translations {
+    pub fn load() -> Self {
+        let bundles = RESOURCES
+            .iter()
+            .map(|(locale, source)| {
+                let langid: LanguageIdentifier =
+                    locale.parse().expect("Failed to parse bundled locale");
+                let resource = FluentResource::try_new(source.to_string())
+                    .unwrap_or_else(|_| panic!("Failed to parse {} translations", locale));
+                let mut bundle = FluentBundle::new_concurrent(vec![langid.clone()]);
+                // Bidi isolation marks would end up inside links and subjects.
+                bundle.set_use_isolating(false);
+                bundle
+                    .add_resource(resource)
+                    .unwrap_or_else(|_| panic!("Duplicate messages in {} translations", locale));
+
+                (langid, bundle)
+            })
+            .collect();
+
+        Self {
+            bundles,
+            default: DEFAULT_LOCALE
+                .parse()
+                .expect("Failed to parse default locale"),
+        }
+    }
+
+    pub fn available(&self) -> Vec<&LanguageIdentifier> {
+        self.bundles.iter().map(|(langid, _)| langid).collect()
+    }
+
+    pub fn negotiate(&self, explicit: Option<&str>, accept_language: Option<&str>) -> Locale {
+        let mut requested: Vec<LanguageIdentifier> = explicit
+            .and_then(|locale| locale.parse().ok())
+            .into_iter()
+            .collect();
+        if let Some(header) = accept_language {
+            requested.extend(accepted_languages::parse(header));
+        }
+
+        let available = self.available();
+        let default = &self.default;
+        let supported = negotiate_languages(
+            &requested,
+            &available,
+            Some(&default),
+            NegotiationStrategy::Filtering,
+        );
+
+        supported
+            .first()
+            .map(|langid| Locale::from(**langid))
+            .unwrap_or_default()
+    }
+
+    pub fn format(&self, locale: &Locale, id: &str, args: Option<&FluentArgs>) -> String {
+        for bundle in self.fallback_chain(locale) {
+            let Some(pattern) = bundle.get_message(id).and_then(|m| m.value()) else {
+                continue;
+            };
+
+            let mut errors = vec![];
+            let message = bundle.format_pattern(pattern, args, &mut errors);
+            if !errors.is_empty() {
+                tracing::error!("Failed to format message {}: {:?}", id, errors);
+            }
+
+            return message.into_owned();
+        }
+
+        tracing::error!("Missing translation for message {}", id);
+        id.to_owned()
+    }
+
+    fn fallback_chain(&self, locale: &Locale) -> Vec<&FluentBundle<FluentResource>> {
+        let langid = locale.language_identifier();
+        let language_only = LanguageIdentifier::from_parts(langid.language, None, None, &[]);
+
+        [&langid, &language_only, &self.default]
+            .into_iter()
+            .filter_map(|wanted| {
+                self.bundles
+                    .iter()
+                    .find(|(langid, _)| langid == wanted)
+                    .map(|(_, bundle)| bundle)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{domain::Locale, i18n::Translations};
+    use fluent_bundle::FluentArgs;
+
+    #[test]
+    fn bundled_translations_define_the_same_messages() {
+        let translations = Translations::load();
+        let ids = [
+            "confirmation-email-subject",
+            "confirmation-email-body",
+            "page-title",
+            "subscription-confirmed",
+            "subscription-already-confirmed",
+            "subscription-token-expired",
+            "subscription-token-invalid",
+            "subscribe-success",
+            "subscribe-duplicate",
+            "subscribe-invalid",
+            "subscribe-failed",
+            "confirm-prompt",
+            "confirm-button",
+            "preferences-email-subject",
+            "preferences-email-body",
+            "preferences-request-prompt",
+            "preferences-email-label",
+            "preferences-request-button",
+            "preferences-link-sent",
+            "preferences-link-invalid",
+            "preferences-link-expired",
+            "preferences-name-label",
+            "preferences-topics-label",
+            "preferences-frequency-label",
+            "frequency-daily",
+            "frequency-weekly",
+            "frequency-monthly",
+            "preferences-pause-label",
+            "preferences-save-button",
+            "preferences-saved",
+            "preferences-invalid",
+            "unsubscribe-button",
+            "unsubscribed",
+            "preferences-lists-label",
+            "membership-confirmed",
+            "membership-pending",
+            "membership-unsubscribed",
+            "unsubscribe-list-button",
+            "unsubscribed-list",
+            "email-change-button",
+            "email-change-invalid",
+            "email-change-taken",
+            "email-change-sent",
+            "email-change-verify-subject",
+            "email-change-verify-body",
+            "email-change-prompt",
+            "email-change-confirm-button",
+            "email-change-confirmed",
+            "email-change-notice-subject",
+            "email-change-notice-body",
+        ];
+        for (_, bundle) in &translations.bundles {
+            for id in ids {
+                assert!(bundle.has_message(id), "missing {}", id);
+            }
+        }
+    }
+
+    #[test]
+    fn explicit_language_takes_precedence_over_header() {
+        let translations = Translations::load();
+        let locale = translations.negotiate(Some("cs"), Some("de-DE,de;q=0.9"));
+        assert_eq!(locale.as_ref(), "cs");
+    }
+
+    #[test]
+    fn accept_language_header_is_used_when_no_language_is_given() {
+        let translations = Translations::load();
+        let locale = translations.negotiate(None, Some("fr;q=0.9, de-AT;q=0.8"));
+        assert_eq!(locale.as_ref(), "de");
+    }
+
+    #[test]
+    fn unsupported_languages_fall_back_to_default() {
+        let translations = Translations::load();
+        assert_eq!(translations.negotiate(Some("ja"), None).as_ref(), "en");
+        assert_eq!(translations.negotiate(None, None).as_ref(), "en");
+    }
+
+    #[test]
+    fn regional_locale_falls_back_to_language() {
+        let translations = Translations::load();
+        let locale = Locale::parse(String::from("de-CH")).unwrap();
+        let mut args = FluentArgs::new();
+        args.set("link", "https://example.com");
+
+        let body = translations.format(&locale, "confirmation-email-body", Some(&args));
+
+        assert!(body.contains("Willkommen"));
+        assert!(body.contains("href=\"https://example.com\""));
+    }
+
+    #[test]
+    fn missing_message_returns_its_id() {
+        let translations = Translations::load();
+        let message = translations.format(&Locale::default(), "does-not-exist", None);
+        assert_eq!(message, "does-not-exist");
+    }
+}
@@ -1,6 +1,19 @@
+pub mod api_keys;
+pub mod attributes;
+pub mod authentication;
+pub mod cli;
 pub mod config;
 pub mod domain;
 pub mod email_client;
+pub mod i18n;
+pub mod lists;
+pub mod metrics;
+pub mod preferences;
+pub mod publications;
 pub mod routes;
+pub mod shutdown;
 pub mod startup;
+pub mod tags;
 pub mod telemetry;
+pub mod webhooks;
+pub mod workers;
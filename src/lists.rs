@@ -0,0 +1,161 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// The list signups without a list identifier go to. Publications get it
+/// with their first signup.
+pub const DEFAULT_LIST: &str = "newsletter";
+
+#[derive(serde::Serialize, utoipa::ToSchema, Debug, Clone)]
+pub struct List {
+    pub list_id: Uuid,
+    #[schema(example = "weekly")]
+    pub slug: String,
+    #[schema(example = "Weekly digest")]
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A subscriber's standing on one list.
+#[derive(Debug)]
+pub struct Membership {
+    pub slug: String,
+    pub name: String,
+    pub status: String,
+}
+
+#[tracing::instrument(name = "Looking up a list", skip(executor))]
+pub async fn find_list<'c>(
+    executor: impl sqlx::PgExecutor<'c>,
+    publication_id: &str,
+    slug: &str,
+) -> Result<Option<List>, sqlx::Error> {
+    sqlx::query_as!(
+        List,
+        r#"SELECT list_id, slug, name, created_at FROM lists
+        WHERE publication_id = $1 AND slug = $2"#,
+        publication_id,
+        slug
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+/// Creates the publication's default list unless it exists.
+#[tracing::instrument(name = "Ensuring the default list", skip(executor))]
+pub async fn ensure_default_list<'c>(
+    executor: impl sqlx::PgExecutor<'c>,
+    publication_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO lists (list_id, publication_id, slug, name, created_at)
+        VALUES ($1, $2, $3, 'Newsletter', $4)
+        ON CONFLICT (publication_id, slug) DO NOTHING"#,
+        Uuid::new_v4(),
+        publication_id,
+        DEFAULT_LIST,
+        Utc::now()
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Listing lists", skip(connection))]
+pub async fn list_lists(
+    connection: &PgPool,
+    publication_id: &str,
+) -> Result<Vec<List>, sqlx::Error> {
+    sqlx::query_as!(
+        List,
+        r#"SELECT list_id, slug, name, created_at FROM lists
+        WHERE publication_id = $1 ORDER BY name"#,
+        publication_id
+    )
+    .fetch_all(connection)
+    .await
+}
+
+#[tracing::instrument(name = "Creating a list", skip(connection))]
+pub async fn create_list(
+    connection: &PgPool,
+    publication_id: &str,
+    slug: &str,
+    name: &str,
+) -> Result<List, String> {
+    let valid_slug = !slug.is_empty()
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid_slug {
+        return Err(String::from(
+            "Slug must be lowercase letters, digits and dashes",
+        ));
+    }
+    if name.trim().is_empty() {
+        return Err(String::from("Name must not be empty"));
+    }
+
+    sqlx::query_as!(
+        List,
+        r#"INSERT INTO lists (list_id, publication_id, slug, name, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (publication_id, slug) DO NOTHING
+        RETURNING list_id, slug, name, created_at"#,
+        Uuid::new_v4(),
+        publication_id,
+        slug,
+        name.trim(),
+        Utc::now()
+    )
+    .fetch_optional(connection)
+    .await
+    .map_err(|e| format!("Failed to create list: {}", e))?
+    .ok_or_else(|| format!("List {} already exists", slug))
+}
+
+#[tracing::instrument(name = "Listing memberships", skip(connection))]
+pub async fn memberships(
+    connection: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<Membership>, sqlx::Error> {
+    sqlx::query_as!(
+        Membership,
+        r#"SELECT l.slug, l.name, m.status FROM list_memberships m
+        JOIN lists l ON l.list_id = m.list_id
+        WHERE m.subscriber_id = $1
+        ORDER BY l.name"#,
+        subscriber_id
+    )
+    .fetch_all(connection)
+    .await
+}
+
+/// Puts the subscriber on the list, pending confirmation. Returns `false`
+/// when they are already on it, while rejoining after an unsubscribe is
+/// allowed.
+#[tracing::instrument(name = "Joining a list", skip(executor))]
+pub async fn join<'c>(
+    executor: impl sqlx::PgExecutor<'c>,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"INSERT INTO list_memberships (subscriber_id, list_id, status, subscribed_at)
+        VALUES ($1, $2, 'pending_confirmation', $3)
+        ON CONFLICT (subscriber_id, list_id) DO UPDATE SET
+            status = 'pending_confirmation',
+            subscribed_at = EXCLUDED.subscribed_at,
+            confirmed_at = NULL,
+            unsubscribed_at = NULL
+        WHERE list_memberships.status = 'unsubscribed'"#,
+        subscriber_id,
+        list_id,
+        Utc::now()
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
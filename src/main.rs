@@ -1,16 +1,47 @@
+use clap::Parser;
 use newsletter::{
-    config::get_config,
-    startup::Application,
-    telemetry::{get_subscriber, init_subscriber},
+    cli::{self, Cli, Command},
+    config::{get_config, Settings},
+    telemetry::{get_subscriber, get_subscriber_with_sink, init_subscriber, TelemetryGuard},
 };
 
 #[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    let config = get_config().expect("Failed to retrieve app configuration");
-    init_subscriber(get_subscriber());
+async fn main() {
+    let command = Cli::parse().command();
+    let config = match get_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let app = Application::build(config).await?;
-    app.run_until_stopped().await?;
+    let telemetry = match init_telemetry(&config, &command) {
+        Ok(telemetry) => telemetry,
+        Err(e) => {
+            eprintln!("Failed to set up telemetry: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    Ok(())
+    let result = cli::run(command, config).await;
+    telemetry.shutdown();
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn init_telemetry(config: &Settings, command: &Command) -> Result<TelemetryGuard, String> {
+    config.telemetry.redaction.install();
+    if command.is_long_running() {
+        let (subscriber, telemetry) = get_subscriber(&config.telemetry)?;
+        init_subscriber(subscriber);
+        Ok(telemetry)
+    } else {
+        let (subscriber, telemetry) = get_subscriber_with_sink(&config.telemetry, std::io::stderr)?;
+        init_subscriber(subscriber);
+        Ok(telemetry)
+    }
 }
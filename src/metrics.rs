@@ -0,0 +1,262 @@
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error,
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use sqlx::PgPool;
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    time::Instant,
+};
+
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    subscriptions: IntCounterVec,
+    emails_sent: IntCounterVec,
+    db_pool_connections: IntGaugeVec,
+    queue_depth: IntGaugeVec,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SubscriptionEvent {
+    Subscribed,
+    Confirmed,
+    Unsubscribed,
+}
+
+impl std::fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Metrics").finish_non_exhaustive()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some(String::from("newsletter")), None)
+            .expect("Failed to create metrics registry");
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["method", "route", "status"],
+        )
+        .expect("Failed to create metric");
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by route and status",
+            ),
+            &["method", "route", "status"],
+        )
+        .expect("Failed to create metric");
+        let subscriptions = IntCounterVec::new(
+            Opts::new("subscriptions_total", "Subscription funnel events"),
+            &["event"],
+        )
+        .expect("Failed to create metric");
+        let emails_sent = IntCounterVec::new(
+            Opts::new("emails_sent_total", "Email send attempts by outcome"),
+            &["provider", "outcome", "error_class"],
+        )
+        .expect("Failed to create metric");
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Database pool connections by state"),
+            &["state"],
+        )
+        .expect("Failed to create metric");
+        let queue_depth = IntGaugeVec::new(
+            Opts::new("queue_depth", "Pending jobs per background queue"),
+            &["queue"],
+        )
+        .expect("Failed to create metric");
+
+        for collector in [
+            Box::new(http_requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(http_request_duration.clone()),
+            Box::new(subscriptions.clone()),
+            Box::new(emails_sent.clone()),
+            Box::new(db_pool_connections.clone()),
+            Box::new(queue_depth.clone()),
+        ] {
+            registry
+                .register(collector)
+                .expect("Failed to register metric");
+        }
+
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            subscriptions,
+            emails_sent,
+            db_pool_connections,
+            queue_depth,
+        }
+    }
+
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, seconds: f64) {
+        let status = status.to_string();
+        let labels = [method, route, status.as_str()];
+        self.http_requests.with_label_values(&labels).inc();
+        self.http_request_duration
+            .with_label_values(&labels)
+            .observe(seconds);
+    }
+
+    pub fn subscription_event(&self, event: SubscriptionEvent) {
+        let event = match event {
+            SubscriptionEvent::Subscribed => "subscribed",
+            SubscriptionEvent::Confirmed => "confirmed",
+            SubscriptionEvent::Unsubscribed => "unsubscribed",
+        };
+        self.subscriptions.with_label_values(&[event]).inc();
+    }
+
+    pub fn email_sent(&self, provider: &str, result: &Result<(), reqwest::Error>) {
+        let (outcome, error_class) = match result {
+            Ok(()) => ("success", "none"),
+            Err(e) => ("failure", classify_email_error(e)),
+        };
+        self.emails_sent
+            .with_label_values(&[provider, outcome, error_class])
+            .inc();
+    }
+
+    pub fn set_queue_depth(&self, queue: &str, depth: i64) {
+        self.queue_depth.with_label_values(&[queue]).set(depth);
+    }
+
+    pub fn observe_pool(&self, pool: &PgPool) {
+        let size = i64::from(pool.size());
+        let idle = pool.num_idle() as i64;
+        let max = i64::from(pool.options().get_max_connections());
+        self.db_pool_connections
+            .with_label_values(&["in_use"])
+            .set(size - idle);
+        self.db_pool_connections
+            .with_label_values(&["idle"])
+            .set(idle);
+        self.db_pool_connections
+            .with_label_values(&["max"])
+            .set(max);
+    }
+
+    pub fn render(&self) -> String {
+        let mut buffer = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("Failed to encode metrics");
+
+        String::from_utf8(buffer).expect("Metrics are always valid utf-8")
+    }
+}
+
+pub struct RequestMetrics(pub Metrics);
+
+pub struct RequestMetricsMiddleware<S> {
+    service: S,
+    metrics: Metrics,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware {
+            service,
+            metrics: self.0.clone(),
+        }))
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let metrics = self.metrics.clone();
+        let method = req.method().to_string();
+        let started = Instant::now();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await;
+            let (route, status) = match &res {
+                Ok(res) => (res.request().match_pattern(), res.status()),
+                Err(e) => (None, e.as_response_error().status_code()),
+            };
+            // Unmatched paths are collapsed to keep label cardinality bounded.
+            metrics.observe_request(
+                &method,
+                route.as_deref().unwrap_or("unmatched"),
+                status.as_u16(),
+                started.elapsed().as_secs_f64(),
+            );
+
+            res
+        })
+    }
+}
+
+fn classify_email_error(e: &reqwest::Error) -> &'static str {
+    if e.is_timeout() {
+        "timeout"
+    } else if e.is_connect() {
+        "connect"
+    } else if let Some(status) = e.status() {
+        if status.is_client_error() {
+            "client_error"
+        } else {
+            "server_error"
+        }
+    } else {
+        "other"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::{Metrics, SubscriptionEvent};
+
+    #[test]
+    fn recorded_metrics_are_rendered() {
+        let metrics = Metrics::new();
+        metrics.observe_request("POST", "/subscribe", 200, 0.01);
+        metrics.subscription_event(SubscriptionEvent::Confirmed);
+        metrics.set_queue_depth("webhooks", 3);
+
+        let output = metrics.render();
+
+        assert!(output.contains(
+            r#"newsletter_http_requests_total{method="POST",route="/subscribe",status="200"} 1"#
+        ));
+        assert!(output.contains(r#"newsletter_subscriptions_total{event="confirmed"} 1"#));
+        assert!(output.contains(r#"newsletter_queue_depth{queue="webhooks"} 3"#));
+    }
+}
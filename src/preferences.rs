@@ -0,0 +1,589 @@
+use crate::domain::{Email, Locale, SubscriberName};
+use crate::lists::{self, Membership};
+use crate::webhooks::{self, WebhookEvent};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const UNIQUE_CONSTRAINT_VIOLATION_CODE: &str = "23505";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Frequency {
+    pub const ALL: [Self; 3] = [Self::Daily, Self::Weekly, Self::Monthly];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+            Self::Monthly => "monthly",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.as_str() == value)
+    }
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema, Debug)]
+pub struct Topic {
+    #[schema(example = "product-updates")]
+    pub slug: String,
+    #[schema(example = "Product updates")]
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct Preferences {
+    pub subscriber_id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub locale: Locale,
+    pub status: String,
+    pub frequency: Frequency,
+    pub paused: bool,
+    pub topics: Vec<String>,
+    pub memberships: Vec<Membership>,
+}
+
+/// What the subscriber can change from the preference page.
+#[derive(Debug)]
+pub struct PreferenceUpdate {
+    pub name: SubscriberName,
+    pub frequency: Frequency,
+    pub paused: bool,
+    pub topics: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LinkError {
+    Invalid,
+    Expired,
+}
+
+/// `{subscriber_id}.{expires_at}.{signature}`, so links work without
+/// storing anything and can't be forged or extended.
+pub fn link_token(key: &Secret<String>, subscriber_id: Uuid, expires_at: DateTime<Utc>) -> String {
+    let claims = format!("{}.{}", subscriber_id, expires_at.timestamp());
+    let signature = URL_SAFE_NO_PAD.encode(mac(key, &claims).finalize().into_bytes());
+    format!("{}.{}", claims, signature)
+}
+
+pub fn verify_link_token(key: &Secret<String>, token: &str) -> Result<Uuid, LinkError> {
+    let (claims, signature) = token.rsplit_once('.').ok_or(LinkError::Invalid)?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| LinkError::Invalid)?;
+    mac(key, claims)
+        .verify_slice(&signature)
+        .map_err(|_| LinkError::Invalid)?;
+
+    let (subscriber_id, expires_at) = claims.split_once('.').ok_or(LinkError::Invalid)?;
+    let subscriber_id = Uuid::parse_str(subscriber_id).map_err(|_| LinkError::Invalid)?;
+    let expires_at: i64 = expires_at.parse().map_err(|_| LinkError::Invalid)?;
+    if expires_at < Utc::now().timestamp() {
+        return Err(LinkError::Expired);
+    }
+
+    Ok(subscriber_id)
+}
+
+fn mac(key: &Secret<String>, claims: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(claims.as_bytes());
+    mac
+}
+
+/// The confirmed subscriber a preference link may be sent to.
+#[tracing::instrument(name = "Looking up a subscriber for a preference link", skip_all)]
+pub async fn find_confirmed(
+    connection: &PgPool,
+    publication_id: &str,
+    email: &str,
+) -> Result<Option<(Uuid, Locale)>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT id, locale FROM subscriptions
+        WHERE publication_id = $1 AND email = $2 AND status = 'confirmed'"#,
+        publication_id,
+        email
+    )
+    .fetch_optional(connection)
+    .await?;
+
+    Ok(row.map(|r| (r.id, Locale::parse(r.locale).unwrap_or_default())))
+}
+
+#[tracing::instrument(name = "Loading subscriber preferences", skip(connection))]
+pub async fn load(
+    connection: &PgPool,
+    publication_id: &str,
+    subscriber_id: Uuid,
+) -> Result<Option<Preferences>, sqlx::Error> {
+    let Some(row) = sqlx::query!(
+        r#"SELECT s.email, s.name, s.locale, s.status, p.frequency AS "frequency?", p.paused_at
+        FROM subscriptions s
+        LEFT JOIN subscriber_preferences p ON p.subscriber_id = s.id
+        WHERE s.id = $1 AND s.publication_id = $2"#,
+        subscriber_id,
+        publication_id
+    )
+    .fetch_optional(connection)
+    .await?
+    else {
+        return Ok(None);
+    };
+    let topics = sqlx::query_scalar!(
+        "SELECT topic FROM subscriber_topics WHERE subscriber_id = $1 ORDER BY topic",
+        subscriber_id
+    )
+    .fetch_all(connection)
+    .await?;
+    let memberships = lists::memberships(connection, subscriber_id).await?;
+
+    Ok(Some(Preferences {
+        subscriber_id,
+        email: row.email,
+        name: row.name,
+        locale: Locale::parse(row.locale).unwrap_or_default(),
+        status: row.status,
+        frequency: row
+            .frequency
+            .as_deref()
+            .and_then(Frequency::parse)
+            .unwrap_or(Frequency::Weekly),
+        paused: row.paused_at.is_some(),
+        topics,
+        memberships,
+    }))
+}
+
+/// Replaces the subscriber's preferences. Unknown topics are ignored.
+#[tracing::instrument(name = "Saving subscriber preferences", skip(connection))]
+pub async fn save(
+    connection: &PgPool,
+    publication_id: &str,
+    subscriber_id: Uuid,
+    update: &PreferenceUpdate,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+    let mut transaction = connection.begin().await?;
+    sqlx::query!(
+        "UPDATE subscriptions SET name = $2 WHERE id = $1 AND publication_id = $3",
+        subscriber_id,
+        update.name.as_ref(),
+        publication_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    // Keeps the original pause date while the subscriber stays paused.
+    sqlx::query!(
+        r#"INSERT INTO subscriber_preferences (subscriber_id, frequency, paused_at, updated_at)
+        VALUES ($1, $2, CASE WHEN $3 THEN $4::timestamptz END, $4)
+        ON CONFLICT (subscriber_id) DO UPDATE SET
+            frequency = EXCLUDED.frequency,
+            paused_at = CASE WHEN $3
+                THEN COALESCE(subscriber_preferences.paused_at, EXCLUDED.paused_at) END,
+            updated_at = EXCLUDED.updated_at"#,
+        subscriber_id,
+        update.frequency.as_str(),
+        update.paused,
+        now
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM subscriber_topics WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        r#"INSERT INTO subscriber_topics (subscriber_id, publication_id, topic)
+        SELECT $1, publication_id, slug FROM topics
+        WHERE publication_id = $2 AND slug = ANY($3)"#,
+        subscriber_id,
+        publication_id,
+        &update.topics
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await
+}
+
+/// Takes the subscriber off `list`, or off every list and the newsletter
+/// as a whole when no list is given. Returns `false` when there was nothing
+/// to leave.
+#[tracing::instrument(name = "Unsubscribing a subscriber", skip(connection))]
+pub async fn unsubscribe(
+    connection: &PgPool,
+    publication_id: &str,
+    subscriber_id: Uuid,
+    list: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let mut transaction = connection.begin().await?;
+    let now = Utc::now();
+    if let Some(list) = list {
+        let Some(email) = sqlx::query_scalar!(
+            r#"UPDATE list_memberships m SET status = 'unsubscribed', unsubscribed_at = $3
+            FROM lists l, subscriptions s
+            WHERE l.list_id = m.list_id AND s.id = m.subscriber_id
+                AND l.publication_id = $4 AND s.publication_id = $4
+                AND m.subscriber_id = $1 AND l.slug = $2 AND m.status <> 'unsubscribed'
+            RETURNING s.email"#,
+            subscriber_id,
+            list,
+            now,
+            publication_id
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        else {
+            return Ok(false);
+        };
+        webhooks::enqueue(
+            &mut *transaction,
+            publication_id,
+            WebhookEvent::Unsubscribed,
+            serde_json::json!({
+                "subscriber_id": subscriber_id,
+                "email": email,
+                "list": list,
+            }),
+        )
+        .await?;
+        transaction.commit().await?;
+        return Ok(true);
+    }
+
+    sqlx::query!(
+        r#"UPDATE list_memberships SET status = 'unsubscribed', unsubscribed_at = $2
+        WHERE subscriber_id = $1 AND status <> 'unsubscribed'"#,
+        subscriber_id,
+        now
+    )
+    .execute(&mut *transaction)
+    .await?;
+    let Some(email) = sqlx::query_scalar!(
+        r#"UPDATE subscriptions SET status = 'unsubscribed', unsubscribed_at = $2
+        WHERE id = $1 AND publication_id = $3 AND status <> 'unsubscribed'
+        RETURNING email"#,
+        subscriber_id,
+        now,
+        publication_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await?
+    else {
+        return Ok(false);
+    };
+    webhooks::enqueue(
+        &mut *transaction,
+        publication_id,
+        WebhookEvent::Unsubscribed,
+        serde_json::json!({
+            "subscriber_id": subscriber_id,
+            "email": email,
+        }),
+    )
+    .await?;
+    transaction.commit().await?;
+
+    Ok(true)
+}
+
+/// Stores a pending change to `new_email` and returns the token that
+/// verifies it, or `None` when another subscription uses the address.
+#[tracing::instrument(name = "Requesting an email change", skip_all)]
+pub async fn request_email_change(
+    connection: &PgPool,
+    publication_id: &str,
+    subscriber_id: Uuid,
+    new_email: &Email,
+) -> Result<Option<String>, sqlx::Error> {
+    let taken = sqlx::query_scalar!(
+        r#"SELECT EXISTS(
+            SELECT 1 FROM subscriptions WHERE publication_id = $1 AND email = $2
+        ) AS "taken!""#,
+        publication_id,
+        new_email.as_ref()
+    )
+    .fetch_one(connection)
+    .await?;
+    if taken {
+        return Ok(None);
+    }
+
+    let token: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(25)
+        .map(char::from)
+        .collect();
+    sqlx::query!(
+        r#"INSERT INTO email_changes (token, subscriber_id, new_email, created_at)
+        VALUES ($1, $2, $3, $4)"#,
+        token,
+        subscriber_id,
+        new_email.as_ref(),
+        Utc::now()
+    )
+    .execute(connection)
+    .await?;
+
+    Ok(Some(token))
+}
+
+#[derive(Debug)]
+pub enum EmailChangeOutcome {
+    /// The token is valid and waiting to be confirmed.
+    Pending(Locale),
+    Changed {
+        old_email: String,
+        new_email: String,
+        locale: Locale,
+    },
+    /// The address was taken by another subscription in the meantime.
+    Taken(Locale),
+    Expired,
+    Invalid,
+}
+
+struct EmailChange {
+    subscriber_id: Uuid,
+    new_email: String,
+    old_email: String,
+    locale: Locale,
+}
+
+/// Looks at an email change token without applying it.
+#[tracing::instrument(name = "Checking an email change", skip_all)]
+pub async fn check_email_change(
+    connection: &PgPool,
+    publication_id: &str,
+    token: &str,
+    ttl: Duration,
+) -> Result<EmailChangeOutcome, sqlx::Error> {
+    Ok(
+        match find_email_change(connection, publication_id, token, ttl).await? {
+            Ok(change) => EmailChangeOutcome::Pending(change.locale),
+            Err(outcome) => outcome,
+        },
+    )
+}
+
+/// Moves the subscription to the new address. The subscriber keeps its id,
+/// so its tokens, preferences and webhook history stay attached.
+#[tracing::instrument(name = "Applying an email change", skip_all)]
+pub async fn apply_email_change(
+    connection: &PgPool,
+    publication_id: &str,
+    token: &str,
+    ttl: Duration,
+) -> Result<EmailChangeOutcome, sqlx::Error> {
+    let change = match find_email_change(connection, publication_id, token, ttl).await? {
+        Ok(change) => change,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    let mut transaction = connection.begin().await?;
+    let updated = sqlx::query!(
+        "UPDATE subscriptions SET email = $2 WHERE id = $1 AND email = $3",
+        change.subscriber_id,
+        change.new_email,
+        change.old_email
+    )
+    .execute(&mut *transaction)
+    .await;
+    match updated {
+        Ok(result) if result.rows_affected() == 0 => {
+            // Changed through another token since the lookup.
+            return Ok(EmailChangeOutcome::Invalid);
+        }
+        Ok(_) => {}
+        Err(sqlx::Error::Database(e))
+            if e.code().as_deref() == Some(UNIQUE_CONSTRAINT_VIOLATION_CODE) =>
+        {
+            return Ok(EmailChangeOutcome::Taken(change.locale));
+        }
+        Err(e) => return Err(e),
+    }
+    sqlx::query!(
+        "UPDATE email_changes SET confirmed_at = $2 WHERE token = $1",
+        token,
+        Utc::now()
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM email_changes WHERE subscriber_id = $1 AND confirmed_at IS NULL",
+        change.subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(EmailChangeOutcome::Changed {
+        old_email: change.old_email,
+        new_email: change.new_email,
+        locale: change.locale,
+    })
+}
+
+async fn find_email_change(
+    connection: &PgPool,
+    publication_id: &str,
+    token: &str,
+    ttl: Duration,
+) -> Result<Result<EmailChange, EmailChangeOutcome>, sqlx::Error> {
+    let Some(row) = sqlx::query!(
+        r#"SELECT c.subscriber_id, c.new_email, c.created_at, s.email, s.locale
+        FROM email_changes c
+        JOIN subscriptions s ON s.id = c.subscriber_id
+        WHERE c.token = $1 AND c.confirmed_at IS NULL AND s.status <> 'unsubscribed'
+            AND s.publication_id = $2"#,
+        token,
+        publication_id
+    )
+    .fetch_optional(connection)
+    .await?
+    else {
+        return Ok(Err(EmailChangeOutcome::Invalid));
+    };
+    if row.created_at + ttl < Utc::now() {
+        return Ok(Err(EmailChangeOutcome::Expired));
+    }
+
+    Ok(Ok(EmailChange {
+        subscriber_id: row.subscriber_id,
+        new_email: row.new_email,
+        old_email: row.email,
+        locale: Locale::parse(row.locale).unwrap_or_default(),
+    }))
+}
+
+#[tracing::instrument(name = "Listing topics", skip(connection))]
+pub async fn list_topics(
+    connection: &PgPool,
+    publication_id: &str,
+) -> Result<Vec<Topic>, sqlx::Error> {
+    sqlx::query_as!(
+        Topic,
+        r#"SELECT slug, name, created_at FROM topics
+        WHERE publication_id = $1 ORDER BY name"#,
+        publication_id
+    )
+    .fetch_all(connection)
+    .await
+}
+
+#[tracing::instrument(name = "Creating a topic", skip(connection))]
+pub async fn create_topic(
+    connection: &PgPool,
+    publication_id: &str,
+    slug: &str,
+    name: &str,
+) -> Result<Topic, String> {
+    let valid_slug = !slug.is_empty()
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid_slug {
+        return Err(String::from(
+            "Slug must be lowercase letters, digits and dashes",
+        ));
+    }
+    if name.trim().is_empty() {
+        return Err(String::from("Name must not be empty"));
+    }
+
+    sqlx::query_as!(
+        Topic,
+        r#"INSERT INTO topics (publication_id, slug, name, created_at) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (publication_id, slug) DO NOTHING
+        RETURNING slug, name, created_at"#,
+        publication_id,
+        slug,
+        name.trim(),
+        Utc::now()
+    )
+    .fetch_optional(connection)
+    .await
+    .map_err(|e| format!("Failed to create topic: {}", e))?
+    .ok_or_else(|| format!("Topic {} already exists", slug))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::preferences::{link_token, verify_link_token, Frequency, LinkError};
+    use chrono::{Duration, Utc};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn key(value: &str) -> Secret<String> {
+        Secret::new(value.to_owned())
+    }
+
+    #[test]
+    fn link_tokens_round_trip() {
+        let subscriber_id = Uuid::new_v4();
+        let token = link_token(&key("key"), subscriber_id, Utc::now() + Duration::hours(1));
+
+        assert_eq!(verify_link_token(&key("key"), &token), Ok(subscriber_id));
+    }
+
+    #[test]
+    fn tampered_or_foreign_tokens_are_invalid() {
+        let expires_at = Utc::now() + Duration::hours(1);
+        let token = link_token(&key("key"), Uuid::new_v4(), expires_at);
+        let (_, signature) = token.rsplit_once('.').unwrap();
+        let forged = format!(
+            "{}.{}.{}",
+            Uuid::new_v4(),
+            expires_at.timestamp(),
+            signature
+        );
+
+        assert_eq!(
+            verify_link_token(&key("other"), &token),
+            Err(LinkError::Invalid)
+        );
+        assert_eq!(
+            verify_link_token(&key("key"), &forged),
+            Err(LinkError::Invalid)
+        );
+        assert_eq!(
+            verify_link_token(&key("key"), "garbage"),
+            Err(LinkError::Invalid)
+        );
+    }
+
+    #[test]
+    fn expired_tokens_are_rejected() {
+        let token = link_token(&key("key"), Uuid::new_v4(), Utc::now() - Duration::hours(1));
+
+        assert_eq!(
+            verify_link_token(&key("key"), &token),
+            Err(LinkError::Expired)
+        );
+    }
+
+    #[test]
+    fn frequencies_round_trip() {
+        for frequency in Frequency::ALL {
+            assert_eq!(Frequency::parse(frequency.as_str()), Some(frequency));
+        }
+        assert_eq!(Frequency::parse("hourly"), None);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::config::Settings;
+use crate::email_client::EmailClient;
+use crate::metrics::Metrics;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Built from the top-level settings. It serves every host no other
+/// publication claims and owns everything stored before publications
+/// existed.
+pub const DEFAULT_PUBLICATION: &str = "default";
+
+/// A newsletter hosted by this deployment. Subscribers, lists, topics, API
+/// keys and webhooks all belong to exactly one.
+#[derive(Debug)]
+pub struct Publication {
+    pub id: String,
+    pub hosts: Vec<String>,
+    pub base_url: String,
+    pub email_client: EmailClient,
+}
+
+/// Every publication the deployment serves, the default one first.
+#[derive(Debug, Clone)]
+pub struct Publications {
+    publications: Vec<Arc<Publication>>,
+    trusted_proxies: Vec<IpAddr>,
+}
+
+impl Publications {
+    pub fn from_settings(settings: &Settings, metrics: &Metrics) -> Result<Self, String> {
+        let mut publications = vec![Arc::new(Publication {
+            id: DEFAULT_PUBLICATION.to_owned(),
+            hosts: vec![],
+            base_url: settings.application.base_url.clone(),
+            email_client: settings
+                .email_client
+                .client()?
+                .with_metrics(metrics.clone()),
+        })];
+        for (id, publication) in &settings.publications {
+            if let Some(host) = publication
+                .hosts
+                .iter()
+                .find(|host| publications.iter().any(|p| p.hosts.contains(host)))
+            {
+                return Err(format!("{} is claimed by more than one publication", host));
+            }
+            let email_client = publication
+                .email_client
+                .client()
+                .map_err(|e| format!("publications.{}: {}", id, e))?;
+            publications.push(Arc::new(Publication {
+                id: id.clone(),
+                hosts: publication.hosts.clone(),
+                base_url: publication.base_url.clone(),
+                email_client: email_client.with_metrics(metrics.clone()),
+            }));
+        }
+
+        let trusted_proxies = settings
+            .application
+            .trusted_proxies
+            .iter()
+            .map(|proxy| {
+                proxy
+                    .parse()
+                    .map_err(|_| format!("{} is not an IP address", proxy))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            publications,
+            trusted_proxies,
+        })
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Arc<Publication>> {
+        self.publications
+            .iter()
+            .find(|publication| publication.id == id)
+    }
+
+    /// The publication serving `host`, which may carry a port.
+    pub fn for_host(&self, host: &str) -> &Arc<Publication> {
+        let host = match host.strip_prefix('[') {
+            Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+            None => host.split(':').next().unwrap_or_default(),
+        }
+        .to_ascii_lowercase();
+        self.publications
+            .iter()
+            .find(|publication| publication.hosts.contains(&host))
+            .unwrap_or(&self.publications[0])
+    }
+
+    /// Whether requests from `peer` may name the host they were meant for in
+    /// forwarded headers.
+    pub fn trusts_proxy(&self, peer: IpAddr) -> bool {
+        self.trusted_proxies.contains(&peer)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Publication>> {
+        self.publications.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{get_embedded_config, Enviroment, PublicationSettings};
+    use crate::metrics::Metrics;
+    use crate::publications::{Publications, DEFAULT_PUBLICATION};
+
+    fn publications(hosts: &[&[&str]]) -> Result<Publications, String> {
+        let mut settings = get_embedded_config(Enviroment::Test).expect("Failed to load config");
+        for (i, hosts) in hosts.iter().enumerate() {
+            settings.publications.insert(
+                format!("client-{}", i),
+                PublicationSettings {
+                    hosts: hosts.iter().map(|h| h.to_string()).collect(),
+                    base_url: format!("https://{}", hosts[0]),
+                    email_client: settings.email_client.clone(),
+                },
+            );
+        }
+        Publications::from_settings(&settings, &Metrics::new())
+    }
+
+    #[test]
+    fn hosts_pick_their_publication_and_everything_else_the_default() {
+        let publications = publications(&[&["news.acme.com", "acme.com"]]).unwrap();
+
+        assert_eq!(publications.for_host("news.acme.com").id, "client-0");
+        assert_eq!(publications.for_host("Acme.com:8000").id, "client-0");
+        assert_eq!(publications.for_host("other.com").id, DEFAULT_PUBLICATION);
+        assert_eq!(publications.for_host("[::1]:8000").id, DEFAULT_PUBLICATION);
+    }
+
+    #[test]
+    fn a_host_can_only_belong_to_one_publication() {
+        assert!(publications(&[&["acme.com"], &["acme.com"]]).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use super::AdminUser;
+use crate::{
+    api_keys::{self, ApiKey, ApiKeyScope, DEFAULT_RATE_LIMIT_PER_MINUTE},
+    routes::{api_v1::ErrorResponse, Tenant},
+};
+use actix_web::{delete, get, post, web, HttpResponse};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize, utoipa::ToSchema, Debug)]
+pub struct CreateApiKeyRequest {
+    #[schema(example = "Partner backend")]
+    name: String,
+    scopes: Vec<ApiKeyScope>,
+    /// Defaults to 60
+    rate_limit_per_minute: Option<i32>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    api_key: ApiKey,
+    /// The key itself, only shown once
+    key: String,
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/api-keys",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, description = "Key created", body = CreatedApiKey),
+        (status = 400, description = "Invalid name, scopes or rate limit", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials")
+    ),
+    security(("basic_auth" = []))
+)]
+#[post("/api-keys")]
+#[tracing::instrument(
+    name = "Creating an API key",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn create_api_key(
+    admin: AdminUser,
+    tenant: Tenant,
+    body: web::Json<CreateApiKeyRequest>,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    let rate_limit = body
+        .rate_limit_per_minute
+        .unwrap_or(DEFAULT_RATE_LIMIT_PER_MINUTE);
+    match api_keys::create_key(
+        &connection,
+        &tenant.id,
+        &body.name,
+        &body.scopes,
+        rate_limit,
+    )
+    .await
+    {
+        Ok((api_key, key)) => HttpResponse::Created().json(CreatedApiKey {
+            api_key,
+            key: key.expose_secret().to_owned(),
+        }),
+        Err(e) => HttpResponse::BadRequest().json(ErrorResponse::new(e)),
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/api-keys",
+    responses(
+        (status = 200, description = "Every key, including revoked ones", body = [ApiKey]),
+        (status = 401, description = "Missing or invalid credentials")
+    ),
+    security(("basic_auth" = []))
+)]
+#[get("/api-keys")]
+#[tracing::instrument(
+    name = "Listing API keys",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn list_api_keys(
+    admin: AdminUser,
+    tenant: Tenant,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    match api_keys::list_keys(&connection, &tenant.id).await {
+        Ok(keys) => HttpResponse::Ok().json(keys),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/api-keys/{key_id}",
+    params(("key_id" = Uuid, Path)),
+    responses(
+        (status = 204, description = "Key revoked"),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 404, description = "Unknown or already revoked key")
+    ),
+    security(("basic_auth" = []))
+)]
+#[delete("/api-keys/{key_id}")]
+#[tracing::instrument(
+    name = "Revoking an API key",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn revoke_api_key(
+    admin: AdminUser,
+    tenant: Tenant,
+    key_id: web::Path<Uuid>,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    match api_keys::revoke_key(&connection, &tenant.id, key_id.into_inner()).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
@@ -0,0 +1,87 @@
+use super::AdminUser;
+use crate::{
+    attributes::{self, AttributeDefinition, AttributeType},
+    routes::{api_v1::ErrorResponse, Tenant},
+};
+use actix_web::{get, post, web, HttpResponse};
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize, utoipa::ToSchema, Debug)]
+pub struct CreateAttributeRequest {
+    /// Lowercase letters, digits and underscores
+    #[schema(example = "country")]
+    name: String,
+    #[serde(rename = "type")]
+    kind: AttributeType,
+    /// Whether every new subscriber has to set it
+    #[serde(default)]
+    required: bool,
+    /// Limits a text attribute to these values
+    #[schema(example = json!(["CZ", "DE"]))]
+    allowed_values: Option<Vec<String>>,
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/attributes",
+    request_body = CreateAttributeRequest,
+    responses(
+        (status = 201, description = "Attribute defined", body = AttributeDefinition),
+        (status = 400, description = "Invalid or taken name, or invalid allowed values", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials")
+    ),
+    security(("basic_auth" = []))
+)]
+#[post("/attributes")]
+#[tracing::instrument(
+    name = "Defining an attribute",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn create_attribute(
+    admin: AdminUser,
+    tenant: Tenant,
+    body: web::Json<CreateAttributeRequest>,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    let body = body.into_inner();
+    match attributes::create_definition(
+        &connection,
+        &tenant.id,
+        &body.name,
+        body.kind,
+        body.required,
+        body.allowed_values,
+    )
+    .await
+    {
+        Ok(definition) => HttpResponse::Created().json(definition),
+        Err(e) => HttpResponse::BadRequest().json(ErrorResponse::new(e)),
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/attributes",
+    responses(
+        (status = 200, description = "Attributes subscribers can have", body = [AttributeDefinition]),
+        (status = 401, description = "Missing or invalid credentials")
+    ),
+    security(("basic_auth" = []))
+)]
+#[get("/attributes")]
+#[tracing::instrument(
+    name = "Listing attribute definitions",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn list_attributes(
+    admin: AdminUser,
+    tenant: Tenant,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    match attributes::definitions(&**connection, &tenant.id).await {
+        Ok(definitions) => HttpResponse::Ok().json(definitions),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
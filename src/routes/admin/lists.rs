@@ -0,0 +1,71 @@
+use super::AdminUser;
+use crate::{
+    lists::{self, List},
+    routes::{api_v1::ErrorResponse, Tenant},
+};
+use actix_web::{get, post, web, HttpResponse};
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize, utoipa::ToSchema, Debug)]
+pub struct CreateListRequest {
+    /// Lowercase letters, digits and dashes, used by `/subscribe`
+    #[schema(example = "weekly")]
+    slug: String,
+    #[schema(example = "Weekly digest")]
+    name: String,
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/lists",
+    request_body = CreateListRequest,
+    responses(
+        (status = 201, description = "List created", body = List),
+        (status = 400, description = "Invalid or taken slug, or empty name", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials")
+    ),
+    security(("basic_auth" = []))
+)]
+#[post("/lists")]
+#[tracing::instrument(
+    name = "Creating a list",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn create_list(
+    admin: AdminUser,
+    tenant: Tenant,
+    body: web::Json<CreateListRequest>,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    match lists::create_list(&connection, &tenant.id, &body.slug, &body.name).await {
+        Ok(list) => HttpResponse::Created().json(list),
+        Err(e) => HttpResponse::BadRequest().json(ErrorResponse::new(e)),
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/lists",
+    responses(
+        (status = 200, description = "Lists people can subscribe to", body = [List]),
+        (status = 401, description = "Missing or invalid credentials")
+    ),
+    security(("basic_auth" = []))
+)]
+#[get("/lists")]
+#[tracing::instrument(
+    name = "Listing lists",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn list_lists(
+    admin: AdminUser,
+    tenant: Tenant,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    match lists::list_lists(&connection, &tenant.id).await {
+        Ok(lists) => HttpResponse::Ok().json(lists),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
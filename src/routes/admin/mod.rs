@@ -0,0 +1,163 @@
+mod api_keys;
+mod attributes;
+mod lists;
+mod subscribers;
+mod tags;
+mod topics;
+mod webhooks;
+
+use super::{api_v1::json_config, Tenant};
+use crate::{
+    authentication::{manages_publication, validate_credentials, AuthError},
+    telemetry::describe_db_error,
+};
+use actix_web::{
+    dev::Payload,
+    error::{ErrorForbidden, ErrorInternalServerError, InternalError},
+    http::header::{self, HeaderMap, HeaderValue},
+    web::{self, ServiceConfig},
+    FromRequest, HttpRequest, HttpResponse,
+};
+use base64::Engine;
+use secrecy::Secret;
+use sqlx::PgPool;
+use std::{future::Future, pin::Pin};
+use uuid::Uuid;
+
+pub use api_keys::*;
+pub use attributes::*;
+pub use lists::*;
+pub use subscribers::*;
+pub use tags::*;
+pub use topics::*;
+pub use webhooks::*;
+
+/// Admin endpoints, mounted under `/admin` and protected by Basic auth
+/// against the `users` table. Admins only reach the publications they are
+/// bound to.
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.app_data(json_config())
+        .service(create_webhook)
+        .service(list_webhooks)
+        .service(deactivate_webhook)
+        .service(list_webhook_deliveries)
+        .service(replay_webhook_delivery)
+        .service(create_api_key)
+        .service(list_api_keys)
+        .service(revoke_api_key)
+        .service(create_topic)
+        .service(list_topics)
+        .service(create_list)
+        .service(list_lists)
+        .service(create_attribute)
+        .service(list_attributes)
+        .service(list_subscribers)
+        .service(list_tags)
+        .service(apply_tag)
+        .service(remove_tag);
+}
+
+#[derive(Debug)]
+pub struct AdminUser {
+    pub user_id: Uuid,
+    pub username: String,
+}
+
+impl FromRequest for AdminUser {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let credentials = basic_credentials(req.headers());
+        let connection = req.app_data::<web::Data<PgPool>>().cloned();
+        let tenant = Tenant::from_request(req, &mut Payload::None).into_inner();
+
+        Box::pin(async move {
+            let Some((username, password)) = credentials else {
+                return Err(unauthorized("Missing or malformed credentials"));
+            };
+            let connection = connection
+                .ok_or_else(|| ErrorInternalServerError("Database pool is not configured"))?;
+            let user_id = match validate_credentials(&connection, &username, password).await {
+                Ok(user_id) => user_id,
+                Err(AuthError::InvalidCredentials) => {
+                    return Err(unauthorized("Invalid credentials"))
+                }
+                Err(AuthError::Unexpected(e)) => {
+                    tracing::error!(error = %e, "Failed to validate credentials");
+                    return Err(ErrorInternalServerError("Failed to validate credentials"));
+                }
+            };
+            match manages_publication(&connection, user_id, &tenant?.id).await {
+                Ok(true) => Ok(AdminUser { user_id, username }),
+                Ok(false) => Err(ErrorForbidden("Not an admin of this publication")),
+                Err(e) => {
+                    tracing::error!(
+                        error = %describe_db_error(&e),
+                        "Failed to check publication access"
+                    );
+                    Err(ErrorInternalServerError(
+                        "Failed to check publication access",
+                    ))
+                }
+            }
+        })
+    }
+}
+
+fn basic_credentials(headers: &HeaderMap) -> Option<(String, Secret<String>)> {
+    let encoded = headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+
+    Some((username.to_owned(), Secret::new(password.to_owned())))
+}
+
+fn unauthorized(reason: &'static str) -> actix_web::Error {
+    let mut response = HttpResponse::Unauthorized().finish();
+    response.headers_mut().insert(
+        header::WWW_AUTHENTICATE,
+        HeaderValue::from_static(r#"Basic realm="admin""#),
+    );
+    InternalError::from_response(reason, response).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::admin::basic_credentials;
+    use actix_web::http::header::{self, HeaderMap, HeaderValue};
+    use secrecy::ExposeSecret;
+
+    fn headers(authorization: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static(authorization),
+        );
+        headers
+    }
+
+    #[test]
+    fn basic_credentials_are_decoded() {
+        // admin:pass:word
+        let (username, password) =
+            basic_credentials(&headers("Basic YWRtaW46cGFzczp3b3Jk")).unwrap();
+
+        assert_eq!(username, "admin");
+        assert_eq!(password.expose_secret(), "pass:word");
+    }
+
+    #[test]
+    fn other_schemes_are_ignored() {
+        assert!(basic_credentials(&headers("Bearer YWRtaW46cGFzcw==")).is_none());
+        assert!(basic_credentials(&headers("Basic not base64")).is_none());
+        assert!(basic_credentials(&HeaderMap::new()).is_none());
+    }
+}
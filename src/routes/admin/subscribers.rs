@@ -0,0 +1,190 @@
+use super::AdminUser;
+use crate::{
+    attributes::{self, Attributes},
+    routes::{
+        api_v1::{ErrorResponse, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE},
+        Tenant,
+    },
+    telemetry::describe_db_error,
+};
+use actix_web::{get, web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct AdminSubscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    locale: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    #[schema(value_type = Object, example = json!({"country": "DE", "seats": 12}))]
+    attributes: Value,
+    #[schema(example = json!(["beta-tester"]))]
+    tags: Vec<String>,
+}
+
+/// The query string, which can repeat `attributes[name]` for any attribute.
+#[derive(Debug, Default, PartialEq)]
+struct SubscriberFilter {
+    status: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    attributes: Attributes,
+    tag: Option<String>,
+}
+
+impl SubscriberFilter {
+    fn parse(pairs: Vec<(String, String)>) -> Result<Self, String> {
+        let mut filter = Self::default();
+        for (key, value) in pairs {
+            let number = || {
+                value
+                    .parse::<i64>()
+                    .map_err(|_| format!("{} must be a number", key))
+            };
+            match key.as_str() {
+                "status" => filter.status = Some(value.clone()),
+                "limit" => filter.limit = Some(number()?),
+                "offset" => filter.offset = Some(number()?),
+                "tag" => filter.tag = Some(value.clone()),
+                _ => {
+                    let Some(name) = key
+                        .strip_prefix("attributes[")
+                        .and_then(|k| k.strip_suffix(']'))
+                    else {
+                        return Err(format!("Unknown parameter {}", key));
+                    };
+                    filter
+                        .attributes
+                        .insert(name.to_owned(), Value::String(value));
+                }
+            }
+        }
+
+        Ok(filter)
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/subscribers",
+    params(
+        ("status" = Option<String>, Query, description = "`confirmed`, `pending_confirmation` or `unsubscribed`"),
+        ("attributes[name]" = Option<String>, Query, description = "Only subscribers with this attribute value, repeatable"),
+        ("tag" = Option<String>, Query, description = "Only subscribers with this tag"),
+        ("limit" = Option<i64>, Query, description = "Defaults to 100, at most 1000"),
+        ("offset" = Option<i64>, Query)
+    ),
+    responses(
+        (status = 200, description = "Subscribers, oldest first", body = [AdminSubscriber]),
+        (status = 400, description = "Invalid paging or unknown attribute", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials")
+    ),
+    security(("basic_auth" = []))
+)]
+#[get("/subscribers")]
+#[tracing::instrument(
+    name = "Listing subscribers",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn list_subscribers(
+    admin: AdminUser,
+    tenant: Tenant,
+    query: web::Query<Vec<(String, String)>>,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    let filter = match SubscriberFilter::parse(query.into_inner()) {
+        Ok(filter) => filter,
+        Err(e) => return HttpResponse::BadRequest().json(ErrorResponse::new(e)),
+    };
+    let limit = filter.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let offset = filter.offset.unwrap_or(0);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) || offset < 0 {
+        return HttpResponse::BadRequest().json(ErrorResponse::new(format!(
+            "limit must be between 1 and {} and offset must not be negative",
+            MAX_PAGE_SIZE
+        )));
+    }
+    let definitions = match attributes::definitions(&**connection, &tenant.id).await {
+        Ok(definitions) => definitions,
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+    let attributes = match attributes::validate_filter(&definitions, filter.attributes) {
+        Ok(attributes) => attributes,
+        Err(e) => return HttpResponse::BadRequest().json(ErrorResponse::new(e)),
+    };
+
+    let result = sqlx::query_as!(
+        AdminSubscriber,
+        r#"SELECT s.id, s.email, s.name, s.locale, s.status, s.subscribed_at, s.attributes,
+            ARRAY(SELECT t.tag FROM subscriber_tags t WHERE t.subscriber_id = s.id ORDER BY t.tag)
+                AS "tags!"
+        FROM subscriptions s
+        WHERE s.publication_id = $1 AND ($2::TEXT IS NULL OR s.status = $2)
+            AND s.attributes @> $3
+            AND ($6::TEXT IS NULL
+                OR EXISTS (SELECT 1 FROM subscriber_tags t WHERE t.subscriber_id = s.id AND t.tag = $6))
+        ORDER BY s.subscribed_at, s.id
+        LIMIT $4 OFFSET $5"#,
+        tenant.id,
+        filter.status,
+        Value::Object(attributes),
+        limit,
+        offset,
+        filter.tag
+    )
+    .fetch_all(&**connection)
+    .await;
+
+    match result {
+        Ok(subscribers) => HttpResponse::Ok().json(subscribers),
+        Err(e) => {
+            tracing::error!(error = %describe_db_error(&e), "Failed to list subscribers");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::admin::subscribers::SubscriberFilter;
+    use serde_json::json;
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn filters_are_read_from_the_query() {
+        let filter = SubscriberFilter::parse(pairs(&[
+            ("status", "confirmed"),
+            ("attributes[country]", "DE"),
+            ("attributes[seats]", "12"),
+            ("tag", "beta-tester"),
+            ("limit", "10"),
+        ]))
+        .unwrap();
+
+        assert_eq!(filter.status.as_deref(), Some("confirmed"));
+        assert_eq!(filter.tag.as_deref(), Some("beta-tester"));
+        assert_eq!(filter.limit, Some(10));
+        assert_eq!(
+            serde_json::Value::Object(filter.attributes),
+            json!({"country": "DE", "seats": "12"})
+        );
+    }
+
+    #[test]
+    fn unknown_parameters_are_rejected() {
+        assert!(SubscriberFilter::parse(pairs(&[("country", "DE")])).is_err());
+        assert!(SubscriberFilter::parse(pairs(&[("limit", "ten")])).is_err());
+    }
+}
@@ -0,0 +1,346 @@
+use super::AdminUser;
+use crate::{
+    attributes::{self, AttributeDefinition, Attributes},
+    cli::csv_reader,
+    domain::Email,
+    routes::{api_v1::ErrorResponse, Tenant},
+    tags::{self, Selection, TagCount, TagError},
+    telemetry::describe_db_error,
+};
+use actix_web::{get, http::header, post, web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize, utoipa::ToSchema, Debug)]
+pub struct BulkTagRequest {
+    /// Only subscribers with these addresses
+    emails: Option<Vec<String>>,
+    /// Only subscribers matching every condition of the filter
+    filter: Option<SubscriberFilterRequest>,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema, Debug, Default)]
+pub struct SubscriberFilterRequest {
+    /// `confirmed`, `pending_confirmation` or `unsubscribed`
+    status: Option<String>,
+    #[serde(default)]
+    #[schema(value_type = Object, example = json!({"country": "DE"}))]
+    attributes: Attributes,
+    /// Only subscribers that already have this tag
+    #[schema(example = "conference-2026")]
+    tag: Option<String>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct BulkTagResponse {
+    /// Subscribers whose tags changed
+    updated: u64,
+    /// Given addresses that aren't valid emails
+    invalid_emails: Vec<String>,
+    /// Valid addresses no subscriber has
+    unmatched_emails: Vec<String>,
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/tags",
+    responses(
+        (status = 200, description = "Tags in use and how many subscribers have them", body = [TagCount]),
+        (status = 401, description = "Missing or invalid credentials")
+    ),
+    security(("basic_auth" = []))
+)]
+#[get("/tags")]
+#[tracing::instrument(
+    name = "Listing tags",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn list_tags(
+    admin: AdminUser,
+    tenant: Tenant,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    match tags::list_tags(&connection, &tenant.id).await {
+        Ok(tags) => HttpResponse::Ok().json(tags),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/tags/{tag}/apply",
+    params(("tag" = String, Path, description = "Lowercase letters, digits and dashes")),
+    request_body(
+        description = "A selection, or an uploaded list of emails: a CSV file with an `email` column or one address per line",
+        content(
+            (BulkTagRequest = "application/json"),
+            (String = "text/csv"),
+            (String = "text/plain")
+        )
+    ),
+    responses(
+        (status = 200, description = "The selected subscribers have the tag", body = BulkTagResponse),
+        (status = 400, description = "Invalid tag, upload or selection, or no selection", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials")
+    ),
+    security(("basic_auth" = []))
+)]
+#[post("/tags/{tag}/apply")]
+#[tracing::instrument(
+    name = "Applying a tag",
+    skip(admin, tenant, request, body, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn apply_tag(
+    admin: AdminUser,
+    tenant: Tenant,
+    tag: web::Path<String>,
+    request: HttpRequest,
+    body: web::Bytes,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    bulk_update(
+        &connection,
+        &tenant.id,
+        &tag,
+        &request,
+        &body,
+        BulkAction::Apply,
+    )
+    .await
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/tags/{tag}/remove",
+    params(("tag" = String, Path)),
+    request_body(
+        description = "A selection, or an uploaded list of emails: a CSV file with an `email` column or one address per line",
+        content(
+            (BulkTagRequest = "application/json"),
+            (String = "text/csv"),
+            (String = "text/plain")
+        )
+    ),
+    responses(
+        (status = 200, description = "The selected subscribers don't have the tag anymore", body = BulkTagResponse),
+        (status = 400, description = "Invalid tag, upload or selection, or no selection", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials")
+    ),
+    security(("basic_auth" = []))
+)]
+#[post("/tags/{tag}/remove")]
+#[tracing::instrument(
+    name = "Removing a tag",
+    skip(admin, tenant, request, body, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn remove_tag(
+    admin: AdminUser,
+    tenant: Tenant,
+    tag: web::Path<String>,
+    request: HttpRequest,
+    body: web::Bytes,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    bulk_update(
+        &connection,
+        &tenant.id,
+        &tag,
+        &request,
+        &body,
+        BulkAction::Remove,
+    )
+    .await
+}
+
+enum BulkAction {
+    Apply,
+    Remove,
+}
+
+async fn bulk_update(
+    connection: &PgPool,
+    publication_id: &str,
+    tag: &str,
+    request: &HttpRequest,
+    body: &[u8],
+    action: BulkAction,
+) -> HttpResponse {
+    let body = match bulk_request(request, body) {
+        Ok(body) => body,
+        Err(e) => return HttpResponse::BadRequest().json(ErrorResponse::new(e)),
+    };
+    let definitions = match attributes::definitions(connection, publication_id).await {
+        Ok(definitions) => definitions,
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+    let (selection, invalid_emails) = match selection(&definitions, body) {
+        Ok(selection) => selection,
+        Err(e) => return HttpResponse::BadRequest().json(ErrorResponse::new(e)),
+    };
+    let updated = match action {
+        BulkAction::Apply => tags::apply_tag(connection, publication_id, tag, &selection).await,
+        BulkAction::Remove => tags::remove_tag(connection, publication_id, tag, &selection).await,
+    };
+    let updated = match updated {
+        Ok(updated) => updated,
+        Err(TagError::InvalidTag(e)) => {
+            return HttpResponse::BadRequest().json(ErrorResponse::new(e))
+        }
+        Err(TagError::Unexpected(e)) => {
+            tracing::error!(error = %describe_db_error(&e), "Failed to update tags");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let unmatched_emails = match &selection.emails {
+        Some(emails) => match tags::unmatched_emails(connection, publication_id, emails).await {
+            Ok(unmatched) => unmatched,
+            Err(_) => return HttpResponse::InternalServerError().finish(),
+        },
+        None => vec![],
+    };
+
+    HttpResponse::Ok().json(BulkTagResponse {
+        updated,
+        invalid_emails,
+        unmatched_emails,
+    })
+}
+
+/// Reads a JSON selection, or the emails of an uploaded file.
+fn bulk_request(request: &HttpRequest, body: &[u8]) -> Result<BulkTagRequest, String> {
+    let content_type = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.split(';').next())
+        .map(|h| h.trim().to_ascii_lowercase());
+    let emails = match content_type.as_deref() {
+        Some("application/json") => {
+            return serde_json::from_slice(body).map_err(|e| e.to_string());
+        }
+        Some("text/csv") => {
+            let mut reader = csv_reader(body);
+            let column = reader
+                .headers()
+                .map_err(|e| e.to_string())?
+                .iter()
+                .position(|h| h.eq_ignore_ascii_case("email"))
+                .ok_or_else(|| String::from("The CSV file has no email column"))?;
+            reader
+                .records()
+                .map(|record| {
+                    record
+                        .map(|r| r.get(column).unwrap_or_default().to_owned())
+                        .map_err(|e| e.to_string())
+                })
+                .collect::<Result<_, _>>()?
+        }
+        Some("text/plain") => std::str::from_utf8(body)
+            .map_err(|_| String::from("The file is not UTF-8 text"))?
+            .lines()
+            .map(str::to_owned)
+            .collect(),
+        _ => {
+            return Err(String::from(
+                "Send a JSON selection, or emails as text/csv or text/plain",
+            ))
+        }
+    };
+
+    Ok(BulkTagRequest {
+        emails: Some(emails),
+        filter: None,
+    })
+}
+
+/// Bulk operations need an explicit selection, an empty filter picks
+/// everyone. Returns the selection and the emails that aren't valid.
+fn selection(
+    definitions: &[AttributeDefinition],
+    body: BulkTagRequest,
+) -> Result<(Selection, Vec<String>), String> {
+    if body.emails.is_none() && body.filter.is_none() {
+        return Err(String::from(
+            "Select subscribers by emails, a filter or both",
+        ));
+    }
+    let filter = body.filter.unwrap_or_default();
+    let (emails, invalid) = match body.emails {
+        Some(emails) => {
+            let (valid, invalid) = parse_emails(emails);
+            (Some(valid), invalid)
+        }
+        None => (None, vec![]),
+    };
+
+    let selection = Selection {
+        status: filter.status,
+        attributes: attributes::validate_filter(definitions, filter.attributes)?,
+        tag: filter.tag,
+        emails,
+    };
+    Ok((selection, invalid))
+}
+
+/// Splits the addresses into valid ones, lowercased and without duplicates,
+/// and invalid ones. Blank entries are left out.
+fn parse_emails(emails: Vec<String>) -> (Vec<String>, Vec<String>) {
+    let mut valid = vec![];
+    let mut invalid = vec![];
+    for email in emails {
+        let trimmed = email.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        match Email::parse(trimmed.to_lowercase()) {
+            Ok(parsed) => valid.push(parsed.as_ref().to_owned()),
+            Err(_) => invalid.push(email),
+        }
+    }
+    valid.sort();
+    valid.dedup();
+
+    (valid, invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::admin::tags::{bulk_request, parse_emails};
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn emails_are_normalised_and_invalid_ones_reported() {
+        let (valid, invalid) = parse_emails(vec![
+            String::from(" Ursula@Example.com "),
+            String::from("ursula@example.com"),
+            String::from(""),
+            String::from("not-an-email"),
+        ]);
+
+        assert_eq!(valid, ["ursula@example.com"]);
+        assert_eq!(invalid, ["not-an-email"]);
+    }
+
+    #[test]
+    fn emails_are_read_from_uploads() {
+        let csv = TestRequest::default()
+            .insert_header(("Content-Type", "text/csv; charset=utf-8"))
+            .to_http_request();
+        let text = TestRequest::default()
+            .insert_header(("Content-Type", "text/plain"))
+            .to_http_request();
+
+        let from_csv = bulk_request(&csv, b"name,Email\nUrsula, ursula@example.com\n").unwrap();
+        let from_text =
+            bulk_request(&text, b"ursula@example.com\r\noctavia@example.com\n").unwrap();
+
+        assert_eq!(from_csv.emails.unwrap(), ["ursula@example.com"]);
+        assert_eq!(
+            from_text.emails.unwrap(),
+            ["ursula@example.com", "octavia@example.com"]
+        );
+        assert!(bulk_request(&csv, b"name\nUrsula\n").is_err());
+    }
+}
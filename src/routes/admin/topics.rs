@@ -0,0 +1,71 @@
+use super::AdminUser;
+use crate::{
+    preferences::{self, Topic},
+    routes::{api_v1::ErrorResponse, Tenant},
+};
+use actix_web::{get, post, web, HttpResponse};
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize, utoipa::ToSchema, Debug)]
+pub struct CreateTopicRequest {
+    /// Lowercase letters, digits and dashes
+    #[schema(example = "product-updates")]
+    slug: String,
+    #[schema(example = "Product updates")]
+    name: String,
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/topics",
+    request_body = CreateTopicRequest,
+    responses(
+        (status = 201, description = "Topic created", body = Topic),
+        (status = 400, description = "Invalid or taken slug, or empty name", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials")
+    ),
+    security(("basic_auth" = []))
+)]
+#[post("/topics")]
+#[tracing::instrument(
+    name = "Creating a topic",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn create_topic(
+    admin: AdminUser,
+    tenant: Tenant,
+    body: web::Json<CreateTopicRequest>,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    match preferences::create_topic(&connection, &tenant.id, &body.slug, &body.name).await {
+        Ok(topic) => HttpResponse::Created().json(topic),
+        Err(e) => HttpResponse::BadRequest().json(ErrorResponse::new(e)),
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/topics",
+    responses(
+        (status = 200, description = "Topics subscribers can pick from", body = [Topic]),
+        (status = 401, description = "Missing or invalid credentials")
+    ),
+    security(("basic_auth" = []))
+)]
+#[get("/topics")]
+#[tracing::instrument(
+    name = "Listing topics",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn list_topics(
+    admin: AdminUser,
+    tenant: Tenant,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    match preferences::list_topics(&connection, &tenant.id).await {
+        Ok(topics) => HttpResponse::Ok().json(topics),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
@@ -0,0 +1,180 @@
+use super::AdminUser;
+use crate::{
+    routes::{api_v1::ErrorResponse, Tenant},
+    webhooks::{self, WebhookDelivery, WebhookEndpoint, WebhookEvent},
+};
+use actix_web::{delete, get, post, web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const DELIVERY_LOG_LIMIT: i64 = 100;
+
+#[derive(serde::Deserialize, utoipa::ToSchema, Debug)]
+pub struct CreateWebhookRequest {
+    #[schema(example = "https://crm.example.com/hooks/newsletter")]
+    url: String,
+    events: Vec<WebhookEvent>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct CreatedWebhook {
+    #[serde(flatten)]
+    endpoint: WebhookEndpoint,
+    /// HMAC key for the `X-Newsletter-Signature` header, only shown once
+    secret: String,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ReplayedDelivery {
+    delivery_id: Uuid,
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/webhooks",
+    request_body = CreateWebhookRequest,
+    responses(
+        (status = 201, description = "Endpoint registered", body = CreatedWebhook),
+        (status = 400, description = "Invalid url or events", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials")
+    ),
+    security(("basic_auth" = []))
+)]
+#[post("/webhooks")]
+#[tracing::instrument(
+    name = "Registering a webhook",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn create_webhook(
+    admin: AdminUser,
+    tenant: Tenant,
+    body: web::Json<CreateWebhookRequest>,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    match webhooks::create_endpoint(&connection, &tenant.id, &body.url, &body.events).await {
+        Ok((endpoint, secret)) => HttpResponse::Created().json(CreatedWebhook { endpoint, secret }),
+        Err(e) => HttpResponse::BadRequest().json(ErrorResponse::new(e)),
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/webhooks",
+    responses(
+        (status = 200, description = "Registered endpoints", body = [WebhookEndpoint]),
+        (status = 401, description = "Missing or invalid credentials")
+    ),
+    security(("basic_auth" = []))
+)]
+#[get("/webhooks")]
+#[tracing::instrument(
+    name = "Listing webhooks",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn list_webhooks(
+    admin: AdminUser,
+    tenant: Tenant,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    match webhooks::list_endpoints(&connection, &tenant.id).await {
+        Ok(endpoints) => HttpResponse::Ok().json(endpoints),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/webhooks/{endpoint_id}",
+    params(("endpoint_id" = Uuid, Path)),
+    responses(
+        (status = 204, description = "Endpoint deactivated, its delivery log is kept"),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 404, description = "Unknown endpoint")
+    ),
+    security(("basic_auth" = []))
+)]
+#[delete("/webhooks/{endpoint_id}")]
+#[tracing::instrument(
+    name = "Deactivating a webhook",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn deactivate_webhook(
+    admin: AdminUser,
+    tenant: Tenant,
+    endpoint_id: web::Path<Uuid>,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    match webhooks::deactivate_endpoint(&connection, &tenant.id, endpoint_id.into_inner()).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/webhooks/{endpoint_id}/deliveries",
+    params(("endpoint_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "The most recent deliveries, newest first", body = [WebhookDelivery]),
+        (status = 401, description = "Missing or invalid credentials")
+    ),
+    security(("basic_auth" = []))
+)]
+#[get("/webhooks/{endpoint_id}/deliveries")]
+#[tracing::instrument(
+    name = "Listing webhook deliveries",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn list_webhook_deliveries(
+    admin: AdminUser,
+    tenant: Tenant,
+    endpoint_id: web::Path<Uuid>,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    match webhooks::list_deliveries(
+        &connection,
+        &tenant.id,
+        endpoint_id.into_inner(),
+        DELIVERY_LOG_LIMIT,
+    )
+    .await
+    {
+        Ok(deliveries) => HttpResponse::Ok().json(deliveries),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    path = "/admin/webhooks/deliveries/{delivery_id}/replay",
+    params(("delivery_id" = Uuid, Path)),
+    responses(
+        (status = 202, description = "A copy of the delivery was queued", body = ReplayedDelivery),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 404, description = "Unknown delivery")
+    ),
+    security(("basic_auth" = []))
+)]
+#[post("/webhooks/deliveries/{delivery_id}/replay")]
+#[tracing::instrument(
+    name = "Replaying a webhook delivery",
+    skip(admin, tenant, connection),
+    fields(tenant = %tenant.id, admin = %admin.username)
+)]
+pub async fn replay_webhook_delivery(
+    admin: AdminUser,
+    tenant: Tenant,
+    delivery_id: web::Path<Uuid>,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    match webhooks::replay_delivery(&connection, &tenant.id, delivery_id.into_inner()).await {
+        Ok(Some(delivery_id)) => HttpResponse::Accepted().json(ReplayedDelivery { delivery_id }),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
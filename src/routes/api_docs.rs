@@ -0,0 +1,133 @@
+use actix_web::{get, http::header::ContentType, HttpResponse, Responder};
+use utoipa::{
+    openapi::security::{Http, HttpAuthScheme, SecurityScheme},
+    Modify, OpenApi,
+};
+
+const SWAGGER_UI_VERSION: &str = "5.17.14";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Newsletter API"),
+    paths(
+        super::health_check::health_check,
+        super::health_check::health_live,
+        super::health_check::health_ready,
+        super::metrics::export_metrics,
+        super::subscriptions::subscribe,
+        super::subscriptions_confirm::subscription_confirm,
+        super::subscriptions_confirm::subscription_confirm_submit,
+        super::preferences::preferences_page,
+        super::preferences::send_preferences_link,
+        super::preferences::update_preferences,
+        super::preferences::unsubscribe,
+        super::email_change::request_email_change,
+        super::email_change::email_change_page,
+        super::email_change::confirm_email_change,
+        super::api_v1::subscribe,
+        super::api_v1::confirm,
+        super::api_v1::list_subscribers,
+        super::admin::create_webhook,
+        super::admin::list_webhooks,
+        super::admin::deactivate_webhook,
+        super::admin::list_webhook_deliveries,
+        super::admin::replay_webhook_delivery,
+        super::admin::create_api_key,
+        super::admin::list_api_keys,
+        super::admin::revoke_api_key,
+        super::admin::create_topic,
+        super::admin::list_topics,
+        super::admin::create_list,
+        super::admin::list_lists,
+        super::admin::create_attribute,
+        super::admin::list_attributes,
+        super::admin::list_subscribers,
+        super::admin::list_tags,
+        super::admin::apply_tag,
+        super::admin::remove_tag,
+    ),
+    modifiers(&SecuritySchemes)
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "basic_auth",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Basic)),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+    }
+}
+
+#[get("/api/openapi.json")]
+pub async fn openapi_json() -> impl Responder {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+#[get("/api/docs")]
+pub async fn swagger_ui() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r##"<!DOCTYPE html>
+<html>
+<head>
+  <title>Newsletter API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@{version}/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@{version}/swagger-ui-bundle.js"></script>
+  <script>SwaggerUIBundle({{ url: "/api/openapi.json", dom_id: "#swagger-ui" }});</script>
+</body>
+</html>"##,
+            version = SWAGGER_UI_VERSION
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::api_docs::ApiDoc;
+    use utoipa::OpenApi;
+
+    #[test]
+    fn every_route_is_documented() {
+        let doc = ApiDoc::openapi();
+
+        for path in [
+            "/health-check",
+            "/health/live",
+            "/health/ready",
+            "/metrics",
+            "/subscribe",
+            "/subscriptions/confirm",
+            "/api/v1/subscriptions",
+            "/api/v1/subscriptions/confirm",
+            "/admin/webhooks",
+            "/admin/webhooks/{endpoint_id}/deliveries",
+            "/api/v1/subscribers",
+            "/admin/api-keys",
+            "/preferences",
+            "/preferences/link",
+            "/preferences/unsubscribe",
+            "/preferences/email",
+            "/preferences/email/confirm",
+            "/admin/topics",
+            "/admin/lists",
+            "/admin/attributes",
+            "/admin/subscribers",
+            "/admin/tags",
+            "/admin/tags/{tag}/apply",
+            "/admin/tags/{tag}/remove",
+        ] {
+            assert!(doc.paths.paths.contains_key(path), "{} is missing", path);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use super::ErrorResponse;
+use crate::api_keys::{self, ApiKeyScope, AuthenticatedKey, RateLimiter};
+use crate::publications::{Publication, Publications};
+use crate::telemetry::describe_db_error;
+use actix_web::{
+    dev::Payload,
+    error::InternalError,
+    http::header::{self, HeaderMap},
+    web, FromRequest, HttpRequest, HttpResponse,
+};
+use secrecy::Secret;
+use sqlx::PgPool;
+use std::{future::Future, pin::Pin, sync::Arc};
+
+/// The API key a request was made with, and the publication it belongs to.
+/// Extracting it authenticates the key, records its use and applies its rate
+/// limit.
+#[derive(Debug)]
+pub struct ApiClient {
+    pub key: AuthenticatedKey,
+    pub publication: Arc<Publication>,
+}
+
+impl ApiClient {
+    /// The response to send when the key does not grant `scope`.
+    pub fn missing_scope(&self, scope: ApiKeyScope) -> Option<HttpResponse> {
+        if self.key.allows(scope) {
+            return None;
+        }
+        Some(HttpResponse::Forbidden().json(ErrorResponse::new(format!(
+            "The API key lacks the {} scope",
+            scope.as_str()
+        ))))
+    }
+}
+
+impl FromRequest for ApiClient {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = bearer_token(req.headers());
+        let connection = req.app_data::<web::Data<PgPool>>().cloned();
+        let limiter = req.app_data::<web::Data<RateLimiter>>().cloned();
+        let publications = req.app_data::<web::Data<Publications>>().cloned();
+
+        Box::pin(async move {
+            let Some(token) = token else {
+                return Err(unauthorized("Missing API key"));
+            };
+            let (Some(connection), Some(limiter), Some(publications)) =
+                (connection, limiter, publications)
+            else {
+                return Err(error(
+                    HttpResponse::InternalServerError(),
+                    "API keys are not configured",
+                ));
+            };
+            let key = match api_keys::authenticate(&connection, &token).await {
+                Ok(Some(key)) => key,
+                Ok(None) => return Err(unauthorized("Invalid API key")),
+                Err(e) => {
+                    tracing::error!(
+                        error = %describe_db_error(&e),
+                        "Failed to authenticate API key"
+                    );
+                    return Err(error(
+                        HttpResponse::InternalServerError(),
+                        "Failed to authenticate API key",
+                    ));
+                }
+            };
+            // Keys of a publication removed from the configuration.
+            let Some(publication) = publications.get(&key.publication_id).cloned() else {
+                return Err(unauthorized("Invalid API key"));
+            };
+            if let Err(retry_after) = limiter.check(&key) {
+                let mut response = HttpResponse::TooManyRequests();
+                response.insert_header((
+                    header::RETRY_AFTER,
+                    retry_after.as_secs().max(1).to_string(),
+                ));
+                return Err(error(response, "Rate limit exceeded"));
+            }
+
+            Ok(ApiClient { key, publication })
+        })
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<Secret<String>> {
+    let token = headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")?
+        .trim();
+    (!token.is_empty()).then(|| Secret::new(token.to_owned()))
+}
+
+fn unauthorized(reason: &'static str) -> actix_web::Error {
+    let mut response = HttpResponse::Unauthorized();
+    response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+    error(response, reason)
+}
+
+fn error(mut response: actix_web::HttpResponseBuilder, reason: &'static str) -> actix_web::Error {
+    InternalError::from_response(reason, response.json(ErrorResponse::new(reason))).into()
+}
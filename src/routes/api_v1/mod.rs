@@ -0,0 +1,293 @@
+use crate::{
+    api_keys::ApiKeyScope,
+    attributes::Attributes,
+    config::{ApiSettings, ConfirmationSettings},
+    domain::{Email, Subscriber, SubscriberName},
+    i18n::Translations,
+    lists::DEFAULT_LIST,
+    metrics::Metrics,
+};
+use actix_web::{
+    error::InternalError,
+    http::header,
+    middleware::DefaultHeaders,
+    post,
+    web::{self, JsonConfig, ServiceConfig},
+    HttpRequest, HttpResponse,
+};
+use sqlx::PgPool;
+
+mod auth;
+mod subscribers;
+
+pub use auth::ApiClient;
+pub use subscribers::*;
+
+use super::{
+    subscriptions::{create_subscription, SubscriberError},
+    subscriptions_confirm::{confirm_subscription, ConfirmationOutcome},
+};
+
+#[derive(serde::Deserialize, utoipa::ToSchema, Debug)]
+pub struct SubscriptionRequest {
+    name: String,
+    email: String,
+    /// Preferred language, takes precedence over `Accept-Language`
+    #[schema(example = "de")]
+    language: Option<String>,
+    /// Slug of the list to join, the default list when omitted
+    #[schema(example = "weekly")]
+    list: Option<String>,
+    /// Custom attributes, checked against the publication's definitions
+    #[serde(default)]
+    #[schema(value_type = Object, example = json!({"country": "DE", "seats": 12}))]
+    attributes: Attributes,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema, Debug)]
+pub struct ConfirmationRequest {
+    /// Token from the confirmation email
+    subscription_token: String,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct SubscriptionResponse {
+    #[schema(example = "pending_confirmation")]
+    status: &'static str,
+}
+
+impl SubscriptionResponse {
+    pub(crate) fn new(status: &'static str) -> Self {
+        Self { status }
+    }
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ErrorResponse {
+    error: String,
+}
+
+impl ErrorResponse {
+    pub(crate) fn new(error: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+        }
+    }
+}
+
+/// The versioned JSON API for machine clients, mounted under `/api/v1` and
+/// authenticated with API keys. The HTML form endpoints at the root stay as
+/// they are for embedded forms.
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.app_data(json_config())
+        .service(subscribe)
+        .service(confirm)
+        .service(list_subscribers);
+}
+
+/// Reports malformed JSON bodies in the same shape as every other API error.
+pub(crate) fn json_config() -> JsonConfig {
+    JsonConfig::default().error_handler(|e, _| {
+        let response = HttpResponse::BadRequest().json(ErrorResponse::new(e.to_string()));
+        InternalError::from_response(e, response).into()
+    })
+}
+
+/// `Deprecation` (RFC 9745) and `Sunset` (RFC 8594) headers for the dates that
+/// are configured, none otherwise.
+pub fn deprecation_headers(settings: &ApiSettings) -> DefaultHeaders {
+    let mut headers = DefaultHeaders::new();
+    if let Some(deprecated_at) = settings.v1_deprecated_at {
+        headers = headers.add(("Deprecation", format!("@{}", deprecated_at.timestamp())));
+    }
+    if let Some(sunset) = settings.v1_sunset {
+        headers = headers.add((
+            "Sunset",
+            sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+        ));
+    }
+
+    headers
+}
+
+#[utoipa::path(
+    tag = "api/v1",
+    path = "/api/v1/subscriptions",
+    request_body = SubscriptionRequest,
+    params(("Accept-Language" = Option<String>, Header, description = "Used to pick the language of the confirmation email")),
+    responses(
+        (status = 201, description = "Subscriber saved and confirmation email sent", body = SubscriptionResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 409, description = "The email is already on the list", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key lacks the subscribe scope", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+        (status = 500, description = "Saving the subscriber or sending the email failed", body = ErrorResponse)
+    ),
+    security(("api_key" = []))
+)]
+#[post("/subscriptions")]
+#[tracing::instrument(
+    name = "Adding a new subscriber through the API", skip(client, body, connection, request, translations, metrics),
+    fields(
+        tenant = %client.publication.id,
+        subscriber_name = %body.name,
+        subscriber_email = %body.email
+    )
+)]
+pub async fn subscribe(
+    client: ApiClient,
+    body: web::Json<SubscriptionRequest>,
+    connection: web::Data<PgPool>,
+    translations: web::Data<Translations>,
+    metrics: web::Data<Metrics>,
+    request: HttpRequest,
+) -> HttpResponse {
+    if let Some(response) = client.missing_scope(ApiKeyScope::Subscribe) {
+        return response;
+    }
+    let accept_language = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|h| h.to_str().ok());
+    let locale = translations.negotiate(body.language.as_deref(), accept_language);
+    let body = body.into_inner();
+    let subscriber = match SubscriberName::parse(body.name)
+        .and_then(|name| Email::parse(body.email).map(|email| (name, email)))
+    {
+        Ok((name, email)) => Subscriber {
+            name,
+            email,
+            locale,
+            attributes: body.attributes,
+        },
+        Err(e) => return HttpResponse::BadRequest().json(ErrorResponse::new(e)),
+    };
+
+    match create_subscription(
+        &connection,
+        &client.publication,
+        &translations,
+        &metrics,
+        subscriber,
+        body.list.as_deref().unwrap_or(DEFAULT_LIST),
+    )
+    .await
+    {
+        Ok(()) => HttpResponse::Created().json(SubscriptionResponse {
+            status: "pending_confirmation",
+        }),
+        Err(e @ SubscriberError::DuplicateEmail) => {
+            HttpResponse::Conflict().json(ErrorResponse::new(e.to_string()))
+        }
+        Err(e @ (SubscriberError::UnknownList | SubscriberError::InvalidAttributes(_))) => {
+            HttpResponse::BadRequest().json(ErrorResponse::new(e.to_string()))
+        }
+        Err(e @ (SubscriberError::DatabaseFailure | SubscriberError::EmailFailure)) => {
+            HttpResponse::InternalServerError().json(ErrorResponse::new(e.to_string()))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "api/v1",
+    path = "/api/v1/subscriptions/confirm",
+    request_body = ConfirmationRequest,
+    responses(
+        (status = 200, description = "Subscription confirmed, or it already was", body = SubscriptionResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Unknown token, or a missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key lacks the subscribe scope", body = ErrorResponse),
+        (status = 410, description = "The token has expired", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
+        (status = 500, description = "Confirming the subscriber failed", body = ErrorResponse)
+    ),
+    security(("api_key" = []))
+)]
+#[post("/subscriptions/confirm")]
+#[tracing::instrument(
+    name = "Confirm a pending subscriber through the API",
+    skip(client, connection, metrics, settings),
+    fields(tenant = %client.publication.id)
+)]
+pub async fn confirm(
+    client: ApiClient,
+    body: web::Json<ConfirmationRequest>,
+    connection: web::Data<PgPool>,
+    metrics: web::Data<Metrics>,
+    settings: web::Data<ConfirmationSettings>,
+) -> HttpResponse {
+    if let Some(response) = client.missing_scope(ApiKeyScope::Subscribe) {
+        return response;
+    }
+    match confirm_subscription(
+        &connection,
+        &metrics,
+        settings.token_ttl(),
+        &client.publication.id,
+        body.into_inner().subscription_token,
+    )
+    .await
+    {
+        Ok(ConfirmationOutcome::Confirmed(_) | ConfirmationOutcome::AlreadyConfirmed(_)) => {
+            HttpResponse::Ok().json(SubscriptionResponse {
+                status: "confirmed",
+            })
+        }
+        Ok(ConfirmationOutcome::Expired(_)) => {
+            HttpResponse::Gone().json(ErrorResponse::new("The token has expired"))
+        }
+        Ok(ConfirmationOutcome::InvalidToken) => {
+            HttpResponse::Unauthorized().json(ErrorResponse::new("Unknown token"))
+        }
+        Err(_) => HttpResponse::InternalServerError()
+            .json(ErrorResponse::new("Failed to confirm the subscriber")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::ApiSettings,
+        routes::api_v1::{configure, deprecation_headers},
+    };
+    use actix_web::{dev::ServiceResponse, test as actix_test, web, App};
+    use chrono::{TimeZone, Utc};
+
+    async fn post_subscription(settings: &ApiSettings) -> ServiceResponse {
+        let app = actix_test::init_service(
+            App::new().service(
+                web::scope("/api/v1")
+                    .wrap(deprecation_headers(settings))
+                    .configure(configure),
+            ),
+        )
+        .await;
+        actix_test::call_service(
+            &app,
+            actix_test::TestRequest::post()
+                .uri("/api/v1/subscriptions")
+                .to_request(),
+        )
+        .await
+    }
+
+    #[actix_web::test]
+    async fn deprecation_headers_are_only_sent_once_configured() {
+        let response = post_subscription(&ApiSettings::default()).await;
+        assert!(response.headers().get("Deprecation").is_none());
+        assert!(response.headers().get("Sunset").is_none());
+
+        let response = post_subscription(&ApiSettings {
+            v1_deprecated_at: Some(Utc.with_ymd_and_hms(2027, 1, 1, 0, 0, 0).unwrap()),
+            v1_sunset: Some(Utc.with_ymd_and_hms(2027, 7, 1, 0, 0, 0).unwrap()),
+        })
+        .await;
+        let headers = response.headers();
+        assert_eq!(headers.get("Deprecation").unwrap(), "@1798761600");
+        assert_eq!(
+            headers.get("Sunset").unwrap(),
+            "Thu, 01 Jul 2027 00:00:00 GMT"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use super::{auth::ApiClient, ErrorResponse};
+use crate::api_keys::ApiKeyScope;
+use crate::telemetry::describe_db_error;
+use actix_web::{get, web, HttpResponse};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub(crate) const DEFAULT_PAGE_SIZE: i64 = 100;
+pub(crate) const MAX_PAGE_SIZE: i64 = 1000;
+
+#[derive(serde::Deserialize, utoipa::IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct SubscriberQuery {
+    /// `confirmed` or `pending_confirmation`
+    status: Option<String>,
+    /// Defaults to 100, at most 1000
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct SubscriberResponse {
+    id: Uuid,
+    email: String,
+    name: String,
+    locale: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    tag = "api/v1",
+    path = "/api/v1/subscribers",
+    params(SubscriberQuery),
+    responses(
+        (status = 200, description = "Subscribers, oldest first", body = [SubscriberResponse]),
+        (status = 400, description = "Invalid paging", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key lacks the read scope", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded", body = ErrorResponse)
+    ),
+    security(("api_key" = []))
+)]
+#[get("/subscribers")]
+#[tracing::instrument(
+    name = "Listing subscribers through the API",
+    skip(client, connection),
+    fields(tenant = %client.publication.id)
+)]
+pub async fn list_subscribers(
+    client: ApiClient,
+    query: web::Query<SubscriberQuery>,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    if let Some(response) = client.missing_scope(ApiKeyScope::Read) {
+        return response;
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) || offset < 0 {
+        return HttpResponse::BadRequest().json(ErrorResponse::new(format!(
+            "limit must be between 1 and {} and offset must not be negative",
+            MAX_PAGE_SIZE
+        )));
+    }
+
+    let result = sqlx::query_as!(
+        SubscriberResponse,
+        r#"SELECT id, email, name, locale, status, subscribed_at FROM subscriptions
+        WHERE publication_id = $4 AND ($1::TEXT IS NULL OR status = $1)
+        ORDER BY subscribed_at, id
+        LIMIT $2 OFFSET $3"#,
+        query.status,
+        limit,
+        offset,
+        client.publication.id
+    )
+    .fetch_all(&**connection)
+    .await;
+
+    match result {
+        Ok(subscribers) => HttpResponse::Ok().json(subscribers),
+        Err(e) => {
+            tracing::error!(error = %describe_db_error(&e), "Failed to list subscribers");
+            HttpResponse::InternalServerError()
+                .json(ErrorResponse::new("Failed to list subscribers"))
+        }
+    }
+}
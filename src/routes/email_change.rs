@@ -0,0 +1,276 @@
+use super::pages::{document, escape, page};
+use super::preferences::{link_failure_page, load_from_link, LinkFailure};
+use crate::{
+    config::PreferenceSettings,
+    domain::{Email, Locale},
+    email_client::EmailClient,
+    i18n::Translations,
+    preferences::{self, EmailChangeOutcome},
+    routes::Tenant,
+};
+use actix_web::{get, http::StatusCode, post, web, HttpRequest, HttpResponse};
+use fluent_bundle::FluentArgs;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize, utoipa::ToSchema, Debug)]
+pub struct EmailChangeRequest {
+    /// Token from the preference link
+    token: String,
+    new_email: String,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams, utoipa::ToSchema, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct EmailChangeParameters {
+    /// Token from the verification email sent to the new address
+    token: String,
+}
+
+#[utoipa::path(
+    tag = "preferences",
+    path = "/preferences/email",
+    request_body(content = EmailChangeRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "A verification link was sent to the new address", content_type = "text/html"),
+        (status = 400, description = "Invalid or unchanged address", content_type = "text/html"),
+        (status = 401, description = "Invalid link", content_type = "text/html"),
+        (status = 409, description = "Another subscription uses the address", content_type = "text/html"),
+        (status = 410, description = "The link has expired", content_type = "text/html"),
+        (status = 500, description = "Sending the verification email failed")
+    )
+)]
+#[post("/preferences/email")]
+#[tracing::instrument(name = "Requesting an email change", skip_all)]
+pub async fn request_email_change(
+    form: web::Form<EmailChangeRequest>,
+    tenant: Tenant,
+    connection: web::Data<PgPool>,
+    translations: web::Data<Translations>,
+    settings: web::Data<PreferenceSettings>,
+    request: HttpRequest,
+) -> HttpResponse {
+    let form = form.into_inner();
+    let preferences = match load_from_link(&connection, &settings, &tenant, &form.token).await {
+        Ok(preferences) if preferences.status == "unsubscribed" => {
+            return link_failure_page(LinkFailure::Invalid, &translations, &request)
+        }
+        Ok(preferences) => preferences,
+        Err(failure) => return link_failure_page(failure, &translations, &request),
+    };
+    let locale = preferences.locale;
+    let new_email = match Email::parse(form.new_email) {
+        Ok(email) if email.as_ref() != preferences.email => email,
+        _ => {
+            return page(
+                StatusCode::BAD_REQUEST,
+                None,
+                &translations,
+                &locale,
+                "email-change-invalid",
+            )
+        }
+    };
+
+    let token = match preferences::request_email_change(
+        &connection,
+        &tenant.id,
+        preferences.subscriber_id,
+        &new_email,
+    )
+    .await
+    {
+        Ok(Some(token)) => token,
+        Ok(None) => {
+            return page(
+                StatusCode::CONFLICT,
+                None,
+                &translations,
+                &locale,
+                "email-change-taken",
+            )
+        }
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+    let mut args = FluentArgs::new();
+    args.set(
+        "link",
+        format!(
+            "{}/preferences/email/confirm?token={}",
+            tenant.base_url, token
+        ),
+    );
+    let subject = translations.format(&locale, "email-change-verify-subject", None);
+    let body = translations.format(&locale, "email-change-verify-body", Some(&args));
+    if let Err(e) = tenant
+        .email_client
+        .send_email(new_email, &subject, &body)
+        .await
+    {
+        tracing::error!(error = %e.without_url(), "Failed to send email");
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    page(
+        StatusCode::OK,
+        None,
+        &translations,
+        &locale,
+        "email-change-sent",
+    )
+}
+
+/// Like the subscription confirmation, following the link only asks to
+/// confirm, so mail scanners can't change anyone's address.
+#[utoipa::path(
+    tag = "preferences",
+    params(EmailChangeParameters),
+    responses(
+        (status = 200, description = "A form to confirm the new address", content_type = "text/html"),
+        (status = 401, description = "Unknown or used token", content_type = "text/html"),
+        (status = 409, description = "Another subscription uses the address", content_type = "text/html"),
+        (status = 410, description = "The token has expired", content_type = "text/html"),
+        (status = 500, description = "Looking up the token failed")
+    )
+)]
+#[get("/preferences/email/confirm")]
+#[tracing::instrument(name = "Show the email change confirmation page", skip_all)]
+pub async fn email_change_page(
+    parameters: web::Query<EmailChangeParameters>,
+    tenant: Tenant,
+    connection: web::Data<PgPool>,
+    translations: web::Data<Translations>,
+    settings: web::Data<PreferenceSettings>,
+    request: HttpRequest,
+) -> HttpResponse {
+    let token = parameters.into_inner().token;
+    match preferences::check_email_change(&connection, &tenant.id, &token, settings.link_ttl())
+        .await
+    {
+        Ok(EmailChangeOutcome::Pending(locale)) => {
+            email_change_form(&translations, &locale, &token)
+        }
+        Ok(outcome) => outcome_page(outcome, &translations, &request),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+#[utoipa::path(
+    tag = "preferences",
+    path = "/preferences/email/confirm",
+    request_body(content = EmailChangeParameters, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "The subscription moved to the new address", content_type = "text/html"),
+        (status = 401, description = "Unknown or used token", content_type = "text/html"),
+        (status = 409, description = "Another subscription uses the address", content_type = "text/html"),
+        (status = 410, description = "The token has expired", content_type = "text/html"),
+        (status = 500, description = "Changing the address failed")
+    )
+)]
+#[post("/preferences/email/confirm")]
+#[tracing::instrument(name = "Confirming an email change", skip_all)]
+pub async fn confirm_email_change(
+    form: web::Form<EmailChangeParameters>,
+    tenant: Tenant,
+    connection: web::Data<PgPool>,
+    translations: web::Data<Translations>,
+    settings: web::Data<PreferenceSettings>,
+    request: HttpRequest,
+) -> HttpResponse {
+    let outcome = match preferences::apply_email_change(
+        &connection,
+        &tenant.id,
+        &form.token,
+        settings.link_ttl(),
+    )
+    .await
+    {
+        Ok(outcome) => outcome,
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    if let EmailChangeOutcome::Changed {
+        old_email,
+        new_email,
+        locale,
+    } = &outcome
+    {
+        // The change is done, a failed notice must not undo or hide it.
+        notify_old_address(
+            &tenant.email_client,
+            &translations,
+            locale,
+            old_email,
+            new_email,
+        )
+        .await;
+    }
+
+    outcome_page(outcome, &translations, &request)
+}
+
+async fn notify_old_address(
+    email_client: &EmailClient,
+    translations: &Translations,
+    locale: &Locale,
+    old_email: &str,
+    new_email: &str,
+) {
+    let Ok(old_email) = Email::parse(old_email.to_owned()) else {
+        tracing::warn!("Not notifying an invalid previous address");
+        return;
+    };
+    let mut args = FluentArgs::new();
+    args.set("email", new_email.to_owned());
+    let subject = translations.format(locale, "email-change-notice-subject", None);
+    let body = translations.format(locale, "email-change-notice-body", Some(&args));
+    if let Err(e) = email_client.send_email(old_email, &subject, &body).await {
+        tracing::error!(
+            error = %e.without_url(),
+            "Failed to notify the previous address"
+        );
+    }
+}
+
+fn outcome_page(
+    outcome: EmailChangeOutcome,
+    translations: &Translations,
+    request: &HttpRequest,
+) -> HttpResponse {
+    match outcome {
+        EmailChangeOutcome::Pending(locale) | EmailChangeOutcome::Changed { locale, .. } => page(
+            StatusCode::OK,
+            None,
+            translations,
+            &locale,
+            "email-change-confirmed",
+        ),
+        EmailChangeOutcome::Taken(locale) => page(
+            StatusCode::CONFLICT,
+            None,
+            translations,
+            &locale,
+            "email-change-taken",
+        ),
+        EmailChangeOutcome::Expired => {
+            link_failure_page(LinkFailure::Expired, translations, request)
+        }
+        EmailChangeOutcome::Invalid => {
+            link_failure_page(LinkFailure::Invalid, translations, request)
+        }
+    }
+}
+
+fn email_change_form(translations: &Translations, locale: &Locale, token: &str) -> HttpResponse {
+    let content = format!(
+        r#"<p>{prompt}</p>
+    <form method="post" action="/preferences/email/confirm">
+      <input type="hidden" name="token" value="{token}">
+      <button type="submit">{button}</button>
+    </form>"#,
+        prompt = translations.format(locale, "email-change-prompt", None),
+        token = escape(token),
+        button = translations.format(locale, "email-change-confirm-button", None),
+    );
+
+    document(StatusCode::OK, translations, locale, &content)
+}
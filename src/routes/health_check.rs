@@ -1,6 +1,197 @@
-use actix_web::{get, HttpResponse, Responder};
+use crate::{
+    config::HealthSettings,
+    publications::{Publications, DEFAULT_PUBLICATION},
+    startup::{check_known_migrations, MIGRATOR},
+    workers::Heartbeats,
+};
+use actix_web::{get, web, HttpResponse, Responder};
+use sqlx::PgPool;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
 
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(serde::Serialize, utoipa::ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Up,
+    Degraded,
+    Down,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema, Debug)]
+struct ComponentHealth {
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip)]
+    critical: bool,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema, Debug)]
+struct HealthReport {
+    status: Status,
+    version: &'static str,
+    components: BTreeMap<String, ComponentHealth>,
+}
+
+impl ComponentHealth {
+    fn from_result<E: std::fmt::Display>(
+        result: Result<(), E>,
+        started: Instant,
+        critical: bool,
+    ) -> Self {
+        Self {
+            status: if result.is_ok() {
+                Status::Up
+            } else {
+                Status::Down
+            },
+            latency_ms: Some(started.elapsed().as_secs_f64() * 1000.0),
+            error: result.err().map(|e| e.to_string()),
+            critical,
+        }
+    }
+}
+
+impl HealthReport {
+    fn new(components: BTreeMap<String, ComponentHealth>) -> Self {
+        let down = |critical: bool| {
+            components
+                .values()
+                .any(|c| c.critical == critical && c.status == Status::Down)
+        };
+        let status = if down(true) {
+            Status::Down
+        } else if down(false) {
+            Status::Degraded
+        } else {
+            Status::Up
+        };
+
+        Self {
+            status,
+            version: VERSION,
+            components,
+        }
+    }
+
+    fn respond(&self) -> HttpResponse {
+        match self.status {
+            Status::Down => HttpResponse::ServiceUnavailable().json(self),
+            _ => HttpResponse::Ok().json(self),
+        }
+    }
+}
+
+#[utoipa::path(tag = "health", responses((status = 200, description = "The service is running")))]
 #[get("/health-check")]
-async fn health_check() -> impl Responder {
+pub async fn health_check() -> impl Responder {
     HttpResponse::Ok().finish()
 }
+
+#[utoipa::path(
+    tag = "health",
+    responses((status = 200, description = "The process is alive", body = HealthReport))
+)]
+#[get("/health/live")]
+pub async fn health_live() -> impl Responder {
+    HealthReport::new(BTreeMap::new()).respond()
+}
+
+#[utoipa::path(
+    tag = "health",
+    responses(
+        (status = 200, description = "All critical dependencies are up", body = HealthReport),
+        (status = 503, description = "A critical dependency is down", body = HealthReport)
+    )
+)]
+#[get("/health/ready")]
+#[tracing::instrument(name = "Checking readiness", skip_all)]
+pub async fn health_ready(
+    connection: web::Data<PgPool>,
+    publications: web::Data<Publications>,
+    heartbeats: web::Data<Heartbeats>,
+    settings: web::Data<HealthSettings>,
+) -> impl Responder {
+    let timeout = Duration::from_millis(settings.probe_timeout_ms);
+    let mut components = BTreeMap::new();
+
+    let started = Instant::now();
+    let result = tokio::time::timeout(timeout, sqlx::query("SELECT 1").execute(&**connection))
+        .await
+        .map_err(|_| String::from("timed out"))
+        .and_then(|r| r.map(|_| ()).map_err(|e| e.to_string()));
+    components.insert(
+        String::from("database"),
+        ComponentHealth::from_result(result, started, true),
+    );
+
+    let started = Instant::now();
+    let result = tokio::time::timeout(timeout, check_migrations(&connection))
+        .await
+        .map_err(|_| String::from("timed out"))
+        .and_then(|r| r);
+    components.insert(
+        String::from("migrations"),
+        ComponentHealth::from_result(result, started, true),
+    );
+
+    if settings.check_email_provider {
+        for publication in publications.iter() {
+            let started = Instant::now();
+            let result = publication.email_client.probe(timeout).await;
+            let component = if publication.id == DEFAULT_PUBLICATION {
+                String::from("email_provider")
+            } else {
+                format!("email_provider:{}", publication.id)
+            };
+            components.insert(
+                component,
+                ComponentHealth::from_result(result, started, false),
+            );
+        }
+    }
+
+    let heartbeat_timeout = Duration::from_secs(settings.worker_heartbeat_timeout_secs);
+    for (worker, since) in heartbeats.since_last_beat() {
+        let result = if since <= heartbeat_timeout {
+            Ok(())
+        } else {
+            Err(format!("last heartbeat {}s ago", since.as_secs()))
+        };
+        components.insert(
+            format!("worker:{}", worker),
+            ComponentHealth {
+                latency_ms: None,
+                ..ComponentHealth::from_result(result, Instant::now(), true)
+            },
+        );
+    }
+
+    HealthReport::new(components).respond()
+}
+
+async fn check_migrations(connection: &PgPool) -> Result<(), String> {
+    check_known_migrations(connection).await?;
+    let expected = MIGRATOR.iter().map(|m| m.version).max().unwrap_or_default();
+    let applied: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+            .fetch_one(connection)
+            .await
+            .map_err(|e| e.to_string())?;
+    let applied = applied.unwrap_or_default();
+
+    if applied < expected {
+        return Err(format!(
+            "database is at migration {}, expected {}",
+            applied, expected
+        ));
+    }
+
+    Ok(())
+}
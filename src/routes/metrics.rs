@@ -0,0 +1,21 @@
+use crate::metrics::Metrics;
+use actix_web::{get, web, HttpResponse, Responder};
+use sqlx::PgPool;
+
+/// Served on the metrics listener (`metrics.host` and `metrics.port`), not
+/// the application port.
+#[utoipa::path(
+    tag = "operations",
+    responses((status = 200, description = "Prometheus metrics", content_type = "text/plain"))
+)]
+#[get("/metrics")]
+pub async fn export_metrics(
+    metrics: web::Data<Metrics>,
+    connection: web::Data<PgPool>,
+) -> impl Responder {
+    metrics.observe_pool(&connection);
+
+    HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(metrics.render())
+}
@@ -1,7 +1,20 @@
+pub mod admin;
+mod api_docs;
+pub mod api_v1;
+mod email_change;
 pub mod health_check;
+mod metrics;
+mod pages;
+mod preferences;
 mod subscriptions;
 mod subscriptions_confirm;
+mod tenant;
 
+pub use api_docs::*;
+pub use email_change::*;
 pub use health_check::*;
+pub use metrics::*;
+pub use preferences::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use tenant::Tenant;
@@ -0,0 +1,161 @@
+use crate::{domain::Locale, i18n::Translations};
+use actix_web::{
+    http::{
+        header::{self, ContentType},
+        StatusCode,
+    },
+    HttpRequest, HttpResponse,
+};
+
+/// Browsers ask for HTML, API clients and scripts usually don't.
+pub(crate) fn wants_html(request: &HttpRequest) -> bool {
+    request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
+/// Sends the browser to the configured redirect, or renders a page with the
+/// translated message when there is none.
+pub(crate) fn page(
+    status: StatusCode,
+    redirect: Option<&str>,
+    translations: &Translations,
+    locale: &Locale,
+    message_id: &str,
+) -> HttpResponse {
+    if let Some(location) = redirect {
+        return HttpResponse::SeeOther()
+            .insert_header((header::LOCATION, location))
+            .finish();
+    }
+
+    let message = translations.format(locale, message_id, None);
+    document(status, translations, locale, &format!("<p>{}</p>", message))
+}
+
+/// Asks the subscriber to confirm with a button, which posts the token back.
+/// With `auto_submit` a script presses it for browsers running JavaScript.
+pub(crate) fn confirmation_form(
+    translations: &Translations,
+    locale: &Locale,
+    subscription_token: &str,
+    auto_submit: bool,
+) -> HttpResponse {
+    let script = if auto_submit {
+        "\n    <script>document.getElementById(\"confirm\").submit();</script>"
+    } else {
+        ""
+    };
+    let content = format!(
+        r#"<p>{prompt}</p>
+    <form id="confirm" method="post" action="/subscriptions/confirm">
+      <input type="hidden" name="subscription_token" value="{token}">
+      <button type="submit">{button}</button>
+    </form>{script}"#,
+        prompt = translations.format(locale, "confirm-prompt", None),
+        token = escape(subscription_token),
+        button = translations.format(locale, "confirm-button", None),
+        script = script
+    );
+
+    document(StatusCode::OK, translations, locale, &content)
+}
+
+pub(crate) fn document(
+    status: StatusCode,
+    translations: &Translations,
+    locale: &Locale,
+    content: &str,
+) -> HttpResponse {
+    let title = translations.format(locale, "page-title", None);
+    HttpResponse::build(status)
+        .content_type(ContentType::html())
+        // Nobody should be able to press the confirm button from inside their page.
+        .insert_header(("X-Frame-Options", "DENY"))
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="{lang}">
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <title>{title}</title>
+</head>
+<body>
+  <main>
+    <h1>{title}</h1>
+    {content}
+  </main>
+</body>
+</html>"#,
+            lang = locale.as_ref(),
+            title = title,
+            content = content
+        ))
+}
+
+pub(crate) fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        domain::Locale,
+        i18n::Translations,
+        routes::pages::{confirmation_form, page},
+    };
+    use actix_web::{body::to_bytes, http::StatusCode};
+
+    #[actix_web::test]
+    async fn a_configured_redirect_replaces_the_page() {
+        let translations = Translations::load();
+        let locale = Locale::default();
+
+        let redirected = page(
+            StatusCode::GONE,
+            Some("https://www.example.com/expired"),
+            &translations,
+            &locale,
+            "subscription-token-expired",
+        );
+        let rendered = page(
+            StatusCode::GONE,
+            None,
+            &translations,
+            &locale,
+            "subscription-token-expired",
+        );
+
+        assert_eq!(redirected.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            redirected.headers().get("Location").unwrap(),
+            "https://www.example.com/expired"
+        );
+        assert_eq!(rendered.status(), StatusCode::GONE);
+        let body = to_bytes(rendered.into_body()).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("<!DOCTYPE html>"));
+    }
+
+    #[actix_web::test]
+    async fn the_token_is_escaped_in_the_confirmation_form() {
+        let translations = Translations::load();
+
+        let response = confirmation_form(
+            &translations,
+            &Locale::default(),
+            r#""><script>alert(1)</script>"#,
+            false,
+        );
+
+        let body = to_bytes(response.into_body()).await.unwrap();
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains(r#"value="&quot;&gt;&lt;script&gt;alert(1)&lt;/script&gt;""#));
+        assert!(!body.contains("<script>"));
+    }
+}
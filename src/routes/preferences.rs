@@ -0,0 +1,492 @@
+use super::pages::{document, escape, page, wants_html};
+use crate::{
+    config::PreferenceSettings,
+    domain::{Email, Locale, SubscriberName},
+    i18n::Translations,
+    metrics::{Metrics, SubscriptionEvent},
+    preferences::{
+        self, verify_link_token, Frequency, LinkError, PreferenceUpdate, Preferences, Topic,
+    },
+    routes::Tenant,
+};
+use actix_web::{
+    get,
+    http::{header, StatusCode},
+    post, web, HttpRequest, HttpResponse,
+};
+use chrono::Utc;
+use fluent_bundle::FluentArgs;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize, utoipa::IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct PreferenceLinkParameters {
+    /// Signed token from the preference email, omitted to ask for a link
+    token: Option<String>,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema, Debug)]
+pub struct PreferenceLinkRequest {
+    email: String,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema, Debug)]
+pub struct UnsubscribeRequest {
+    token: String,
+    /// The list to leave, omitted to unsubscribe from all of them
+    #[schema(example = "newsletter")]
+    list: Option<String>,
+}
+
+/// The form posted from the preference page, for the OpenAPI document only.
+/// Topics are repeated checkboxes, which is why it isn't deserialized
+/// directly.
+#[derive(utoipa::ToSchema)]
+#[allow(dead_code)]
+pub struct PreferenceForm {
+    token: String,
+    name: String,
+    #[schema(example = "weekly")]
+    frequency: String,
+    /// Present when checked
+    paused: Option<String>,
+    topic: Vec<String>,
+}
+
+pub(super) enum LinkFailure {
+    Invalid,
+    Expired,
+    Unexpected,
+}
+
+#[utoipa::path(
+    tag = "preferences",
+    params(PreferenceLinkParameters),
+    responses(
+        (status = 200, description = "The preference page, or a form to request a link", content_type = "text/html"),
+        (status = 401, description = "Invalid link", content_type = "text/html"),
+        (status = 410, description = "The link has expired", content_type = "text/html"),
+        (status = 500, description = "Loading the preferences failed")
+    )
+)]
+#[get("/preferences")]
+#[tracing::instrument(name = "Show the preference page", skip_all)]
+pub async fn preferences_page(
+    parameters: web::Query<PreferenceLinkParameters>,
+    tenant: Tenant,
+    connection: web::Data<PgPool>,
+    translations: web::Data<Translations>,
+    settings: web::Data<PreferenceSettings>,
+    request: HttpRequest,
+) -> HttpResponse {
+    let Some(token) = parameters.0.token else {
+        let locale = request_locale(&translations, &request);
+        return link_request_form(&translations, &locale);
+    };
+    match load_from_link(&connection, &settings, &tenant, &token).await {
+        Ok(preferences) if preferences.status == "unsubscribed" => page(
+            StatusCode::OK,
+            None,
+            &translations,
+            &preferences.locale,
+            "unsubscribed",
+        ),
+        Ok(preferences) => match preferences::list_topics(&connection, &tenant.id).await {
+            Ok(topics) => preferences_form(
+                StatusCode::OK,
+                &translations,
+                &preferences,
+                &topics,
+                &token,
+                None,
+            ),
+            Err(_) => HttpResponse::InternalServerError().finish(),
+        },
+        Err(failure) => link_failure_page(failure, &translations, &request),
+    }
+}
+
+#[utoipa::path(
+    tag = "preferences",
+    path = "/preferences/link",
+    request_body(content = PreferenceLinkRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "A link was sent if the address belongs to a confirmed subscriber"),
+        (status = 500, description = "Sending the email failed")
+    )
+)]
+#[post("/preferences/link")]
+#[tracing::instrument(name = "Sending a preference link", skip_all)]
+pub async fn send_preferences_link(
+    form: web::Form<PreferenceLinkRequest>,
+    tenant: Tenant,
+    connection: web::Data<PgPool>,
+    translations: web::Data<Translations>,
+    settings: web::Data<PreferenceSettings>,
+    request: HttpRequest,
+) -> HttpResponse {
+    // The response never tells whether the address is subscribed.
+    let subscriber = match Email::parse(form.0.email) {
+        Ok(email) => {
+            match preferences::find_confirmed(&connection, &tenant.id, email.as_ref()).await {
+                Ok(subscriber) => subscriber.map(|(id, locale)| (id, locale, email)),
+                Err(_) => return HttpResponse::InternalServerError().finish(),
+            }
+        }
+        Err(_) => None,
+    };
+
+    if let Some((subscriber_id, locale, email)) = subscriber {
+        let token = preferences::link_token(
+            &settings.signing_key,
+            subscriber_id,
+            Utc::now() + settings.link_ttl(),
+        );
+        let mut args = FluentArgs::new();
+        args.set(
+            "link",
+            format!("{}/preferences?token={}", tenant.base_url, token),
+        );
+        let subject = translations.format(&locale, "preferences-email-subject", None);
+        let body = translations.format(&locale, "preferences-email-body", Some(&args));
+        if let Err(e) = tenant.email_client.send_email(email, &subject, &body).await {
+            tracing::error!(error = %e.without_url(), "Failed to send email");
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    if !wants_html(&request) {
+        return HttpResponse::Ok().finish();
+    }
+    let locale = request_locale(&translations, &request);
+    page(
+        StatusCode::OK,
+        None,
+        &translations,
+        &locale,
+        "preferences-link-sent",
+    )
+}
+
+#[utoipa::path(
+    tag = "preferences",
+    path = "/preferences",
+    request_body(content = PreferenceForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Preferences saved", content_type = "text/html"),
+        (status = 400, description = "Invalid name or frequency", content_type = "text/html"),
+        (status = 401, description = "Invalid link", content_type = "text/html"),
+        (status = 410, description = "The link has expired", content_type = "text/html"),
+        (status = 500, description = "Saving the preferences failed")
+    )
+)]
+#[post("/preferences")]
+#[tracing::instrument(name = "Saving preferences", skip_all)]
+pub async fn update_preferences(
+    form: web::Form<Vec<(String, String)>>,
+    tenant: Tenant,
+    connection: web::Data<PgPool>,
+    translations: web::Data<Translations>,
+    settings: web::Data<PreferenceSettings>,
+    request: HttpRequest,
+) -> HttpResponse {
+    let field = |name: &str| {
+        form.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+    let token = field("token").unwrap_or_default();
+    let mut preferences = match load_from_link(&connection, &settings, &tenant, &token).await {
+        Ok(preferences) if preferences.status == "unsubscribed" => {
+            return link_failure_page(LinkFailure::Invalid, &translations, &request)
+        }
+        Ok(preferences) => preferences,
+        Err(failure) => return link_failure_page(failure, &translations, &request),
+    };
+    let topics = match preferences::list_topics(&connection, &tenant.id).await {
+        Ok(topics) => topics,
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    let name = field("name").unwrap_or_default();
+    let update = SubscriberName::parse(name.clone()).ok().and_then(|name| {
+        Some(PreferenceUpdate {
+            name,
+            frequency: Frequency::parse(&field("frequency")?)?,
+            paused: field("paused").is_some(),
+            topics: form
+                .iter()
+                .filter(|(key, _)| key == "topic")
+                .map(|(_, value)| value.clone())
+                .collect(),
+        })
+    });
+    let Some(update) = update else {
+        preferences.name = name;
+        return preferences_form(
+            StatusCode::BAD_REQUEST,
+            &translations,
+            &preferences,
+            &topics,
+            &token,
+            Some("preferences-invalid"),
+        );
+    };
+
+    if preferences::save(&connection, &tenant.id, preferences.subscriber_id, &update)
+        .await
+        .is_err()
+    {
+        return HttpResponse::InternalServerError().finish();
+    }
+    let preferences = Preferences {
+        name: update.name.as_ref().to_owned(),
+        frequency: update.frequency,
+        paused: update.paused,
+        topics: update.topics,
+        ..preferences
+    };
+
+    preferences_form(
+        StatusCode::OK,
+        &translations,
+        &preferences,
+        &topics,
+        &token,
+        Some("preferences-saved"),
+    )
+}
+
+#[utoipa::path(
+    tag = "preferences",
+    path = "/preferences/unsubscribe",
+    request_body(content = UnsubscribeRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Unsubscribed, or already was", content_type = "text/html"),
+        (status = 401, description = "Invalid link", content_type = "text/html"),
+        (status = 410, description = "The link has expired", content_type = "text/html"),
+        (status = 500, description = "Unsubscribing failed")
+    )
+)]
+#[post("/preferences/unsubscribe")]
+#[tracing::instrument(name = "Unsubscribing from the preference page", skip_all)]
+pub async fn unsubscribe(
+    form: web::Form<UnsubscribeRequest>,
+    tenant: Tenant,
+    connection: web::Data<PgPool>,
+    translations: web::Data<Translations>,
+    settings: web::Data<PreferenceSettings>,
+    metrics: web::Data<Metrics>,
+    request: HttpRequest,
+) -> HttpResponse {
+    let preferences = match load_from_link(&connection, &settings, &tenant, &form.token).await {
+        Ok(preferences) => preferences,
+        Err(failure) => return link_failure_page(failure, &translations, &request),
+    };
+    let list = form.list.as_deref();
+    match preferences::unsubscribe(&connection, &tenant.id, preferences.subscriber_id, list).await {
+        Ok(true) => metrics.subscription_event(SubscriptionEvent::Unsubscribed),
+        Ok(false) => {}
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    }
+
+    page(
+        StatusCode::OK,
+        None,
+        &translations,
+        &preferences.locale,
+        if list.is_some() {
+            "unsubscribed-list"
+        } else {
+            "unsubscribed"
+        },
+    )
+}
+
+pub(super) async fn load_from_link(
+    connection: &PgPool,
+    settings: &PreferenceSettings,
+    tenant: &Tenant,
+    token: &str,
+) -> Result<Preferences, LinkFailure> {
+    let subscriber_id = verify_link_token(&settings.signing_key, token).map_err(|e| match e {
+        LinkError::Invalid => LinkFailure::Invalid,
+        LinkError::Expired => LinkFailure::Expired,
+    })?;
+
+    match preferences::load(connection, &tenant.id, subscriber_id).await {
+        Ok(Some(preferences)) => Ok(preferences),
+        // Signed for a subscriber that has since been deleted, or one of
+        // another publication.
+        Ok(None) => Err(LinkFailure::Invalid),
+        Err(_) => Err(LinkFailure::Unexpected),
+    }
+}
+
+pub(super) fn link_failure_page(
+    failure: LinkFailure,
+    translations: &Translations,
+    request: &HttpRequest,
+) -> HttpResponse {
+    let locale = request_locale(translations, request);
+    match failure {
+        LinkFailure::Invalid => page(
+            StatusCode::UNAUTHORIZED,
+            None,
+            translations,
+            &locale,
+            "preferences-link-invalid",
+        ),
+        LinkFailure::Expired => page(
+            StatusCode::GONE,
+            None,
+            translations,
+            &locale,
+            "preferences-link-expired",
+        ),
+        LinkFailure::Unexpected => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+fn request_locale(translations: &Translations, request: &HttpRequest) -> Locale {
+    let accept_language = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|h| h.to_str().ok());
+    translations.negotiate(None, accept_language)
+}
+
+fn link_request_form(translations: &Translations, locale: &Locale) -> HttpResponse {
+    let t = |id: &str| translations.format(locale, id, None);
+    let content = format!(
+        r#"<p>{prompt}</p>
+    <form method="post" action="/preferences/link">
+      <label>{label} <input type="email" name="email" required></label>
+      <button type="submit">{button}</button>
+    </form>"#,
+        prompt = t("preferences-request-prompt"),
+        label = t("preferences-email-label"),
+        button = t("preferences-request-button"),
+    );
+
+    document(StatusCode::OK, translations, locale, &content)
+}
+
+fn preferences_form(
+    status: StatusCode,
+    translations: &Translations,
+    preferences: &Preferences,
+    topics: &[Topic],
+    token: &str,
+    notice: Option<&str>,
+) -> HttpResponse {
+    let t = |id: &str| translations.format(&preferences.locale, id, None);
+    let checked = |on: bool| if on { " checked" } else { "" };
+    let notice = notice
+        .map(|id| format!("<p>{}</p>\n    ", t(id)))
+        .unwrap_or_default();
+    let frequencies: String = Frequency::ALL
+        .iter()
+        .map(|f| {
+            let selected = if *f == preferences.frequency {
+                " selected"
+            } else {
+                ""
+            };
+            format!(
+                r#"<option value="{}"{}>{}</option>"#,
+                f.as_str(),
+                selected,
+                t(&format!("frequency-{}", f.as_str()))
+            )
+        })
+        .collect();
+    let topics: String = topics
+        .iter()
+        .map(|topic| {
+            format!(
+                r#"
+        <label><input type="checkbox" name="topic" value="{}"{}> {}</label>"#,
+                escape(&topic.slug),
+                checked(preferences.topics.contains(&topic.slug)),
+                escape(&topic.name)
+            )
+        })
+        .collect();
+    let memberships: String = preferences
+        .memberships
+        .iter()
+        .map(|membership| {
+            let leave = if membership.status == "unsubscribed" {
+                String::new()
+            } else {
+                format!(
+                    r#"
+        <form method="post" action="/preferences/unsubscribe">
+          <input type="hidden" name="token" value="{}">
+          <input type="hidden" name="list" value="{}">
+          <button type="submit">{}</button>
+        </form>"#,
+                    escape(token),
+                    escape(&membership.slug),
+                    t("unsubscribe-list-button")
+                )
+            };
+            format!(
+                r#"
+      <li>{} ({}){}
+      </li>"#,
+                escape(&membership.name),
+                t(match membership.status.as_str() {
+                    "confirmed" => "membership-confirmed",
+                    "unsubscribed" => "membership-unsubscribed",
+                    _ => "membership-pending",
+                }),
+                leave
+            )
+        })
+        .collect();
+    let content = format!(
+        r#"{notice}<form method="post" action="/preferences">
+      <input type="hidden" name="token" value="{token}">
+      <label>{name_label} <input type="text" name="name" value="{name}" required></label>
+      <fieldset>
+        <legend>{topics_label}</legend>{topics}
+      </fieldset>
+      <label>{frequency_label} <select name="frequency">{frequencies}</select></label>
+      <label><input type="checkbox" name="paused" value="on"{paused}> {pause_label}</label>
+      <button type="submit">{save}</button>
+    </form>
+    <form method="post" action="/preferences/email">
+      <input type="hidden" name="token" value="{token}">
+      <label>{email_label} <input type="email" name="new_email" value="{email}" required></label>
+      <button type="submit">{change_email}</button>
+    </form>
+    <h2>{lists_label}</h2>
+    <ul>{memberships}
+    </ul>
+    <form method="post" action="/preferences/unsubscribe">
+      <input type="hidden" name="token" value="{token}">
+      <button type="submit">{unsubscribe}</button>
+    </form>"#,
+        notice = notice,
+        token = escape(token),
+        name_label = t("preferences-name-label"),
+        name = escape(&preferences.name),
+        topics_label = t("preferences-topics-label"),
+        topics = topics,
+        frequency_label = t("preferences-frequency-label"),
+        frequencies = frequencies,
+        paused = checked(preferences.paused),
+        pause_label = t("preferences-pause-label"),
+        save = t("preferences-save-button"),
+        email_label = t("preferences-email-label"),
+        email = escape(&preferences.email),
+        change_email = t("email-change-button"),
+        lists_label = t("preferences-lists-label"),
+        memberships = memberships,
+        unsubscribe = t("unsubscribe-button"),
+    );
+
+    document(status, translations, &preferences.locale, &content)
+}
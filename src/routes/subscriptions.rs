@@ -1,25 +1,66 @@
+use super::{
+    api_v1::{ErrorResponse, SubscriptionResponse},
+    pages::{page, wants_html},
+    Tenant,
+};
 use crate::{
-    domain::{Email, Subscriber, SubscriberName},
+    attributes::{self, Attributes},
+    config::ConfirmationSettings,
+    domain::{Email, Locale, Subscriber, SubscriberName},
     email_client::EmailClient,
-    startup::ApplicationBaseUrl,
+    i18n::Translations,
+    lists::{self, DEFAULT_LIST},
+    metrics::{Metrics, SubscriptionEvent},
+    publications::Publication,
+    telemetry::describe_db_error,
+    webhooks::{self, WebhookEvent},
+};
+use actix_web::{
+    http::{header, StatusCode},
+    post, web, HttpRequest, HttpResponse, Responder,
 };
-use actix_web::{post, web, HttpResponse, Responder};
 use chrono::Utc;
+use fluent_bundle::FluentArgs;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use serde_json::Value;
 use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
 use uuid::Uuid;
 
-const UNIQUE_CONSTRAINT_VIOLATION_CODE: &str = "23505";
-
-enum SubscriberError {
+pub(crate) enum SubscriberError {
     DuplicateEmail,
+    UnknownList,
+    InvalidAttributes(String),
     DatabaseFailure,
+    EmailFailure,
+}
+
+impl std::fmt::Display for SubscriberError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateEmail => write!(f, "The email is already on the list"),
+            Self::UnknownList => write!(f, "Unknown list"),
+            Self::InvalidAttributes(e) => write!(f, "{}", e),
+            Self::DatabaseFailure => write!(f, "Failed to save the subscriber"),
+            Self::EmailFailure => write!(f, "Failed to send the confirmation email"),
+        }
+    }
 }
 
-#[derive(serde::Deserialize, Debug)]
+#[derive(serde::Deserialize, utoipa::ToSchema, Debug)]
 struct SubscribeFormData {
     name: String,
     email: String,
+    /// Preferred language, takes precedence over `Accept-Language`
+    #[schema(example = "de")]
+    language: Option<String>,
+    /// Slug of the list to join, the default list when omitted
+    #[schema(example = "weekly")]
+    list: Option<String>,
+    /// Custom attributes, sent as `attributes[name]` fields
+    #[serde(flatten)]
+    #[schema(ignore)]
+    fields: HashMap<String, String>,
 }
 
 impl TryFrom<SubscribeFormData> for Subscriber {
@@ -27,67 +68,205 @@ impl TryFrom<SubscribeFormData> for Subscriber {
     fn try_from(value: SubscribeFormData) -> Result<Self, Self::Error> {
         let name = SubscriberName::parse(value.name)?;
         let email = Email::parse(value.email)?;
+        let attributes = value
+            .fields
+            .into_iter()
+            .filter_map(|(field, value)| {
+                let name = field.strip_prefix("attributes[")?.strip_suffix(']')?;
+                Some((name.to_owned(), Value::String(value)))
+            })
+            .collect();
 
-        Ok(Self { name, email })
+        Ok(Self {
+            name,
+            email,
+            locale: Locale::default(),
+            attributes,
+        })
     }
 }
 
+#[utoipa::path(
+    tag = "subscriptions",
+    request_body(content = SubscribeFormData, content_type = "application/x-www-form-urlencoded"),
+    params(("Accept-Language" = Option<String>, Header, description = "Used to pick the language of the confirmation email")),
+    responses(
+        (status = 200, description = "Subscriber saved and confirmation email sent. Browsers asking for `text/html` get a page for every outcome", body = SubscriptionResponse),
+        (status = 303, description = "Redirect configured for the outcome, for browsers only"),
+        (status = 400, description = "Invalid name, email, list or attributes", body = ErrorResponse),
+        (status = 409, description = "The email is already on the list", body = ErrorResponse),
+        (status = 500, description = "Saving the subscriber or sending the email failed", body = ErrorResponse)
+    )
+)]
 #[post("/subscribe")]
 #[tracing::instrument(
-    name = "Adding a new subscriber", skip(form, connection),
+    name = "Adding a new subscriber", skip(form, tenant, connection, request, translations, metrics, settings),
     fields(
         subscriber_name = %form.name,
         subscriber_email = %form.email
     )
 )]
-async fn subscribe(
+pub async fn subscribe(
     form: web::Form<SubscribeFormData>,
+    tenant: Tenant,
     connection: web::Data<PgPool>,
-    email_client: web::Data<EmailClient>,
-    base_url: web::Data<ApplicationBaseUrl>,
+    translations: web::Data<Translations>,
+    metrics: web::Data<Metrics>,
+    settings: web::Data<ConfirmationSettings>,
+    request: HttpRequest,
 ) -> impl Responder {
-    let subscriber = match Subscriber::try_from(form.0) {
-        Ok(v) => v,
-        Err(_) => return HttpResponse::BadRequest().finish(),
+    let accept_language = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|h| h.to_str().ok());
+    let locale = translations.negotiate(form.language.as_deref(), accept_language);
+    let list = form.list.clone().unwrap_or_else(|| DEFAULT_LIST.to_owned());
+    let result = match Subscriber::try_from(form.0) {
+        Ok(v) => Ok(create_subscription(
+            &connection,
+            &tenant,
+            &translations,
+            &metrics,
+            Subscriber {
+                locale: locale.clone(),
+                ..v
+            },
+            &list,
+        )
+        .await),
+        Err(e) => Err(e),
     };
 
-    let mut transaction = match connection.begin().await {
-        Ok(transaction) => transaction,
-        Err(_) => return HttpResponse::InternalServerError().finish(),
+    let redirects = &settings.redirects;
+    let (status, redirect, message_id) = match result {
+        Ok(Ok(())) => (StatusCode::OK, &redirects.subscribed, "subscribe-success"),
+        Ok(Err(SubscriberError::DuplicateEmail)) => (
+            StatusCode::CONFLICT,
+            &redirects.already_subscribed,
+            "subscribe-duplicate",
+        ),
+        Ok(Err(SubscriberError::UnknownList | SubscriberError::InvalidAttributes(_))) | Err(_) => (
+            StatusCode::BAD_REQUEST,
+            &redirects.subscribe_invalid,
+            "subscribe-invalid",
+        ),
+        Ok(Err(_)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &redirects.subscribe_failed,
+            "subscribe-failed",
+        ),
     };
 
-    let subscriber_id = match insert_subscriber(&mut transaction, &subscriber).await {
-        Ok(subscriber_id) => subscriber_id,
-        Err(e) => {
-            if let SubscriberError::DuplicateEmail = e {
-                return HttpResponse::Conflict().finish();
-            }
-            return HttpResponse::InternalServerError().finish();
-        }
+    // Scripts posting the form get the same bodies as API clients.
+    if !wants_html(&request) {
+        let mut response = HttpResponse::build(status);
+        return match result {
+            Ok(Ok(())) => response.json(SubscriptionResponse::new("pending_confirmation")),
+            Ok(Err(e)) => response.json(ErrorResponse::new(e.to_string())),
+            Err(e) => response.json(ErrorResponse::new(e)),
+        };
+    }
+    page(
+        status,
+        redirect.as_deref(),
+        &translations,
+        &locale,
+        message_id,
+    )
+}
+
+/// Puts the subscriber on one of the publication's lists, pending
+/// confirmation, and sends the confirmation email. Addresses already known
+/// are added to further lists, the attributes they set only being saved once
+/// the new list is confirmed.
+pub(crate) async fn create_subscription(
+    connection: &PgPool,
+    publication: &Publication,
+    translations: &Translations,
+    metrics: &Metrics,
+    subscriber: Subscriber,
+    list: &str,
+) -> Result<(), SubscriberError> {
+    let mut transaction = connection
+        .begin()
+        .await
+        .map_err(|_| SubscriberError::DatabaseFailure)?;
+
+    if list == DEFAULT_LIST {
+        lists::ensure_default_list(&mut *transaction, &publication.id)
+            .await
+            .map_err(|_| SubscriberError::DatabaseFailure)?;
+    }
+    let list = lists::find_list(&mut *transaction, &publication.id, list)
+        .await
+        .map_err(|_| SubscriberError::DatabaseFailure)?
+        .ok_or(SubscriberError::UnknownList)?;
+    let definitions = attributes::definitions(&mut *transaction, &publication.id)
+        .await
+        .map_err(|_| SubscriberError::DatabaseFailure)?;
+    let subscriber = Subscriber {
+        attributes: attributes::validate(&definitions, subscriber.attributes)
+            .map_err(SubscriberError::InvalidAttributes)?,
+        ..subscriber
     };
-    let token = generate_subscription_token();
-    if store_token(&mut transaction, subscriber_id, &token)
+    let subscriber_id = upsert_subscriber(&mut transaction, &publication.id, &subscriber).await?;
+    let joined = lists::join(&mut *transaction, subscriber_id, list.list_id)
         .await
-        .is_err()
-    {
-        return HttpResponse::InternalServerError().finish();
+        .map_err(|_| SubscriberError::DatabaseFailure)?;
+    if !joined {
+        return Err(SubscriberError::DuplicateEmail);
     }
+    let token = generate_subscription_token();
+    store_token(
+        &mut transaction,
+        subscriber_id,
+        list.list_id,
+        &token,
+        &subscriber.attributes,
+    )
+    .await
+    .map_err(|_| SubscriberError::DatabaseFailure)?;
+    webhooks::enqueue(
+        &mut *transaction,
+        &publication.id,
+        WebhookEvent::Subscribed,
+        serde_json::json!({
+            "subscriber_id": subscriber_id,
+            "email": subscriber.email.as_ref(),
+            "name": subscriber.name.as_ref(),
+            "locale": subscriber.locale.as_ref(),
+            "attributes": subscriber.attributes,
+            "list": list.slug,
+        }),
+    )
+    .await
+    .map_err(|_| SubscriberError::DatabaseFailure)?;
 
-    if transaction.commit().await.is_err() {
-        return HttpResponse::InternalServerError().finish();
-    }
+    transaction
+        .commit()
+        .await
+        .map_err(|_| SubscriberError::DatabaseFailure)?;
+    metrics.subscription_event(SubscriptionEvent::Subscribed);
 
-    let res = send_confirmation_email(&email_client, subscriber, &base_url.0, &token).await;
-    if res.is_err() {
-        tracing::error!("Failed to send email {:?}", res);
-        return HttpResponse::InternalServerError().finish();
+    let res = send_confirmation_email(
+        &publication.email_client,
+        translations,
+        subscriber,
+        &publication.base_url,
+        &token,
+    )
+    .await;
+    if let Err(e) = res {
+        tracing::error!(error = %e.without_url(), "Failed to send email");
+        return Err(SubscriberError::EmailFailure);
     };
 
-    HttpResponse::Ok().finish()
+    Ok(())
 }
 
 async fn send_confirmation_email(
     email_client: &EmailClient,
+    translations: &Translations,
     subscriber: Subscriber,
     base_url: &str,
     subscription_token: &str,
@@ -96,48 +275,50 @@ async fn send_confirmation_email(
         "{}/subscriptions/confirm?subscription_token={}",
         base_url, subscription_token
     );
-    let body = format!(
-        "Welcome to our newsletter <br> Click <a href=\"{}\"here</a> to confirm the subscription",
-        confirmation_link
-    );
+    let mut args = FluentArgs::new();
+    args.set("link", confirmation_link);
+    let subject = translations.format(&subscriber.locale, "confirmation-email-subject", None);
+    let body = translations.format(&subscriber.locale, "confirmation-email-body", Some(&args));
 
     email_client
-        .send_email(subscriber.email, "Newsletter subscription", &body)
+        .send_email(subscriber.email, &subject, &body)
         .await
 }
 
+/// Inserts a new subscriber, or returns the publication's existing one with
+/// the same email. Existing subscribers are left as they are, anyone can sign
+/// up with their address.
 #[tracing::instrument(
     name = "Saving new subscriber details in the database",
     skip(form, transaction)
 )]
-async fn insert_subscriber(
+async fn upsert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
+    publication_id: &str,
     form: &Subscriber,
 ) -> Result<Uuid, SubscriberError> {
-    let subscriber_id = Uuid::new_v4();
-    let result = sqlx::query!(
+    sqlx::query_scalar!(
         r#"
-        INSERT INTO subscriptions(id, email, name, subscribed_at, status)
-        VALUES($1, $2, $3, $4, 'pending_confirmation')
+        INSERT INTO subscriptions
+            (id, publication_id, email, name, subscribed_at, status, locale, attributes)
+        VALUES($1, $2, $3, $4, $5, 'pending_confirmation', $6, $7)
+        ON CONFLICT (publication_id, email) DO UPDATE SET email = subscriptions.email
+        RETURNING id
         "#,
-        subscriber_id,
+        Uuid::new_v4(),
+        publication_id,
         form.email.as_ref(),
         form.name.as_ref(),
-        Utc::now()
+        Utc::now(),
+        form.locale.as_ref(),
+        Value::Object(form.attributes.clone())
     )
-    .execute(&mut **transaction)
-    .await;
-
-    if let Err(sqlx::Error::Database(e)) = result {
-        tracing::error!("Failed to execute query {:?}", e);
-        if *UNIQUE_CONSTRAINT_VIOLATION_CODE.to_string() == e.code().unwrap() {
-            return Err(SubscriberError::DuplicateEmail);
-        }
-
-        return Err(SubscriberError::DatabaseFailure);
-    }
-
-    Ok(subscriber_id)
+    .fetch_one(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!(error = %describe_db_error(&e), "Failed to execute query");
+        SubscriberError::DatabaseFailure
+    })
 }
 
 fn generate_subscription_token() -> String {
@@ -148,23 +329,28 @@ fn generate_subscription_token() -> String {
         .collect()
 }
 
-#[tracing::instrument(name = "Saving the subscription token")]
+/// Saves the token along with the attributes the signup set, which confirming
+/// applies to the subscriber.
+#[tracing::instrument(name = "Saving the subscription token", skip(attributes))]
 async fn store_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
+    list_id: Uuid,
     token: &str,
+    attributes: &Attributes,
 ) -> Result<(), sqlx::Error> {
     sqlx::query!(
-        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id)
-        VALUES($1, $2)"#,
+        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id, attributes)
+        VALUES($1, $2, $3, $4)"#,
         token,
-        subscriber_id
+        subscriber_id,
+        list_id,
+        Value::Object(attributes.clone())
     )
     .execute(&mut **transaction)
     .await
-    .map_err(|e| {
+    .inspect_err(|_| {
         tracing::error!("Failed to save subscription_token");
-        e
     })?;
 
     Ok(())
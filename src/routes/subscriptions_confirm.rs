@@ -1,71 +1,320 @@
+use super::{
+    pages::{confirmation_form, page},
+    Tenant,
+};
+use crate::{
+    config::ConfirmationSettings,
+    domain::Locale,
+    i18n::Translations,
+    metrics::{Metrics, SubscriptionEvent},
+    webhooks::{self, WebhookEvent},
+};
 use actix_web::{
     get,
+    http::{header, StatusCode},
+    post,
     web::{self},
-    HttpResponse,
+    HttpRequest, HttpResponse,
 };
-use sqlx::PgPool;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::Value;
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-#[derive(serde::Deserialize, Debug)]
+#[derive(serde::Deserialize, utoipa::IntoParams, utoipa::ToSchema, Debug)]
+#[into_params(parameter_in = Query)]
 pub struct Parameters {
+    /// Token from the confirmation email
     subscription_token: String,
 }
 
+/// The page behind the link in the confirmation email. Mail scanners fetch
+/// links on their own, so this only asks to confirm and never changes state.
+#[utoipa::path(
+    tag = "subscriptions",
+    params(Parameters),
+    responses(
+        (status = 200, description = "A form to confirm the subscription, or a page saying it already is", content_type = "text/html"),
+        (status = 303, description = "Redirect configured for the outcome"),
+        (status = 401, description = "Unknown token", content_type = "text/html"),
+        (status = 410, description = "The token has expired", content_type = "text/html"),
+        (status = 500, description = "Looking up the token failed")
+    )
+)]
 #[get("/subscriptions/confirm")]
-#[tracing::instrument(name = "Confirm a pending subscriber")]
+#[tracing::instrument(
+    name = "Show the confirmation page",
+    skip(tenant, translations, settings, request)
+)]
 pub async fn subscription_confirm(
     parameters: web::Query<Parameters>,
+    tenant: Tenant,
     connection: web::Data<PgPool>,
+    translations: web::Data<Translations>,
+    settings: web::Data<ConfirmationSettings>,
+    request: HttpRequest,
 ) -> HttpResponse {
-    let id = match get_subscriber_id_from_token(&connection, parameters.0.subscription_token).await
-    {
-        Ok(id) => id,
+    let token = parameters.0.subscription_token;
+    let owner = match get_subscriber_from_token(&connection, &tenant.id, token.clone()).await {
+        Ok(owner) => owner,
         Err(_) => return HttpResponse::InternalServerError().finish(),
     };
 
-    match id {
-        None => return HttpResponse::Unauthorized().finish(),
-        Some(id) => {
-            if confirm_subscriber(&connection, id).await.is_err() {
-                return HttpResponse::InternalServerError().finish();
+    let outcome = match owner {
+        None => ConfirmationOutcome::InvalidToken,
+        Some(owner) => match settled_outcome(&owner, settings.token_ttl()) {
+            Some(outcome) => outcome,
+            None => {
+                return confirmation_form(
+                    &translations,
+                    &owner.locale,
+                    &token,
+                    settings.auto_submit,
+                )
             }
+        },
+    };
+
+    outcome_page(outcome, &settings, &translations, &request)
+}
+
+#[utoipa::path(
+    tag = "subscriptions",
+    path = "/subscriptions/confirm",
+    request_body(content = Parameters, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Subscription confirmed, or it already was", content_type = "text/html"),
+        (status = 303, description = "Redirect configured for the outcome"),
+        (status = 401, description = "Unknown token", content_type = "text/html"),
+        (status = 410, description = "The token has expired", content_type = "text/html"),
+        (status = 500, description = "Confirming the subscriber failed")
+    )
+)]
+#[post("/subscriptions/confirm")]
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(form, tenant, translations, metrics, settings, request)
+)]
+pub async fn subscription_confirm_submit(
+    form: web::Form<Parameters>,
+    tenant: Tenant,
+    connection: web::Data<PgPool>,
+    translations: web::Data<Translations>,
+    metrics: web::Data<Metrics>,
+    settings: web::Data<ConfirmationSettings>,
+    request: HttpRequest,
+) -> HttpResponse {
+    match confirm_subscription(
+        &connection,
+        &metrics,
+        settings.token_ttl(),
+        &tenant.id,
+        form.0.subscription_token,
+    )
+    .await
+    {
+        Ok(outcome) => outcome_page(outcome, &settings, &translations, &request),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+fn outcome_page(
+    outcome: ConfirmationOutcome,
+    settings: &ConfirmationSettings,
+    translations: &Translations,
+    request: &HttpRequest,
+) -> HttpResponse {
+    let redirects = &settings.redirects;
+    let (status, redirect, locale, message_id) = match outcome {
+        ConfirmationOutcome::Confirmed(locale) => (
+            StatusCode::OK,
+            &redirects.confirmed,
+            locale,
+            "subscription-confirmed",
+        ),
+        ConfirmationOutcome::AlreadyConfirmed(locale) => (
+            StatusCode::OK,
+            &redirects.already_confirmed,
+            locale,
+            "subscription-already-confirmed",
+        ),
+        ConfirmationOutcome::Expired(locale) => (
+            StatusCode::GONE,
+            &redirects.token_expired,
+            locale,
+            "subscription-token-expired",
+        ),
+        ConfirmationOutcome::InvalidToken => {
+            let accept_language = request
+                .headers()
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|h| h.to_str().ok());
+            (
+                StatusCode::UNAUTHORIZED,
+                &redirects.token_invalid,
+                translations.negotiate(None, accept_language),
+                "subscription-token-invalid",
+            )
         }
+    };
+
+    page(
+        status,
+        redirect.as_deref(),
+        translations,
+        &locale,
+        message_id,
+    )
+}
+
+pub(crate) enum ConfirmationOutcome {
+    Confirmed(Locale),
+    AlreadyConfirmed(Locale),
+    Expired(Locale),
+    InvalidToken,
+}
+
+/// Confirms the publication's subscriber owning the token. Tokens older than
+/// `token_ttl` no longer confirm anyone, but still tell a confirmed
+/// subscriber so.
+pub(crate) async fn confirm_subscription(
+    connection: &PgPool,
+    metrics: &Metrics,
+    token_ttl: Duration,
+    publication_id: &str,
+    subscription_token: String,
+) -> Result<ConfirmationOutcome, sqlx::Error> {
+    let Some(subscriber) =
+        get_subscriber_from_token(connection, publication_id, subscription_token).await?
+    else {
+        return Ok(ConfirmationOutcome::InvalidToken);
+    };
+    if let Some(outcome) = settled_outcome(&subscriber, token_ttl) {
+        return Ok(outcome);
     }
 
-    return HttpResponse::Ok().finish();
+    let mut transaction = connection.begin().await?;
+    if !confirm_subscriber(&mut transaction, &subscriber).await? {
+        // Confirmed by a concurrent request since the lookup.
+        return Ok(ConfirmationOutcome::AlreadyConfirmed(subscriber.locale));
+    }
+    webhooks::enqueue(
+        &mut *transaction,
+        publication_id,
+        WebhookEvent::Confirmed,
+        serde_json::json!({
+            "subscriber_id": subscriber.id,
+            "email": subscriber.email,
+            "list": subscriber.list,
+        }),
+    )
+    .await?;
+    transaction.commit().await?;
+    metrics.subscription_event(SubscriptionEvent::Confirmed);
+
+    Ok(ConfirmationOutcome::Confirmed(subscriber.locale))
+}
+
+/// The outcome for a token that can't confirm its subscriber anymore, `None`
+/// while it still can.
+fn settled_outcome(owner: &TokenOwner, token_ttl: Duration) -> Option<ConfirmationOutcome> {
+    match owner.status.as_str() {
+        "confirmed" => return Some(ConfirmationOutcome::AlreadyConfirmed(owner.locale.clone())),
+        // Left the list since, rejoining sends a new token.
+        "unsubscribed" => return Some(ConfirmationOutcome::InvalidToken),
+        _ => {}
+    }
+    if owner.token_created_at + token_ttl < Utc::now() {
+        return Some(ConfirmationOutcome::Expired(owner.locale.clone()));
+    }
+
+    None
+}
+
+/// The subscriber a token belongs to, and their membership on the list
+/// the token confirms.
+struct TokenOwner {
+    id: Uuid,
+    list_id: Uuid,
+    list: String,
+    email: String,
+    locale: Locale,
+    status: String,
+    token_created_at: DateTime<Utc>,
+    /// Set when signing up, saved on confirmation
+    attributes: Value,
 }
 
 #[tracing::instrument(name = "Fech subscriber by token")]
-async fn get_subscriber_id_from_token(
+async fn get_subscriber_from_token(
     connection: &PgPool,
+    publication_id: &str,
     subscription_token: String,
-) -> Result<Option<Uuid>, sqlx::Error> {
+) -> Result<Option<TokenOwner>, sqlx::Error> {
     let result = sqlx::query!(
-        "SELECT subscriber_id FROM subscription_tokens WHERE subscription_token = $1",
-        subscription_token
+        r#"SELECT t.subscriber_id, t.list_id, t.created_at, t.attributes, s.email, s.locale,
+            m.status, l.slug AS list
+        FROM subscription_tokens t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        JOIN list_memberships m ON m.subscriber_id = t.subscriber_id AND m.list_id = t.list_id
+        JOIN lists l ON l.list_id = t.list_id
+        WHERE t.subscription_token = $1 AND s.publication_id = $2"#,
+        subscription_token,
+        publication_id
     )
     .fetch_optional(connection)
     .await
-    .map_err(|e| {
+    .inspect_err(|_| {
         tracing::error!("Failed to fech subscriber");
-        e
     })?;
 
-    Ok(result.map(|r| r.subscriber_id))
+    Ok(result.map(|r| TokenOwner {
+        id: r.subscriber_id,
+        list_id: r.list_id,
+        list: r.list,
+        email: r.email,
+        locale: Locale::parse(r.locale).unwrap_or_default(),
+        status: r.status,
+        token_created_at: r.created_at,
+        attributes: r.attributes,
+    }))
 }
 
-#[tracing::instrument(name = "Confirm subscriber")]
-async fn confirm_subscriber(connection: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        "UPDATE subscriptions SET status = 'confirmed' WHERE id = $1",
-        subscriber_id
+#[tracing::instrument(
+    name = "Confirm subscriber",
+    skip(transaction, owner),
+    fields(subscriber_id = %owner.id, list_id = %owner.list_id)
+)]
+async fn confirm_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    owner: &TokenOwner,
+) -> Result<bool, sqlx::Error> {
+    let now = Utc::now();
+    let result = sqlx::query!(
+        r#"UPDATE list_memberships SET status = 'confirmed', confirmed_at = $3
+        WHERE subscriber_id = $1 AND list_id = $2 AND status = 'pending_confirmation'"#,
+        owner.id,
+        owner.list_id,
+        now
     )
-    .execute(connection)
+    .execute(&mut **transaction)
     .await
-    .map_err(|e| {
+    .inspect_err(|_| {
         tracing::error!("Failed to confirm subscriber");
-        e
     })?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    // Confirming any list verifies the address itself.
+    sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'confirmed', unsubscribed_at = NULL,
+            attributes = attributes || $2
+        WHERE id = $1"#,
+        owner.id,
+        owner.attributes
+    )
+    .execute(&mut **transaction)
+    .await?;
 
-    Ok(())
+    Ok(true)
 }
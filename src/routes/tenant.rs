@@ -0,0 +1,100 @@
+use crate::publications::{Publication, Publications};
+use actix_web::{
+    dev::Payload, error::ErrorInternalServerError, http::header, web, FromRequest, HttpRequest,
+};
+use std::future::{ready, Ready};
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// The publication a request is for, picked by the host it was sent to.
+#[derive(Debug)]
+pub struct Tenant(pub Arc<Publication>);
+
+impl Deref for Tenant {
+    type Target = Publication;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for Tenant {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let tenant = match req.app_data::<web::Data<Publications>>() {
+            Some(publications) => Ok(Tenant(
+                publications
+                    .for_host(&request_host(req, publications))
+                    .clone(),
+            )),
+            None => Err(ErrorInternalServerError("Publications are not configured")),
+        };
+
+        ready(tenant)
+    }
+}
+
+/// Any client can send `X-Forwarded-Host` or `Forwarded`, so they only count
+/// when a trusted proxy sent the request.
+fn request_host(req: &HttpRequest, publications: &Publications) -> String {
+    let proxied = req
+        .peer_addr()
+        .is_some_and(|peer| publications.trusts_proxy(peer.ip()));
+    if proxied {
+        return req.connection_info().host().to_owned();
+    }
+
+    req.headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| req.uri().authority().map(|authority| authority.as_str()))
+        .unwrap_or_default()
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{get_embedded_config, Enviroment, PublicationSettings};
+    use crate::metrics::Metrics;
+    use crate::publications::{Publications, DEFAULT_PUBLICATION};
+    use crate::routes::tenant::request_host;
+    use actix_web::test::TestRequest;
+
+    const PROXY: &str = "10.0.0.1:443";
+
+    fn publications() -> Publications {
+        let mut settings = get_embedded_config(Enviroment::Test).expect("Failed to load config");
+        settings.application.trusted_proxies = vec![String::from("10.0.0.1")];
+        settings.publications.insert(
+            String::from("acme"),
+            PublicationSettings {
+                hosts: vec![String::from("acme.test")],
+                base_url: String::from("https://acme.test"),
+                email_client: settings.email_client.clone(),
+            },
+        );
+        Publications::from_settings(&settings, &Metrics::new()).unwrap()
+    }
+
+    fn publication_for(peer: &str) -> String {
+        let publications = publications();
+        let request = TestRequest::default()
+            .peer_addr(peer.parse().unwrap())
+            .insert_header(("Host", "127.0.0.1:8000"))
+            .insert_header(("X-Forwarded-Host", "acme.test"))
+            .to_http_request();
+
+        publications
+            .for_host(&request_host(&request, &publications))
+            .id
+            .clone()
+    }
+
+    #[test]
+    fn forwarded_hosts_only_count_from_trusted_proxies() {
+        assert_eq!(publication_for(PROXY), "acme");
+        assert_eq!(publication_for("203.0.113.7:50000"), DEFAULT_PUBLICATION);
+    }
+}
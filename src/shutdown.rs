@@ -0,0 +1,59 @@
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownHandle(CancellationToken);
+
+impl ShutdownHandle {
+    pub fn trigger(&self) {
+        self.0.cancel();
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.0.is_cancelled()
+    }
+
+    pub async fn triggered(&self) {
+        self.0.cancelled().await
+    }
+}
+
+pub async fn trigger_on_signal(handle: ShutdownHandle) {
+    let interrupt = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for SIGINT");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => tracing::info!("Received SIGINT, shutting down"),
+        _ = terminate => tracing::info!("Received SIGTERM, shutting down"),
+        _ = handle.triggered() => return,
+    }
+    handle.trigger();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shutdown::ShutdownHandle;
+
+    #[tokio::test]
+    async fn clones_observe_the_trigger() {
+        let handle = ShutdownHandle::default();
+        let worker = handle.clone();
+        let waiting = tokio::spawn(async move { worker.triggered().await });
+
+        handle.trigger();
+
+        waiting.await.unwrap();
+        assert!(handle.is_triggered());
+    }
+}
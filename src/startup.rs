@@ -1,77 +1,361 @@
+use crate::api_keys::RateLimiter;
 use crate::config::{DatabaseSettings, Settings};
-use crate::domain::Email;
-use crate::email_client::EmailClient;
-use crate::routes::{health_check, subscribe, subscription_confirm};
-use actix_web::dev::Server;
+use crate::i18n::Translations;
+use crate::metrics::{Metrics, RequestMetrics};
+use crate::publications::Publications;
+use crate::routes::{
+    admin, api_v1, confirm_email_change, email_change_page, export_metrics, health_check,
+    health_live, health_ready, openapi_json, preferences_page, request_email_change,
+    send_preferences_link, subscribe, subscription_confirm, subscription_confirm_submit,
+    swagger_ui, unsubscribe, update_preferences,
+};
+use crate::shutdown::ShutdownHandle;
+use crate::workers::{Heartbeats, Workers};
+use actix_web::dev::{Server, ServerHandle};
 use actix_web::web::Data;
 use actix_web::{web, App, HttpServer};
+use sqlx::migrate::Migrator;
 use sqlx::PgPool;
 use std::net::TcpListener;
+use std::time::{Duration, Instant};
 use tracing_actix_web::TracingLogger;
 
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+const UNDEFINED_TABLE_CODE: &str = "42P01";
+
+// Arbitrary but fixed, so every instance contends for the same lock.
+const MIGRATION_LOCK_ID: i64 = 0x6e65_7773_6c65_7474;
+
 pub struct Application {
     port: String,
     server: Server,
+    metrics_port: Option<String>,
+    metrics_server: Option<Server>,
+    heartbeats: Heartbeats,
+    shutdown: ShutdownHandle,
+    shutdown_timeout: Duration,
+    workers: Workers,
 }
 
-#[derive(Debug)]
-pub struct ApplicationBaseUrl(pub String);
-
 impl Application {
     pub async fn build(config: Settings) -> Result<Self, std::io::Error> {
-        // let sender = config.email_client.sender().expect("Could not get parse the sender email");
-        let sender =
-            Email::parse(String::from("lawsofoutreach@gmail.com")).expect("Failed to parse email");
-        let code = String::from("");
-        let email_client = EmailClient::new(config.email_client.url, sender, code);
+        let metrics = Metrics::new();
+        let shutdown = ShutdownHandle::default();
+        let shutdown_timeout = Duration::from_secs(config.application.shutdown_timeout_secs);
+        let publications = Publications::from_settings(&config, &metrics)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
 
         let connection_pool = get_connection_pool(&config.database);
+        if config.database.connect_eagerly {
+            wait_for_database(&connection_pool, &config.database)
+                .await
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::ConnectionRefused, e))?;
+        }
+        if config.database.migrate_on_startup {
+            run_migrations(&connection_pool)
+                .await
+                .map_err(std::io::Error::other)?;
+        } else if config.database.connect_eagerly {
+            ensure_known_migrations(&connection_pool)
+                .await
+                .map_err(std::io::Error::other)?;
+        }
+        let heartbeats = Heartbeats::default();
+        let workers = Workers::spawn(
+            &connection_pool,
+            &config.webhooks,
+            &metrics,
+            &heartbeats,
+            &shutdown,
+        );
         let address = format!("{}:{}", config.application.host, config.application.port);
         let listener = TcpListener::bind(address).expect("Failed to create a tcp listnener");
         let port = listener.local_addr().unwrap().port().to_string();
 
+        let (metrics_port, metrics_server) = if config.metrics.enabled {
+            let address = format!("{}:{}", config.metrics.host, config.metrics.port);
+            let listener = TcpListener::bind(address)?;
+            let port = listener.local_addr()?.port().to_string();
+            let server = run_metrics_server(
+                listener,
+                connection_pool.clone(),
+                metrics.clone(),
+                shutdown_timeout,
+            )?;
+            (Some(port), Some(server))
+        } else {
+            (None, None)
+        };
+
         let server = run(
             listener,
             connection_pool,
-            email_client,
-            config.application.base_url,
+            publications,
+            config,
+            heartbeats.clone(),
+            metrics,
         )?;
 
-        Ok(Self { port, server })
+        Ok(Self {
+            port,
+            server,
+            metrics_port,
+            metrics_server,
+            heartbeats,
+            shutdown,
+            shutdown_timeout,
+            workers,
+        })
     }
 
     pub fn port(&self) -> String {
         self.port.to_string()
     }
 
+    pub fn metrics_port(&self) -> Option<String> {
+        self.metrics_port.clone()
+    }
+
+    pub fn heartbeats(&self) -> Heartbeats {
+        self.heartbeats.clone()
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
     pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
-        self.server.await
+        let mut handles = vec![self.server.handle()];
+        handles.extend(self.metrics_server.as_ref().map(Server::handle));
+        let stopping = tokio::spawn(stop_servers_on_shutdown(self.shutdown.clone(), handles));
+
+        // Servers resolve once they stopped accepting and drained in-flight requests.
+        let result = match self.metrics_server {
+            Some(metrics_server) => tokio::try_join!(self.server, metrics_server).map(|_| ()),
+            None => self.server.await,
+        };
+
+        // Workers were told to stop together with the servers and share the same deadline.
+        self.shutdown.trigger();
+        let started = stopping.await.unwrap_or_else(|_| Instant::now());
+        let remaining = self.shutdown_timeout.saturating_sub(started.elapsed());
+        self.workers.wait(remaining).await;
+        tracing::info!("Shutdown complete");
+
+        result
     }
 }
 
 pub fn get_connection_pool(config: &DatabaseSettings) -> PgPool {
-    PgPool::connect_lazy(config.connection_string().as_str()).expect("Failed to connect to db")
+    config.pool_options().connect_lazy_with(config.with_db())
+}
+
+#[tracing::instrument(name = "Waiting for the database", skip_all)]
+pub async fn wait_for_database(
+    pool: &PgPool,
+    config: &DatabaseSettings,
+) -> Result<(), sqlx::Error> {
+    let mut backoff = Duration::from_millis(config.connect_backoff_ms);
+    let mut attempt = 0;
+    loop {
+        match sqlx::query("SELECT 1").execute(pool).await {
+            Ok(_) => return Ok(()),
+            Err(e) if attempt < config.connect_retries => {
+                attempt += 1;
+                tracing::warn!(
+                    "Database is not reachable ({}), retrying in {:?} ({}/{})",
+                    e,
+                    backoff,
+                    attempt,
+                    config.connect_retries
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            Err(e) => {
+                tracing::error!("Giving up connecting to the database: {}", e);
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// Applies pending migrations while holding an advisory lock, so instances
+/// starting at the same time take turns instead of racing each other.
+#[tracing::instrument(name = "Running database migrations", skip_all)]
+pub async fn run_migrations(pool: &PgPool) -> Result<(), String> {
+    let mut connection = pool
+        .acquire()
+        .await
+        .map_err(|e| format!("Failed to connect to the database: {}", e))?;
+    sqlx::query("SELECT pg_advisory_lock($1)")
+        .bind(MIGRATION_LOCK_ID)
+        .execute(&mut *connection)
+        .await
+        .map_err(|e| format!("Failed to acquire the migration lock: {}", e))?;
+
+    let result = async {
+        check_known_migrations(&mut *connection).await?;
+        MIGRATOR
+            .run(&mut *connection)
+            .await
+            .map_err(|e| format!("Failed to migrate the database: {}", e))
+    }
+    .await;
+
+    if let Err(e) = sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(MIGRATION_LOCK_ID)
+        .execute(&mut *connection)
+        .await
+    {
+        // The lock is released with the session anyway, so drop the connection.
+        tracing::warn!("Failed to release the migration lock: {}", e);
+        connection.detach();
+    }
+
+    result
+}
+
+#[tracing::instrument(name = "Checking the database schema", skip_all)]
+pub async fn ensure_known_migrations(pool: &PgPool) -> Result<(), String> {
+    check_known_migrations(pool).await
+}
+
+/// Fails when the database has migrations newer than this release. Startup
+/// only checks when it connects eagerly or migrates, readiness always does.
+pub(crate) async fn check_known_migrations<'c>(
+    executor: impl sqlx::PgExecutor<'c>,
+) -> Result<(), String> {
+    let applied: Vec<i64> =
+        match sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(executor)
+            .await
+        {
+            Ok(applied) => applied,
+            Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some(UNDEFINED_TABLE_CODE) => {
+                vec![]
+            }
+            Err(e) => return Err(format!("Failed to read applied migrations: {}", e)),
+        };
+
+    let unknown: Vec<String> = applied
+        .into_iter()
+        .filter(|version| !MIGRATOR.iter().any(|m| m.version == *version))
+        .map(|version| version.to_string())
+        .collect();
+    if !unknown.is_empty() {
+        return Err(format!(
+            "The database has migrations this release does not know about ({}), refusing to start",
+            unknown.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+async fn stop_servers_on_shutdown(shutdown: ShutdownHandle, handles: Vec<ServerHandle>) -> Instant {
+    shutdown.triggered().await;
+    let started = Instant::now();
+    tracing::info!("Stopping HTTP servers, draining in-flight requests");
+    for handle in handles {
+        handle.stop(true).await;
+    }
+
+    started
 }
 
 pub fn run(
     listener: TcpListener,
     db_pool: PgPool,
-    email_client: EmailClient,
-    base_url: String,
+    publications: Publications,
+    config: Settings,
+    heartbeats: Heartbeats,
+    metrics: Metrics,
 ) -> Result<Server, std::io::Error> {
+    let expose_swagger_ui = config.api_docs.swagger_ui;
+    let api_settings = config.api;
+    let cors = config.cors;
+    let confirmation_settings = Data::new(config.confirmation);
+    let preference_settings = Data::new(config.preferences);
+    let rate_limiter = Data::new(RateLimiter::default());
+    let shutdown_timeout = config.application.shutdown_timeout_secs;
     let db_pool = web::Data::new(db_pool);
-    let email_client = web::Data::new(email_client);
-    let base_url = Data::new(ApplicationBaseUrl(base_url));
+    let publications = Data::new(publications);
+    let translations = Data::new(Translations::load());
+    let heartbeats = Data::new(heartbeats);
+    let health_settings = Data::new(config.health);
+    let metrics = Data::new(metrics);
     let server = HttpServer::new(move || {
-        App::new()
+        let app = App::new()
+            .wrap(RequestMetrics(metrics.get_ref().clone()))
             .wrap(TracingLogger::default())
             .service(health_check)
-            .service(subscribe)
-            .service(subscription_confirm)
+            .service(health_live)
+            .service(health_ready)
+            .service(openapi_json)
+            .service(
+                web::scope("/api/v1")
+                    .wrap(api_v1::deprecation_headers(&api_settings))
+                    .configure(api_v1::configure),
+            )
+            .service(web::scope("/admin").configure(admin::configure))
+            .service(preferences_page)
+            .service(send_preferences_link)
+            .service(update_preferences)
+            .service(unsubscribe)
+            .service(request_email_change)
+            .service(email_change_page)
+            .service(confirm_email_change)
+            .app_data(db_pool.clone())
+            .app_data(publications.clone())
+            .app_data(translations.clone())
+            .app_data(heartbeats.clone())
+            .app_data(health_settings.clone())
+            .app_data(metrics.clone())
+            .app_data(rate_limiter.clone())
+            .app_data(confirmation_settings.clone())
+            .app_data(preference_settings.clone());
+
+        let app = if expose_swagger_ui {
+            app.service(swagger_ui)
+        } else {
+            app
+        };
+        // The only routes browsers call cross-origin, from embedded forms. The
+        // scope matches every path, so it has to be registered last.
+        app.service(
+            web::scope("")
+                .wrap(cors.middleware())
+                .service(subscribe)
+                .service(subscription_confirm)
+                .service(subscription_confirm_submit),
+        )
+    })
+    .disable_signals()
+    .shutdown_timeout(shutdown_timeout)
+    .listen(listener)?
+    .run();
+
+    Ok(server)
+}
+
+pub fn run_metrics_server(
+    listener: TcpListener,
+    db_pool: PgPool,
+    metrics: Metrics,
+    shutdown_timeout: Duration,
+) -> Result<Server, std::io::Error> {
+    let db_pool = web::Data::new(db_pool);
+    let metrics = web::Data::new(metrics);
+    let server = HttpServer::new(move || {
+        App::new()
+            .service(export_metrics)
             .app_data(db_pool.clone())
-            .app_data(email_client.clone())
-            .app_data(base_url.clone())
+            .app_data(metrics.clone())
     })
+    .disable_signals()
+    .shutdown_timeout(shutdown_timeout.as_secs())
     .listen(listener)?
     .run();
 
@@ -0,0 +1,185 @@
+use crate::attributes::Attributes;
+use chrono::Utc;
+use serde_json::Value;
+use sqlx::PgPool;
+
+/// Which of a publication's subscribers a bulk operation applies to. Every
+/// condition that is set has to hold.
+#[derive(Debug, Default)]
+pub struct Selection {
+    pub status: Option<String>,
+    /// Checked against the attribute definitions already
+    pub attributes: Attributes,
+    pub tag: Option<String>,
+    /// Lowercase, matched regardless of how subscribers wrote them
+    pub emails: Option<Vec<String>>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema, Debug)]
+pub struct TagCount {
+    #[schema(example = "beta-tester")]
+    pub tag: String,
+    pub subscribers: i64,
+}
+
+#[derive(Debug)]
+pub enum TagError {
+    InvalidTag(String),
+    Unexpected(sqlx::Error),
+}
+
+impl From<sqlx::Error> for TagError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Unexpected(e)
+    }
+}
+
+pub fn validate_tag(tag: &str) -> Result<(), String> {
+    let valid = !tag.is_empty()
+        && tag.len() <= 64
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid {
+        return Err(String::from(
+            "Tags must be lowercase letters, digits and dashes",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Tags the selected subscribers, returning how many weren't tagged yet.
+#[tracing::instrument(
+    name = "Tagging subscribers",
+    skip(connection, selection),
+    fields(
+        emails = selection.emails.as_ref().map(|emails| emails.len()),
+        status = ?selection.status,
+        filter_tag = ?selection.tag
+    )
+)]
+pub async fn apply_tag(
+    connection: &PgPool,
+    publication_id: &str,
+    tag: &str,
+    selection: &Selection,
+) -> Result<u64, TagError> {
+    validate_tag(tag).map_err(TagError::InvalidTag)?;
+    let result = sqlx::query!(
+        r#"INSERT INTO subscriber_tags (subscriber_id, tag, tagged_at)
+        SELECT s.id, $2, $3 FROM subscriptions s
+        WHERE s.publication_id = $1
+            AND ($4::TEXT IS NULL OR s.status = $4)
+            AND s.attributes @> $5
+            AND ($6::TEXT IS NULL
+                OR EXISTS (SELECT 1 FROM subscriber_tags t WHERE t.subscriber_id = s.id AND t.tag = $6))
+            AND ($7::TEXT[] IS NULL OR lower(s.email) = ANY($7))
+        ON CONFLICT (subscriber_id, tag) DO NOTHING"#,
+        publication_id,
+        tag,
+        Utc::now(),
+        selection.status,
+        Value::Object(selection.attributes.clone()),
+        selection.tag,
+        selection.emails.as_deref()
+    )
+    .execute(connection)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Removes the tag from the selected subscribers, returning how many had it.
+#[tracing::instrument(
+    name = "Untagging subscribers",
+    skip(connection, selection),
+    fields(
+        emails = selection.emails.as_ref().map(|emails| emails.len()),
+        status = ?selection.status,
+        filter_tag = ?selection.tag
+    )
+)]
+pub async fn remove_tag(
+    connection: &PgPool,
+    publication_id: &str,
+    tag: &str,
+    selection: &Selection,
+) -> Result<u64, TagError> {
+    validate_tag(tag).map_err(TagError::InvalidTag)?;
+    let result = sqlx::query!(
+        r#"DELETE FROM subscriber_tags t USING subscriptions s
+        WHERE t.subscriber_id = s.id AND t.tag = $2
+            AND s.publication_id = $1
+            AND ($3::TEXT IS NULL OR s.status = $3)
+            AND s.attributes @> $4
+            AND ($5::TEXT IS NULL
+                OR EXISTS (SELECT 1 FROM subscriber_tags o WHERE o.subscriber_id = s.id AND o.tag = $5))
+            AND ($6::TEXT[] IS NULL OR lower(s.email) = ANY($6))"#,
+        publication_id,
+        tag,
+        selection.status,
+        Value::Object(selection.attributes.clone()),
+        selection.tag,
+        selection.emails.as_deref()
+    )
+    .execute(connection)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// The lowercase `emails` no subscriber of the publication has.
+#[tracing::instrument(
+    name = "Finding unknown emails",
+    skip(connection, emails),
+    fields(emails = emails.len())
+)]
+pub async fn unmatched_emails(
+    connection: &PgPool,
+    publication_id: &str,
+    emails: &[String],
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT e.email AS "email!" FROM UNNEST($2::TEXT[]) AS e(email)
+        WHERE NOT EXISTS (
+            SELECT 1 FROM subscriptions s
+            WHERE s.publication_id = $1 AND lower(s.email) = e.email
+        )
+        ORDER BY e.email"#,
+        publication_id,
+        emails
+    )
+    .fetch_all(connection)
+    .await
+}
+
+#[tracing::instrument(name = "Listing tags", skip(connection))]
+pub async fn list_tags(
+    connection: &PgPool,
+    publication_id: &str,
+) -> Result<Vec<TagCount>, sqlx::Error> {
+    sqlx::query_as!(
+        TagCount,
+        r#"SELECT t.tag, COUNT(*) AS "subscribers!" FROM subscriber_tags t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE s.publication_id = $1
+        GROUP BY t.tag ORDER BY t.tag"#,
+        publication_id
+    )
+    .fetch_all(connection)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tags::validate_tag;
+
+    #[test]
+    fn tags_are_slugs() {
+        assert!(validate_tag("conference-2026").is_ok());
+        for invalid in ["", "Beta", "beta tester", "beta_tester", &"a".repeat(65)] {
+            assert!(validate_tag(invalid).is_err(), "{} was accepted", invalid);
+        }
+    }
+}
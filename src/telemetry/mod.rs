@@ -0,0 +1,324 @@
+mod redaction;
+
+use crate::config::{LogFormat, LogSink, TelemetrySettings};
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    trace::{BatchSpanProcessor, SdkTracerProvider},
+    Resource,
+};
+use tracing::{subscriber::set_global_default, Subscriber};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
+use tracing_log::LogTracer;
+use tracing_subscriber::{
+    fmt::{self, writer::BoxMakeWriter, MakeWriter},
+    layer::SubscriberExt,
+    EnvFilter, Registry,
+};
+
+pub use redaction::{
+    describe_db_error, redact_email, redact_field, redact_name, RedactingProcessor,
+    RedactingWriter, Redaction,
+};
+
+pub struct TelemetryGuard {
+    tracer_provider: Option<SdkTracerProvider>,
+    file_writer: Option<WorkerGuard>,
+}
+
+impl TelemetryGuard {
+    pub fn shutdown(self) {
+        if let Some(provider) = &self.tracer_provider {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed to flush traces: {}", e);
+            }
+        }
+        // Dropping the worker guard flushes buffered log lines to the file.
+        drop(self.file_writer);
+    }
+}
+
+pub fn init_subscriber(subscriber: impl Subscriber + Sync + Send) {
+    LogTracer::init().expect("Failed to set logger");
+    set_global_default(subscriber).expect("Failed to set subscriber");
+    global::set_text_map_propagator(TraceContextPropagator::new());
+}
+
+pub fn get_subscriber(
+    settings: &TelemetrySettings,
+) -> Result<(impl Subscriber + Sync + Send, TelemetryGuard), String> {
+    let (sink, file_writer) = match settings.sink {
+        LogSink::Stdout => (BoxMakeWriter::new(std::io::stdout), None),
+        LogSink::None => (BoxMakeWriter::new(std::io::sink), None),
+        LogSink::File => {
+            let appender = RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .filename_prefix(&settings.service_name)
+                .filename_suffix("log")
+                .build(&settings.log_directory)
+                .map_err(|e| format!("Failed to open log file: {}", e))?;
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (BoxMakeWriter::new(writer), Some(guard))
+        }
+    };
+
+    let (subscriber, mut guard) = get_subscriber_with_sink(settings, sink)?;
+    guard.file_writer = file_writer;
+
+    Ok((subscriber, guard))
+}
+
+pub fn get_subscriber_with_sink<Sink>(
+    settings: &TelemetrySettings,
+    sink: Sink,
+) -> Result<(impl Subscriber + Sync + Send, TelemetryGuard), String>
+where
+    Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&settings.log_level))
+        .map_err(|e| format!("Invalid log level: {}", e))?;
+    let (bunyan_layer, pretty_layer) = match settings.format {
+        LogFormat::Bunyan => (
+            Some(BunyanFormattingLayer::new(
+                settings.service_name.clone(),
+                RedactingWriter(sink),
+            )),
+            None,
+        ),
+        LogFormat::Pretty => (
+            None,
+            Some(
+                fmt::layer()
+                    .fmt_fields(redaction::redacting_fields())
+                    .with_ansi(settings.sink == LogSink::Stdout)
+                    .with_writer(sink),
+            ),
+        ),
+    };
+
+    let tracer_provider = settings
+        .otlp_endpoint
+        .as_deref()
+        .map(|endpoint| get_tracer_provider(endpoint, &settings.service_name))
+        .transpose()
+        .map_err(|e| format!("Failed to build trace exporter: {}", e))?;
+    let otel_layer = tracer_provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer(settings.service_name.clone()))
+    });
+
+    let subscriber = Registry::default()
+        .with(filter)
+        .with(JsonStorageLayer)
+        .with(bunyan_layer)
+        .with(pretty_layer)
+        .with(otel_layer);
+
+    Ok((
+        subscriber,
+        TelemetryGuard {
+            tracer_provider,
+            file_writer: None,
+        },
+    ))
+}
+
+fn get_tracer_provider(
+    endpoint: &str,
+    service_name: &str,
+) -> Result<SdkTracerProvider, opentelemetry_otlp::ExporterBuildError> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()?;
+
+    Ok(SdkTracerProvider::builder()
+        .with_span_processor(RedactingProcessor(
+            BatchSpanProcessor::builder(exporter).build(),
+        ))
+        .with_resource(
+            Resource::builder()
+                .with_service_name(service_name.to_owned())
+                .build(),
+        )
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{LogFormat, LogSink, TelemetrySettings},
+        domain::{Email, SubscriberName},
+        telemetry::{get_subscriber, get_subscriber_with_sink, Redaction},
+    };
+    use actix_web::{get, test as actix_test, App, HttpResponse};
+    use opentelemetry::{global, trace::TraceContextExt};
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use std::sync::{Arc, Mutex};
+    use tracing_actix_web::TracingLogger;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::fmt::MakeWriter;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    const TRACEPARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+    fn settings(otlp_endpoint: Option<String>) -> TelemetrySettings {
+        TelemetrySettings {
+            service_name: String::from("newsletter-test"),
+            log_level: String::from("info"),
+            format: LogFormat::Bunyan,
+            sink: LogSink::None,
+            log_directory: String::from("logs"),
+            redaction: Redaction::Mask,
+            otlp_endpoint,
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl CapturedLogs {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for CapturedLogs {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn logs_go_to_the_configured_sink_in_the_configured_format() {
+        let logs = CapturedLogs::default();
+        let (subscriber, _guard) = get_subscriber_with_sink(&settings(None), logs.clone()).unwrap();
+        tracing::subscriber::with_default(subscriber, || tracing::info!("hello"));
+
+        let line: serde_json::Value = serde_json::from_str(logs.contents().trim()).unwrap();
+        assert_eq!(line["name"], "newsletter-test");
+        assert_eq!(line["msg"], "hello");
+
+        let logs = CapturedLogs::default();
+        let pretty = TelemetrySettings {
+            format: LogFormat::Pretty,
+            ..settings(None)
+        };
+        let (subscriber, _guard) = get_subscriber_with_sink(&pretty, logs.clone()).unwrap();
+        tracing::subscriber::with_default(subscriber, || tracing::info!("hello"));
+
+        assert!(serde_json::from_str::<serde_json::Value>(&logs.contents()).is_err());
+        assert!(logs.contents().contains("hello"));
+    }
+
+    #[test]
+    fn log_level_filters_events() {
+        let logs = CapturedLogs::default();
+        let quiet = TelemetrySettings {
+            log_level: String::from("warn"),
+            ..settings(None)
+        };
+        let (subscriber, _guard) = get_subscriber_with_sink(&quiet, logs.clone()).unwrap();
+        tracing::subscriber::with_default(subscriber, || tracing::info!("hello"));
+
+        assert!(logs.contents().is_empty());
+    }
+
+    #[test]
+    fn subscriber_details_are_redacted_in_spans() {
+        for format in [LogFormat::Bunyan, LogFormat::Pretty] {
+            let logs = CapturedLogs::default();
+            let settings = TelemetrySettings {
+                format,
+                ..settings(None)
+            };
+            let (subscriber, _guard) = get_subscriber_with_sink(&settings, logs.clone()).unwrap();
+            tracing::subscriber::with_default(subscriber, || {
+                let email = Email::parse(String::from("ursula@example.com")).unwrap();
+                let name = SubscriberName::parse(String::from("Ursula Le Guin")).unwrap();
+                tracing::info_span!(
+                    "subscribe",
+                    subscriber_email = %email.as_ref(),
+                    subscriber_name = %name.as_ref(),
+                    subscriber = ?email,
+                )
+                .in_scope(|| {
+                    tracing::info!(subscriber_email = "octavia@example.com", "subscribed")
+                });
+            });
+
+            let logs = logs.contents();
+            assert!(!logs.contains("ursula@example.com"), "{}", logs);
+            assert!(!logs.contains("Ursula Le Guin"), "{}", logs);
+            assert!(!logs.contains("octavia@example.com"), "{}", logs);
+            assert!(logs.contains("u***@example.com"), "{}", logs);
+        }
+    }
+
+    #[tokio::test]
+    async fn spans_are_exported_to_the_otlp_collector() {
+        let collector = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/traces"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1..)
+            .mount(&collector)
+            .await;
+
+        let (subscriber, guard) =
+            get_subscriber(&settings(Some(collector.uri()))).expect("Failed to build subscriber");
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("exported span").in_scope(|| {});
+        });
+        tokio::task::spawn_blocking(move || guard.shutdown())
+            .await
+            .unwrap();
+    }
+
+    #[get("/trace")]
+    async fn trace_id() -> HttpResponse {
+        let context = tracing::Span::current().context();
+        HttpResponse::Ok().body(context.span().span_context().trace_id().to_string())
+    }
+
+    #[actix_web::test]
+    async fn incoming_traceparent_is_continued() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let collector = MockServer::start().await;
+        let (subscriber, _guard) =
+            get_subscriber(&settings(Some(collector.uri()))).expect("Failed to build subscriber");
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let app =
+            actix_test::init_service(App::new().wrap(TracingLogger::default()).service(trace_id))
+                .await;
+        let request = actix_test::TestRequest::get()
+            .uri("/trace")
+            .insert_header(("traceparent", TRACEPARENT))
+            .to_request();
+        let body = actix_test::call_and_read_body(&app, request).await;
+
+        assert_eq!(body, "0af7651916cd43dd8448eb211c80319c");
+    }
+}
@@ -0,0 +1,291 @@
+use opentelemetry::{Context, KeyValue, Value};
+use opentelemetry_sdk::{
+    error::OTelSdkResult,
+    trace::{Span, SpanData, SpanProcessor},
+    Resource,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    io::Write,
+    sync::atomic::{AtomicU8, Ordering},
+    time::Duration,
+};
+use tracing_subscriber::{
+    field::MakeExt,
+    fmt::{
+        format::{self, Writer},
+        FormatFields, MakeWriter,
+    },
+};
+
+static POLICY: AtomicU8 = AtomicU8::new(Redaction::Mask as u8);
+
+// Personal details are only ever recorded under these field names, never
+// formatted into messages.
+const EMAIL_FIELDS: &[&str] = &["subscriber_email"];
+const NAME_FIELDS: &[&str] = &["subscriber_name"];
+
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Redaction {
+    None = 0,
+    Mask = 1,
+    Hash = 2,
+}
+
+impl Redaction {
+    pub fn install(self) {
+        POLICY.store(self as u8, Ordering::Relaxed);
+    }
+
+    pub fn current() -> Self {
+        match POLICY.load(Ordering::Relaxed) {
+            0 => Self::None,
+            2 => Self::Hash,
+            _ => Self::Mask,
+        }
+    }
+
+    pub fn email(self, email: &str) -> String {
+        match self {
+            Self::None => email.to_owned(),
+            Self::Mask => match email.rsplit_once('@') {
+                Some((local, domain)) => format!("{}@{}", mask(local), domain),
+                None => mask(email),
+            },
+            Self::Hash => hash(&email.to_lowercase()),
+        }
+    }
+
+    pub fn name(self, name: &str) -> String {
+        match self {
+            Self::None => name.to_owned(),
+            Self::Mask => mask(name),
+            Self::Hash => hash(name),
+        }
+    }
+}
+
+pub fn redact_email(email: &str) -> String {
+    Redaction::current().email(email)
+}
+
+pub fn redact_name(name: &str) -> String {
+    Redaction::current().name(name)
+}
+
+/// Redacts a log field holding a subscriber's email address or name. Every
+/// sink goes through this, so spans and events can record the raw values.
+pub fn redact_field(key: &str, value: &str) -> Option<String> {
+    let policy = Redaction::current();
+    if EMAIL_FIELDS.contains(&key) {
+        Some(policy.email(value))
+    } else if NAME_FIELDS.contains(&key) {
+        Some(policy.name(value))
+    } else {
+        None
+    }
+}
+
+/// What went wrong in a query, without the values Postgres quotes in its
+/// messages, such as the address of a duplicate subscriber.
+pub fn describe_db_error(e: &sqlx::Error) -> String {
+    match e {
+        sqlx::Error::Database(e) => match (e.code(), e.constraint()) {
+            (Some(code), Some(constraint)) => {
+                format!("database error {} on {}", code, constraint)
+            }
+            (Some(code), None) => format!("database error {}", code),
+            (None, _) => String::from("database error"),
+        },
+        sqlx::Error::ColumnDecode { index, .. } => format!("failed to decode column {}", index),
+        sqlx::Error::Decode(_) => String::from("failed to decode a value"),
+        e => e.to_string(),
+    }
+}
+
+/// Writes bunyan records with their personal fields redacted.
+pub struct RedactingWriter<M>(pub M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for RedactingWriter<M> {
+    type Writer = RedactedLines<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactedLines(self.0.make_writer())
+    }
+}
+
+pub struct RedactedLines<W>(W);
+
+impl<W: Write> Write for RedactedLines<W> {
+    // The bunyan layer writes each record, a JSON object and a newline, at once.
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let record = serde_json::from_slice::<serde_json::Map<String, serde_json::Value>>(buf);
+        let Ok(mut record) = record else {
+            self.0.write_all(buf)?;
+            return Ok(buf.len());
+        };
+        for (key, value) in record.iter_mut() {
+            if let Some(redacted) = value.as_str().and_then(|v| redact_field(key, v)) {
+                *value = serde_json::Value::String(redacted);
+            }
+        }
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        self.0.write_all(&line)?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Formats fields for the human readable log format, redacting the personal
+/// ones.
+pub fn redacting_fields() -> impl for<'w> FormatFields<'w> + Send + Sync + 'static {
+    format::debug_fn(|writer: &mut Writer<'_>, field, value| {
+        let rendered = format!("{:?}", value);
+        match field.name() {
+            "message" => write!(writer, "{}", rendered),
+            name => match redact_field(name, rendered.trim_matches('"')) {
+                Some(redacted) => write!(writer, "{}={}", name, redacted),
+                None => write!(writer, "{}={}", name, rendered),
+            },
+        }
+    })
+    .delimited(" ")
+}
+
+/// Redacts the personal attributes of spans before they're exported.
+#[derive(Debug)]
+pub struct RedactingProcessor<P>(pub P);
+
+impl<P: SpanProcessor> SpanProcessor for RedactingProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.0.on_start(span, cx);
+    }
+
+    fn on_end(&self, mut span: SpanData) {
+        redact_attributes(&mut span.attributes);
+        for event in span.events.events.iter_mut() {
+            redact_attributes(&mut event.attributes);
+        }
+        self.0.on_end(span);
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.0.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.0.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.0.set_resource(resource);
+    }
+}
+
+fn redact_attributes(attributes: &mut [KeyValue]) {
+    for attribute in attributes.iter_mut() {
+        let redacted = match &attribute.value {
+            Value::String(value) => redact_field(attribute.key.as_str(), value.as_str()),
+            _ => None,
+        };
+        if let Some(redacted) = redacted {
+            attribute.value = Value::from(redacted);
+        }
+    }
+}
+
+fn mask(value: &str) -> String {
+    let first: String = value.chars().take(1).collect();
+    format!("{}***", first)
+}
+
+// A truncated digest is enough to correlate log lines without exposing the value.
+fn hash(value: &str) -> String {
+    let digest = Sha256::digest(value.as_bytes());
+    let hex: String = digest
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("sha256:{}", hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::telemetry::{
+        describe_db_error, redact_field, redaction::redact_attributes, Redaction,
+    };
+    use opentelemetry::KeyValue;
+
+    #[test]
+    fn emails_are_masked_but_keep_their_domain() {
+        assert_eq!(
+            Redaction::Mask.email("ursula@example.com"),
+            "u***@example.com"
+        );
+        assert_eq!(Redaction::Mask.email("not-an-email"), "n***");
+        assert_eq!(Redaction::Mask.name("Ursula Le Guin"), "U***");
+    }
+
+    #[test]
+    fn hashes_are_stable_and_hide_the_value() {
+        let hashed = Redaction::Hash.email("Ursula@example.com");
+
+        assert_eq!(hashed, Redaction::Hash.email("ursula@example.com"));
+        assert!(hashed.starts_with("sha256:"));
+        assert!(!hashed.contains("ursula"));
+    }
+
+    #[test]
+    fn nothing_is_redacted_when_disabled() {
+        assert_eq!(
+            Redaction::None.email("ursula@example.com"),
+            "ursula@example.com"
+        );
+        assert_eq!(Redaction::None.name("Ursula"), "Ursula");
+    }
+
+    #[test]
+    fn fields_are_redacted_by_their_name() {
+        assert_eq!(
+            redact_field("subscriber_email", "ursula@example.com").as_deref(),
+            Some("u***@example.com")
+        );
+        assert_eq!(
+            redact_field("subscriber_name", "Ursula").as_deref(),
+            Some("U***")
+        );
+        assert_eq!(redact_field("list", "weekly"), None);
+        assert_eq!(redact_field("list_name", "Weekly digest"), None);
+        assert_eq!(redact_field("otel.name", "HTTP request"), None);
+    }
+
+    #[test]
+    fn exported_span_attributes_are_redacted() {
+        let mut attributes = vec![
+            KeyValue::new("subscriber_email", "ursula@example.com"),
+            KeyValue::new("http.route", "/subscribe"),
+        ];
+        redact_attributes(&mut attributes);
+
+        assert_eq!(attributes[0].value.as_str(), "u***@example.com");
+        assert_eq!(attributes[1].value.as_str(), "/subscribe");
+    }
+
+    #[test]
+    fn database_errors_are_described_without_their_message() {
+        let decode = sqlx::Error::Decode("ursula@example.com".into());
+
+        assert_eq!(describe_db_error(&decode), "failed to decode a value");
+        assert_eq!(
+            describe_db_error(&sqlx::Error::RowNotFound),
+            sqlx::Error::RowNotFound.to_string()
+        );
+    }
+}
@@ -0,0 +1,425 @@
+use crate::config::WebhookSettings;
+use crate::telemetry::describe_db_error;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use reqwest::Url;
+use sha2::Sha256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const DELIVERY_BATCH_SIZE: i64 = 50;
+// Caps the exponential backoff at 2^10 times the base delay.
+const MAX_BACKOFF_EXPONENT: u32 = 10;
+
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema, Debug, Clone, Copy, PartialEq)]
+pub enum WebhookEvent {
+    #[serde(rename = "subscriber.subscribed")]
+    Subscribed,
+    #[serde(rename = "subscriber.confirmed")]
+    Confirmed,
+    #[serde(rename = "subscriber.unsubscribed")]
+    Unsubscribed,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Subscribed => "subscriber.subscribed",
+            Self::Confirmed => "subscriber.confirmed",
+            Self::Unsubscribed => "subscriber.unsubscribed",
+        }
+    }
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema, Debug)]
+pub struct WebhookEndpoint {
+    pub endpoint_id: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema, Debug)]
+pub struct WebhookDelivery {
+    pub delivery_id: Uuid,
+    pub event: String,
+    pub status: String,
+    pub attempts: i32,
+    pub last_response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// Queues `event` for every active endpoint of the publication listening to
+/// it. Meant to run in
+/// the transaction that caused the event, so events are never lost or
+/// announced for changes that were rolled back.
+#[tracing::instrument(name = "Enqueueing webhook deliveries", skip(executor, data))]
+pub async fn enqueue<'c>(
+    executor: impl sqlx::PgExecutor<'c>,
+    publication_id: &str,
+    event: WebhookEvent,
+    data: serde_json::Value,
+) -> Result<u64, sqlx::Error> {
+    let now = Utc::now();
+    let payload = serde_json::json!({
+        "id": Uuid::new_v4(),
+        "type": event.as_str(),
+        "publication": publication_id,
+        "created_at": now,
+        "data": data,
+    });
+    let result = sqlx::query!(
+        r#"INSERT INTO webhook_deliveries
+            (delivery_id, endpoint_id, event, payload, status, next_attempt_at, created_at)
+        SELECT gen_random_uuid(), endpoint_id, $1, $2, 'pending', $3, $3
+        FROM webhook_endpoints
+        WHERE active AND $1 = ANY(events) AND publication_id = $4"#,
+        event.as_str(),
+        payload,
+        now,
+        publication_id
+    )
+    .execute(executor)
+    .await
+    .inspect_err(|e| {
+        tracing::error!(
+            error = %describe_db_error(e),
+            "Failed to enqueue webhook deliveries"
+        )
+    })?;
+
+    Ok(result.rows_affected())
+}
+
+/// Registers an endpoint and returns it together with its signing secret,
+/// which is not retrievable afterwards.
+#[tracing::instrument(name = "Creating a webhook endpoint", skip(connection))]
+pub async fn create_endpoint(
+    connection: &PgPool,
+    publication_id: &str,
+    url: &str,
+    events: &[WebhookEvent],
+) -> Result<(WebhookEndpoint, String), String> {
+    match Url::parse(url) {
+        Ok(parsed) if ["http", "https"].contains(&parsed.scheme()) => {}
+        _ => return Err(format!("{} is not an http(s) url", url)),
+    }
+    if events.is_empty() {
+        return Err(String::from("At least one event is required"));
+    }
+
+    let secret: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+    let mut events: Vec<String> = events.iter().map(|e| e.as_str().to_owned()).collect();
+    events.sort();
+    events.dedup();
+    let endpoint = WebhookEndpoint {
+        endpoint_id: Uuid::new_v4(),
+        url: url.to_owned(),
+        events,
+        active: true,
+        created_at: Utc::now(),
+    };
+    sqlx::query!(
+        r#"INSERT INTO webhook_endpoints
+            (endpoint_id, publication_id, url, secret, events, active, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+        endpoint.endpoint_id,
+        publication_id,
+        endpoint.url,
+        secret,
+        &endpoint.events,
+        endpoint.active,
+        endpoint.created_at
+    )
+    .execute(connection)
+    .await
+    .map_err(|e| format!("Failed to create webhook endpoint: {}", e))?;
+
+    Ok((endpoint, secret))
+}
+
+#[tracing::instrument(name = "Listing webhook endpoints", skip(connection))]
+pub async fn list_endpoints(
+    connection: &PgPool,
+    publication_id: &str,
+) -> Result<Vec<WebhookEndpoint>, sqlx::Error> {
+    sqlx::query_as!(
+        WebhookEndpoint,
+        r#"SELECT endpoint_id, url, events, active, created_at
+        FROM webhook_endpoints WHERE publication_id = $1 ORDER BY created_at"#,
+        publication_id
+    )
+    .fetch_all(connection)
+    .await
+}
+
+/// Stops deliveries to an endpoint. Its delivery log is kept.
+#[tracing::instrument(name = "Deactivating a webhook endpoint", skip(connection))]
+pub async fn deactivate_endpoint(
+    connection: &PgPool,
+    publication_id: &str,
+    endpoint_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE webhook_endpoints SET active = FALSE
+        WHERE endpoint_id = $1 AND publication_id = $2"#,
+        endpoint_id,
+        publication_id
+    )
+    .execute(connection)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[tracing::instrument(name = "Listing webhook deliveries", skip(connection))]
+pub async fn list_deliveries(
+    connection: &PgPool,
+    publication_id: &str,
+    endpoint_id: Uuid,
+    limit: i64,
+) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+    sqlx::query_as!(
+        WebhookDelivery,
+        r#"SELECT d.delivery_id, d.event, d.status, d.attempts, d.last_response_status,
+            d.last_error, d.created_at, d.next_attempt_at, d.delivered_at
+        FROM webhook_deliveries d
+        JOIN webhook_endpoints e ON e.endpoint_id = d.endpoint_id
+        WHERE d.endpoint_id = $1 AND e.publication_id = $2
+        ORDER BY d.created_at DESC
+        LIMIT $3"#,
+        endpoint_id,
+        publication_id,
+        limit
+    )
+    .fetch_all(connection)
+    .await
+}
+
+/// Queues a fresh copy of a delivery, keeping the original in the log.
+#[tracing::instrument(name = "Replaying a webhook delivery", skip(connection))]
+pub async fn replay_delivery(
+    connection: &PgPool,
+    publication_id: &str,
+    delivery_id: Uuid,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let replay_id = Uuid::new_v4();
+    let result = sqlx::query!(
+        r#"INSERT INTO webhook_deliveries
+            (delivery_id, endpoint_id, event, payload, status, next_attempt_at, created_at)
+        SELECT $1, d.endpoint_id, d.event, d.payload, 'pending', $3, $3
+        FROM webhook_deliveries d
+        JOIN webhook_endpoints e ON e.endpoint_id = d.endpoint_id
+        WHERE d.delivery_id = $2 AND e.publication_id = $4"#,
+        replay_id,
+        delivery_id,
+        Utc::now(),
+        publication_id
+    )
+    .execute(connection)
+    .await?;
+
+    Ok((result.rows_affected() > 0).then_some(replay_id))
+}
+
+pub async fn pending_deliveries(connection: &PgPool) -> Result<i64, sqlx::Error> {
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM webhook_deliveries WHERE status = 'pending'"#
+    )
+    .fetch_one(connection)
+    .await?;
+
+    Ok(count)
+}
+
+/// Attempts every due delivery once. Deliveries are claimed, counting the
+/// attempt, before anything is sent, so several workers can share the queue
+/// without holding locks or a connection while waiting on endpoints. Claims
+/// of a worker that stops mid-batch lapse and the deliveries are retried.
+#[tracing::instrument(name = "Delivering webhooks", skip_all)]
+pub async fn deliver_due(
+    connection: &PgPool,
+    client: &reqwest::Client,
+    settings: &WebhookSettings,
+) -> Result<usize, sqlx::Error> {
+    let now = Utc::now();
+    // Long enough for every request of the batch to time out.
+    let claimed_until = now
+        + chrono::Duration::milliseconds(
+            i64::try_from(settings.timeout_ms)
+                .unwrap_or(i64::MAX)
+                .saturating_mul(DELIVERY_BATCH_SIZE),
+        );
+    let due = sqlx::query!(
+        r#"UPDATE webhook_deliveries d SET attempts = d.attempts + 1, next_attempt_at = $3
+        FROM webhook_endpoints e
+        WHERE e.endpoint_id = d.endpoint_id AND d.delivery_id IN (
+            SELECT d.delivery_id FROM webhook_deliveries d
+            JOIN webhook_endpoints e ON e.endpoint_id = d.endpoint_id
+            WHERE d.status = 'pending' AND e.active AND d.next_attempt_at <= $1
+            ORDER BY d.next_attempt_at
+            LIMIT $2
+            FOR UPDATE OF d SKIP LOCKED
+        )
+        RETURNING d.delivery_id, d.event, d.payload, d.attempts, e.url, e.secret"#,
+        now,
+        DELIVERY_BATCH_SIZE,
+        claimed_until
+    )
+    .fetch_all(connection)
+    .await?;
+
+    for delivery in &due {
+        let body = delivery.payload.to_string();
+        let result = send(
+            client,
+            &delivery.url,
+            &delivery.secret,
+            delivery.delivery_id,
+            &delivery.event,
+            body,
+        )
+        .await;
+        let attempts = delivery.attempts;
+        let now = Utc::now();
+        match result {
+            Ok(status) => {
+                sqlx::query!(
+                    r#"UPDATE webhook_deliveries
+                    SET status = 'delivered', last_response_status = $2, last_error = NULL,
+                        delivered_at = $3
+                    WHERE delivery_id = $1"#,
+                    delivery.delivery_id,
+                    status,
+                    now
+                )
+                .execute(connection)
+                .await?;
+            }
+            Err((status, error)) => {
+                tracing::warn!(
+                    delivery_id = %delivery.delivery_id,
+                    attempts,
+                    error = %error,
+                    "Webhook delivery failed"
+                );
+                let gave_up = attempts >= settings.max_attempts as i32;
+                let next_attempt_at = now + retry_delay(settings, attempts as u32);
+                sqlx::query!(
+                    r#"UPDATE webhook_deliveries
+                    SET status = $2, last_response_status = $3, last_error = $4,
+                        next_attempt_at = $5
+                    WHERE delivery_id = $1"#,
+                    delivery.delivery_id,
+                    if gave_up { "failed" } else { "pending" },
+                    status,
+                    error,
+                    next_attempt_at
+                )
+                .execute(connection)
+                .await?;
+            }
+        }
+    }
+
+    Ok(due.len())
+}
+
+fn retry_delay(settings: &WebhookSettings, attempts: u32) -> chrono::Duration {
+    let exponent = attempts.saturating_sub(1).min(MAX_BACKOFF_EXPONENT);
+    let backoff = i64::try_from(settings.retry_backoff_ms).unwrap_or(i64::MAX);
+    chrono::Duration::milliseconds(backoff.saturating_mul(1 << exponent))
+}
+
+async fn send(
+    client: &reqwest::Client,
+    url: &str,
+    secret: &str,
+    delivery_id: Uuid,
+    event: &str,
+    body: String,
+) -> Result<i32, (Option<i32>, String)> {
+    let timestamp = Utc::now().timestamp();
+    let signature = sign(secret, timestamp, &body);
+    let response = client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header("X-Newsletter-Event", event)
+        .header("X-Newsletter-Delivery", delivery_id.to_string())
+        .header("X-Newsletter-Timestamp", timestamp.to_string())
+        .header("X-Newsletter-Signature", signature)
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| (None, e.to_string()))?;
+
+    let status = i32::from(response.status().as_u16());
+    if response.status().is_success() {
+        Ok(status)
+    } else {
+        Err((Some(status), format!("endpoint responded with {}", status)))
+    }
+}
+
+/// `sha256=` followed by the hex HMAC of `{timestamp}.{body}`. Receivers should
+/// reject stale timestamps to prevent replays.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::WebhookSettings;
+    use crate::webhooks::{retry_delay, sign};
+
+    #[test]
+    fn signatures_cover_the_timestamp_and_body() {
+        let signature = sign("secret", 1700000000, r#"{"type":"subscriber.confirmed"}"#);
+
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_ne!(
+            signature,
+            sign("secret", 1700000001, r#"{"type":"subscriber.confirmed"}"#)
+        );
+        assert_ne!(
+            signature,
+            sign("other", 1700000000, r#"{"type":"subscriber.confirmed"}"#)
+        );
+    }
+
+    #[test]
+    fn retries_back_off_exponentially() {
+        let settings = WebhookSettings {
+            poll_interval_ms: 1000,
+            timeout_ms: 1000,
+            max_attempts: 5,
+            retry_backoff_ms: 1000,
+        };
+
+        let delays: Vec<i64> = (1..=4)
+            .map(|attempts| retry_delay(&settings, attempts).num_seconds())
+            .collect();
+
+        assert_eq!(delays, [1, 2, 4, 8]);
+        assert_eq!(retry_delay(&settings, 40).num_seconds(), 1024);
+    }
+}
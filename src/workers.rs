@@ -0,0 +1,150 @@
+use crate::config::WebhookSettings;
+use crate::metrics::Metrics;
+use crate::shutdown::ShutdownHandle;
+use crate::telemetry::describe_db_error;
+use crate::webhooks;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+const POOL_MONITOR_INTERVAL: Duration = Duration::from_secs(10);
+
+pub struct Workers {
+    handles: Vec<JoinHandle<()>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Heartbeats(Arc<Mutex<HashMap<String, Instant>>>);
+
+impl Heartbeats {
+    pub fn beat(&self, worker: &str) {
+        self.0
+            .lock()
+            .expect("Heartbeats lock is poisoned")
+            .insert(worker.to_owned(), Instant::now());
+    }
+
+    pub fn since_last_beat(&self) -> Vec<(String, Duration)> {
+        let mut beats: Vec<(String, Duration)> = self
+            .0
+            .lock()
+            .expect("Heartbeats lock is poisoned")
+            .iter()
+            .map(|(worker, last)| (worker.clone(), last.elapsed()))
+            .collect();
+        beats.sort_by(|a, b| a.0.cmp(&b.0));
+
+        beats
+    }
+}
+
+impl Workers {
+    pub fn spawn(
+        pool: &PgPool,
+        webhook_settings: &WebhookSettings,
+        metrics: &Metrics,
+        heartbeats: &Heartbeats,
+        shutdown: &ShutdownHandle,
+    ) -> Self {
+        let handles = vec![
+            tokio::spawn(monitor_pool(pool.clone(), shutdown.clone())),
+            tokio::spawn(deliver_webhooks(
+                pool.clone(),
+                webhook_settings.clone(),
+                metrics.clone(),
+                heartbeats.clone(),
+                shutdown.clone(),
+            )),
+        ];
+
+        Self { handles }
+    }
+
+    /// Waits for workers that were told to stop, giving up after `deadline`.
+    pub async fn wait(self, deadline: Duration) {
+        let workers = async {
+            for handle in self.handles {
+                let _ = handle.await;
+            }
+        };
+        if tokio::time::timeout(deadline, workers).await.is_err() {
+            tracing::warn!(
+                "Background workers did not stop within {:?}, abandoning them",
+                deadline
+            );
+        }
+    }
+}
+
+async fn monitor_pool(pool: PgPool, shutdown: ShutdownHandle) {
+    let max_connections = pool.options().get_max_connections();
+    let mut interval = tokio::time::interval(POOL_MONITOR_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.triggered() => return,
+        }
+        let size = pool.size();
+        let idle = pool.num_idle();
+        if size >= max_connections && idle == 0 {
+            tracing::warn!(size, idle, max_connections, "Database pool is saturated");
+        } else {
+            tracing::debug!(size, idle, max_connections, "Database pool usage");
+        }
+    }
+}
+
+async fn deliver_webhooks(
+    pool: PgPool,
+    settings: WebhookSettings,
+    metrics: Metrics,
+    heartbeats: Heartbeats,
+    shutdown: ShutdownHandle,
+) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_millis(settings.timeout_ms))
+        .build()
+        .expect("Failed to build the webhook client");
+    let mut interval = tokio::time::interval(Duration::from_millis(settings.poll_interval_ms));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.triggered() => return,
+        }
+        if let Err(e) = webhooks::deliver_due(&pool, &client, &settings).await {
+            tracing::error!(error = %describe_db_error(&e), "Failed to deliver webhooks");
+            continue;
+        }
+        match webhooks::pending_deliveries(&pool).await {
+            Ok(depth) => metrics.set_queue_depth("webhooks", depth),
+            Err(e) => tracing::error!(
+                error = %describe_db_error(&e),
+                "Failed to count pending webhooks"
+            ),
+        }
+        heartbeats.beat("webhooks");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::workers::Heartbeats;
+
+    #[test]
+    fn beats_are_recorded_per_worker() {
+        let heartbeats = Heartbeats::default();
+        heartbeats.beat("b");
+        heartbeats.beat("a");
+        heartbeats.beat("a");
+
+        let workers: Vec<String> = heartbeats
+            .since_last_beat()
+            .into_iter()
+            .map(|(worker, _)| worker)
+            .collect();
+
+        assert_eq!(workers, ["a", "b"]);
+    }
+}
@@ -0,0 +1,49 @@
+use crate::helpers::{app, app_with_config};
+
+#[tokio::test]
+async fn openapi_document_describes_the_subscription_routes() {
+    let app = app().await;
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/api/openapi.json", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let doc: serde_json::Value = response.json().await.expect("Failed to parse document");
+    assert!(doc["openapi"].as_str().unwrap().starts_with("3."));
+    assert!(doc["paths"]["/subscribe"]["post"]["requestBody"]["content"]
+        ["application/x-www-form-urlencoded"]
+        .is_object());
+    assert!(doc["components"]["schemas"]["SubscribeFormData"].is_object());
+    let parameters = doc["paths"]["/subscriptions/confirm"]["get"]["parameters"]
+        .as_array()
+        .unwrap();
+    assert!(parameters
+        .iter()
+        .any(|p| p["name"] == "subscription_token" && p["in"] == "query"));
+}
+
+#[tokio::test]
+async fn swagger_ui_is_only_served_when_enabled() {
+    let client = reqwest::Client::new();
+    let disabled = app().await;
+    let enabled = app_with_config(|c| c.api_docs.swagger_ui = true).await;
+
+    let response = client
+        .get(format!("{}/api/docs", disabled.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = client
+        .get(format!("{}/api/docs", enabled.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("/api/openapi.json"));
+}
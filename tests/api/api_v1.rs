@@ -0,0 +1,232 @@
+use reqwest::Url;
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use newsletter::api_keys::ApiKeyScope;
+
+use crate::helpers::{app, app_with_config};
+
+#[tokio::test]
+async fn subscribing_and_confirming_through_the_api_uses_json() {
+    let client = reqwest::Client::new();
+    let app = app().await;
+    let key = app.create_api_key(&[ApiKeyScope::Subscribe]).await;
+
+    Mock::given(path("mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = client
+        .post(format!("{}/api/v1/subscriptions", app.address))
+        .bearer_auth(&key)
+        .json(&json!({"name": "le guin", "email": "ursula_le_guin@gmail.com"}))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(response.status().as_u16(), 201);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "pending_confirmation");
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let link = Url::parse(&app.get_confirmation_link(email_request)).unwrap();
+    let (_, token) = link
+        .query_pairs()
+        .find(|(key, _)| key == "subscription_token")
+        .unwrap();
+
+    let response = client
+        .post(format!("{}/api/v1/subscriptions/confirm", app.address))
+        .bearer_auth(&key)
+        .json(&json!({ "subscription_token": token }))
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "confirmed");
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn api_errors_are_returned_as_json() {
+    let client = reqwest::Client::new();
+    let app = app().await;
+    let key = app.create_api_key(&[ApiKeyScope::Subscribe]).await;
+
+    let cases = [
+        (json!({"name": "le guin"}), 400),
+        (json!({"name": "le guin", "email": "not-an-email"}), 400),
+    ];
+    for (body, status) in cases {
+        let response = client
+            .post(format!("{}/api/v1/subscriptions", app.address))
+            .bearer_auth(&key)
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to send request");
+
+        assert_eq!(response.status().as_u16(), status, "{}", body);
+        let body: Value = response.json().await.unwrap();
+        assert!(body["error"].is_string());
+    }
+
+    let response = client
+        .post(format!("{}/api/v1/subscriptions/confirm", app.address))
+        .bearer_auth(&key)
+        .json(&json!({"subscription_token": "unknown"}))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status().as_u16(), 401);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "Unknown token");
+}
+
+#[tokio::test]
+async fn deprecated_api_versions_announce_their_sunset() {
+    let client = reqwest::Client::new();
+    let current = app().await;
+    let deprecated = app_with_config(|c| {
+        c.api.v1_deprecated_at = Some("2027-01-01T00:00:00Z".parse().unwrap());
+        c.api.v1_sunset = Some("2027-07-01T00:00:00Z".parse().unwrap());
+    })
+    .await;
+
+    let response = client
+        .post(format!("{}/api/v1/subscriptions/confirm", current.address))
+        .json(&json!({"subscription_token": "unknown"}))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert!(response.headers().get("Deprecation").is_none());
+
+    let response = client
+        .post(format!(
+            "{}/api/v1/subscriptions/confirm",
+            deprecated.address
+        ))
+        .json(&json!({"subscription_token": "unknown"}))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.headers()["Deprecation"], "@1798761600");
+    assert_eq!(
+        response.headers()["Sunset"],
+        "Thu, 01 Jul 2027 00:00:00 GMT"
+    );
+
+    // The form endpoints are not versioned and never deprecated this way.
+    let response = client
+        .get(format!("{}/subscriptions/confirm", deprecated.address))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert!(response.headers().get("Deprecation").is_none());
+}
+
+#[tokio::test]
+async fn api_keys_are_required_and_scoped() {
+    let client = reqwest::Client::new();
+    let app = app().await;
+    let subscribe_only = app.create_api_key(&[ApiKeyScope::Subscribe]).await;
+    let admin = app.create_api_key(&[ApiKeyScope::Admin]).await;
+    let url = format!("{}/api/v1/subscribers", app.address);
+
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+
+    let response = client
+        .get(&url)
+        .bearer_auth("nl_unknown")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = client
+        .get(&url)
+        .bearer_auth(&subscribe_only)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = client.get(&url).bearer_auth(&admin).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body, json!([]));
+
+    let last_used = sqlx::query!("SELECT last_used_at FROM api_keys ORDER BY created_at")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(last_used.iter().all(|k| k.last_used_at.is_some()));
+}
+
+#[tokio::test]
+async fn keys_are_shown_once_and_can_be_revoked() {
+    let client = reqwest::Client::new();
+    let app = app().await;
+    let admin = app.create_admin().await;
+
+    let response = client
+        .post(format!("{}/admin/api-keys", app.address))
+        .basic_auth(&admin.username, Some(&admin.password))
+        .json(&json!({"name": "partner", "scopes": ["read"], "rate_limit_per_minute": 2}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    let created: Value = response.json().await.unwrap();
+    let key = created["key"].as_str().unwrap().to_owned();
+    assert!(key.starts_with(created["prefix"].as_str().unwrap()));
+
+    let listed: Value = client
+        .get(format!("{}/admin/api-keys", app.address))
+        .basic_auth(&admin.username, Some(&admin.password))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed[0]["key_id"], created["key_id"]);
+    assert!(listed[0].get("key").is_none());
+
+    let url = format!("{}/api/v1/subscribers", app.address);
+    for _ in 0..2 {
+        let response = client.get(&url).bearer_auth(&key).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    let response = client.get(&url).bearer_auth(&key).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key("Retry-After"));
+
+    let response = client
+        .delete(format!(
+            "{}/admin/api-keys/{}",
+            app.address,
+            created["key_id"].as_str().unwrap()
+        ))
+        .basic_auth(&admin.username, Some(&admin.password))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 204);
+    let response = client.get(&url).bearer_auth(&key).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
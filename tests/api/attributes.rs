@@ -0,0 +1,292 @@
+use newsletter::api_keys::ApiKeyScope;
+use newsletter::cli::{import_subscribers, ImportSummary, SubscriberStatus};
+use newsletter::publications::DEFAULT_PUBLICATION;
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{app, TestAdmin, TestApp};
+
+async fn define(app: &TestApp, admin: &TestAdmin, definition: Value) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/admin/attributes", app.address))
+        .basic_auth(&admin.username, Some(&admin.password))
+        .json(&definition)
+        .send()
+        .await
+        .expect("Failed to send request")
+}
+
+/// A required `country` limited to CZ and DE, and an optional `seats` number.
+async fn app_with_attributes() -> (TestApp, TestAdmin) {
+    let app = app().await;
+    let admin = app.create_admin().await;
+    Mock::given(path("mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let country = json!({"name": "country", "type": "text", "required": true, "allowed_values": ["CZ", "DE"]});
+    assert_eq!(define(&app, &admin, country).await.status().as_u16(), 201);
+    let seats = json!({"name": "seats", "type": "number"});
+    assert_eq!(define(&app, &admin, seats).await.status().as_u16(), 201);
+
+    (app, admin)
+}
+
+async fn saved_attributes(app: &TestApp) -> Vec<Value> {
+    sqlx::query!("SELECT attributes FROM subscriptions ORDER BY email")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.attributes)
+        .collect()
+}
+
+#[tokio::test]
+async fn invalid_definitions_are_rejected() {
+    let app = app().await;
+    let admin = app.create_admin().await;
+
+    for definition in [
+        json!({"name": "Country", "type": "text"}),
+        json!({"name": "seats", "type": "integer"}),
+        json!({"name": "seats", "type": "number", "allowed_values": ["1"]}),
+    ] {
+        let response = define(&app, &admin, definition.clone()).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "{} was accepted",
+            definition
+        );
+    }
+    let first = define(&app, &admin, json!({"name": "plan", "type": "text"})).await;
+    let again = define(&app, &admin, json!({"name": "plan", "type": "text"})).await;
+    assert_eq!(first.status().as_u16(), 201);
+    assert_eq!(again.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn form_attributes_are_checked_and_stored_with_their_type() {
+    let (app, _) = app_with_attributes().await;
+    let client = reqwest::Client::new();
+
+    let invalid = client
+        .post(format!("{}/subscribe", app.address))
+        .form(&[
+            ("name", "le guin"),
+            ("email", "ursula_le_guin@gmail.com"),
+            ("attributes[country]", "US"),
+        ])
+        .send()
+        .await
+        .unwrap();
+    let valid = client
+        .post(format!("{}/subscribe", app.address))
+        .form(&[
+            ("name", "le guin"),
+            ("email", "ursula_le_guin@gmail.com"),
+            ("attributes[country]", "DE"),
+            ("attributes[seats]", "12"),
+        ])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(invalid.status().as_u16(), 400);
+    assert_eq!(valid.status().as_u16(), 200);
+    assert_eq!(
+        saved_attributes(&app).await,
+        vec![json!({"country": "DE", "seats": 12})]
+    );
+}
+
+#[tokio::test]
+async fn api_clients_are_told_what_is_wrong_with_their_attributes() {
+    let (app, _) = app_with_attributes().await;
+    let key = app.create_api_key(&[ApiKeyScope::Subscribe]).await;
+    let client = reqwest::Client::new();
+
+    let missing = client
+        .post(format!("{}/api/v1/subscriptions", app.address))
+        .bearer_auth(&key)
+        .json(&json!({"name": "le guin", "email": "ursula_le_guin@gmail.com"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status().as_u16(), 400);
+    let body: Value = missing.json().await.unwrap();
+    assert_eq!(body["error"], "country is required");
+
+    let created = client
+        .post(format!("{}/api/v1/subscriptions", app.address))
+        .bearer_auth(&key)
+        .json(&json!({
+            "name": "le guin",
+            "email": "ursula_le_guin@gmail.com",
+            "attributes": {"country": "CZ", "seats": 3}
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(created.status().as_u16(), 201);
+    assert_eq!(
+        saved_attributes(&app).await,
+        vec![json!({"country": "CZ", "seats": 3})]
+    );
+}
+
+#[tokio::test]
+async fn imported_rows_with_invalid_attributes_are_skipped() {
+    let (app, _) = app_with_attributes().await;
+    let csv = "email,name,country,seats
+ursula@example.com,Ursula Le Guin,DE,4
+octavia@example.com,Octavia Butler,US,
+samuel@example.com,Samuel Delany,CZ,
+";
+
+    let summary = import_subscribers(
+        &app.db_pool,
+        DEFAULT_PUBLICATION,
+        csv.as_bytes(),
+        SubscriberStatus::Confirmed,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        summary,
+        ImportSummary {
+            imported: 2,
+            duplicates: 0,
+            errors: vec![(3, String::from("country must be one of CZ, DE"))],
+        }
+    );
+    assert_eq!(
+        saved_attributes(&app).await,
+        vec![
+            json!({"country": "CZ"}),
+            json!({"country": "DE", "seats": 4})
+        ]
+    );
+}
+
+#[tokio::test]
+async fn admins_can_filter_subscribers_by_attribute() {
+    let (app, admin) = app_with_attributes().await;
+    let csv = "email,name,country,seats
+ursula@example.com,Ursula Le Guin,DE,4
+octavia@example.com,Octavia Butler,DE,2
+samuel@example.com,Samuel Delany,CZ,4
+";
+    import_subscribers(
+        &app.db_pool,
+        DEFAULT_PUBLICATION,
+        csv.as_bytes(),
+        SubscriberStatus::Confirmed,
+    )
+    .await
+    .unwrap();
+    let client = reqwest::Client::new();
+
+    let list = |query: &'static str| {
+        client
+            .get(format!("{}/admin/subscribers?{}", app.address, query))
+            .basic_auth(&admin.username, Some(&admin.password))
+            .send()
+    };
+    let emails = |body: Value| -> Vec<String> {
+        let mut emails: Vec<_> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["email"].as_str().unwrap().to_owned())
+            .collect();
+        emails.sort();
+        emails
+    };
+
+    let germans = list("attributes[country]=DE").await.unwrap();
+    let german_teams = list("attributes%5Bcountry%5D=DE&attributes%5Bseats%5D=4")
+        .await
+        .unwrap();
+    let unknown = list("attributes[plan]=pro").await.unwrap();
+
+    assert_eq!(
+        emails(germans.json().await.unwrap()),
+        vec!["octavia@example.com", "ursula@example.com"]
+    );
+    assert_eq!(
+        emails(german_teams.json().await.unwrap()),
+        vec!["ursula@example.com"]
+    );
+    assert_eq!(unknown.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn signing_up_again_only_changes_attributes_once_confirmed() {
+    let (app, admin) = app_with_attributes().await;
+    let client = reqwest::Client::new();
+    let weekly = client
+        .post(format!("{}/admin/lists", app.address))
+        .basic_auth(&admin.username, Some(&admin.password))
+        .json(&json!({"slug": "weekly", "name": "Weekly digest"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(weekly.status().as_u16(), 201);
+    let subscribe = |list: &'static str, country: &'static str, seats: &'static str| {
+        client
+            .post(format!("{}/subscribe", app.address))
+            .form(&[
+                ("name", "le guin"),
+                ("email", "ursula_le_guin@gmail.com"),
+                ("list", list),
+                ("attributes[country]", country),
+                ("attributes[seats]", seats),
+            ])
+            .send()
+    };
+
+    assert_eq!(
+        subscribe("newsletter", "DE", "12")
+            .await
+            .unwrap()
+            .status()
+            .as_u16(),
+        200
+    );
+    let requests = app.email_server.received_requests().await.unwrap();
+    app.confirm_subscription(&app.get_confirmation_link(&requests[0]))
+        .await;
+    assert_eq!(
+        subscribe("weekly", "CZ", "1")
+            .await
+            .unwrap()
+            .status()
+            .as_u16(),
+        200
+    );
+
+    assert_eq!(
+        saved_attributes(&app).await,
+        vec![json!({"country": "DE", "seats": 12})]
+    );
+    let status = sqlx::query_scalar!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "confirmed");
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    app.confirm_subscription(&app.get_confirmation_link(&requests[1]))
+        .await;
+    assert_eq!(
+        saved_attributes(&app).await,
+        vec![json!({"country": "CZ", "seats": 1})]
+    );
+}
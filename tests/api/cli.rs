@@ -0,0 +1,118 @@
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use newsletter::authentication::create_user;
+use newsletter::cli::{export_subscribers, import_subscribers, ImportSummary, SubscriberStatus};
+use newsletter::publications::DEFAULT_PUBLICATION;
+use secrecy::Secret;
+
+use crate::helpers::app;
+
+const CSV: &str = "email,name,locale
+ursula@example.com,Ursula Le Guin,de
+not-an-email,Broken,
+octavia@example.com,Octavia Butler,
+ursula@example.com,Ursula Again,en
+";
+
+#[tokio::test]
+async fn import_skips_invalid_rows_and_duplicates() {
+    let app = app().await;
+
+    let summary = import_subscribers(
+        &app.db_pool,
+        DEFAULT_PUBLICATION,
+        CSV.as_bytes(),
+        SubscriberStatus::Confirmed,
+    )
+    .await
+    .expect("Failed to import");
+
+    assert_eq!(
+        summary,
+        ImportSummary {
+            imported: 2,
+            duplicates: 1,
+            errors: vec![(3, String::from("Not a valid email address"))],
+        }
+    );
+    let saved = sqlx::query!(
+        "SELECT name, locale, status FROM subscriptions WHERE email = 'ursula@example.com'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.name, "Ursula Le Guin");
+    assert_eq!(saved.locale, "de");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn exported_subscribers_can_be_filtered_by_status() {
+    let app = app().await;
+    import_subscribers(
+        &app.db_pool,
+        DEFAULT_PUBLICATION,
+        CSV.as_bytes(),
+        SubscriberStatus::PendingConfirmation,
+    )
+    .await
+    .unwrap();
+
+    let mut output = vec![];
+    let exported = export_subscribers(
+        &app.db_pool,
+        DEFAULT_PUBLICATION,
+        &mut output,
+        Some(SubscriberStatus::Confirmed),
+    )
+    .await
+    .unwrap();
+    assert_eq!(exported, 0);
+
+    let mut output = vec![];
+    let exported = export_subscribers(&app.db_pool, DEFAULT_PUBLICATION, &mut output, None)
+        .await
+        .unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(exported, 2);
+    assert!(output.starts_with("email,name,locale,status,subscribed_at,tags\n"));
+    assert!(output.contains("octavia@example.com,Octavia Butler,en,pending_confirmation,"));
+}
+
+#[tokio::test]
+async fn admins_are_stored_with_a_password_hash() {
+    let app = app().await;
+    let password = Secret::new(String::from("correct horse battery staple"));
+
+    create_user(&app.db_pool, "admin", &password, &[DEFAULT_PUBLICATION])
+        .await
+        .expect("Failed to create admin");
+
+    let saved = sqlx::query!("SELECT password_hash FROM users WHERE username = 'admin'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let hash = PasswordHash::new(&saved.password_hash).unwrap();
+    assert!(Argon2::default()
+        .verify_password(b"correct horse battery staple", &hash)
+        .is_ok());
+    assert!(
+        create_user(&app.db_pool, "admin", &password, &[DEFAULT_PUBLICATION])
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn short_admin_passwords_are_rejected() {
+    let app = app().await;
+
+    let result = create_user(
+        &app.db_pool,
+        "admin",
+        &Secret::new(String::from("hunter2")),
+        &[DEFAULT_PUBLICATION],
+    )
+    .await;
+
+    assert!(result.is_err());
+}
@@ -0,0 +1,74 @@
+use crate::helpers::{app_with_config, TestApp};
+
+const ORIGIN: &str = "https://www.example.com";
+
+async fn app() -> TestApp {
+    app_with_config(|c| c.cors.allowed_origins = vec![ORIGIN.to_owned()]).await
+}
+
+async fn preflight(app: &TestApp, path: &str, origin: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .request(reqwest::Method::OPTIONS, format!("{}{}", app.address, path))
+        .header("Origin", origin)
+        .header("Access-Control-Request-Method", "POST")
+        .header("Access-Control-Request-Headers", "content-type")
+        .send()
+        .await
+        .expect("Failed to send request")
+}
+
+#[tokio::test]
+async fn embedded_forms_may_post_to_subscribe() {
+    let app = app().await;
+
+    let response = preflight(&app, "/subscribe", ORIGIN).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Access-Control-Allow-Origin"], ORIGIN);
+    assert_eq!(response.headers()["Access-Control-Max-Age"], "3600");
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/subscribe", app.address))
+        .header("Origin", ORIGIN)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=le%20guin")
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(response.headers()["Access-Control-Allow-Origin"], ORIGIN);
+}
+
+#[tokio::test]
+async fn unknown_origins_are_not_allowed() {
+    let app = app().await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/subscribe", app.address))
+        .header("Origin", "https://evil.example.com")
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=le%20guin")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert!(!response
+        .headers()
+        .contains_key("Access-Control-Allow-Origin"));
+}
+
+#[tokio::test]
+async fn admin_routes_never_allow_cross_origin_requests() {
+    let app = app().await;
+
+    for path in ["/admin/webhooks", "/admin/api-keys"] {
+        let response = preflight(&app, path, ORIGIN).await;
+
+        assert!(
+            !response
+                .headers()
+                .contains_key("Access-Control-Allow-Origin"),
+            "{}",
+            path
+        );
+    }
+}
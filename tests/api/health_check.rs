@@ -1,4 +1,6 @@
-use crate::helpers::app;
+use crate::helpers::{app, app_with_config};
+use newsletter::config::{get_embedded_config, Enviroment};
+use newsletter::startup::Application;
 
 #[tokio::test]
 async fn test_health_check_works() {
@@ -11,3 +13,100 @@ async fn test_health_check_works() {
 
     assert!(response.status().is_success())
 }
+
+#[tokio::test]
+async fn test_liveness_reports_the_build_version() {
+    let client = reqwest::Client::new();
+    let response = client
+        .get(format!("{}/health/live", app().await.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.expect("Failed to parse body");
+    assert_eq!(body["status"], "up");
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+}
+
+#[tokio::test]
+async fn test_readiness_reports_every_component() {
+    let client = reqwest::Client::new();
+    let app = app_with_config(|c| c.health.check_email_provider = true).await;
+    app.heartbeats.beat("delivery");
+
+    let response = client
+        .get(format!("{}/health/ready", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.expect("Failed to parse body");
+    assert_eq!(body["status"], "up");
+    for component in [
+        "database",
+        "migrations",
+        "email_provider",
+        "worker:delivery",
+    ] {
+        assert_eq!(
+            body["components"][component]["status"], "up",
+            "{}",
+            component
+        );
+    }
+    assert!(body["components"]["database"]["latency_ms"].is_number());
+}
+
+#[tokio::test]
+async fn test_readiness_fails_when_database_is_unreachable() {
+    let client = reqwest::Client::new();
+    let config = {
+        let mut c = get_embedded_config(Enviroment::Test).expect("Failed to read config");
+        c.database.port = 1;
+        c.database.acquire_timeout_secs = 1;
+
+        c
+    };
+    let app = Application::build(config)
+        .await
+        .expect("Failed to build app");
+    let address = format!("http://127.0.0.1:{}", app.port());
+    tokio::spawn(app.run_until_stopped());
+
+    let response = client
+        .get(format!("{}/health/ready", address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 503);
+    let body: serde_json::Value = response.json().await.expect("Failed to parse body");
+    assert_eq!(body["status"], "down");
+    assert_eq!(body["components"]["database"]["status"], "down");
+    assert!(body["components"]["database"]["error"].is_string());
+}
+
+#[tokio::test]
+async fn test_readiness_fails_when_the_database_has_unknown_migrations() {
+    let client = reqwest::Client::new();
+    let app = app().await;
+    sqlx::query(
+        r#"INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+        VALUES (99990101000000, 'from the future', true, '\x00', 0)"#,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = client
+        .get(format!("{}/health/ready", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 503);
+    let body: serde_json::Value = response.json().await.expect("Failed to parse body");
+    assert_eq!(body["components"]["migrations"]["status"], "down");
+}
@@ -1,18 +1,68 @@
+use newsletter::api_keys::{create_key, ApiKeyScope};
+use newsletter::authentication::create_user;
+use newsletter::config::{get_embedded_config, DatabaseSettings, Enviroment, Settings};
+use newsletter::publications::DEFAULT_PUBLICATION;
+use newsletter::shutdown::ShutdownHandle;
+use newsletter::startup::{get_connection_pool, Application};
+use newsletter::telemetry::{get_subscriber, init_subscriber};
+use newsletter::workers::Heartbeats;
 use reqwest::Url;
+use secrecy::{ExposeSecret, Secret};
 use sqlx::{Connection, PgConnection, PgPool};
-use stoic_newsletter::config::{get_config, DatabaseSettings};
-use stoic_newsletter::startup::{get_connection_pool, Application};
+use std::sync::Once;
+use tokio::task::JoinHandle;
 use uuid::Uuid;
 use wiremock::MockServer;
 
+// Logs are discarded unless a run opts in, e.g. `APP_TELEMETRY__SINK=stdout cargo test`.
+static TRACING: Once = Once::new();
+
 pub struct TestApp {
     pub address: String,
     pub port: String,
     pub db_pool: PgPool,
     pub email_server: MockServer,
+    pub heartbeats: Heartbeats,
+    pub metrics_address: Option<String>,
+    pub shutdown: ShutdownHandle,
+    pub server: JoinHandle<Result<(), std::io::Error>>,
+}
+
+pub struct TestAdmin {
+    pub username: String,
+    pub password: String,
 }
 
 impl TestApp {
+    pub async fn create_api_key(&self, scopes: &[ApiKeyScope]) -> String {
+        let (_, key) = create_key(&self.db_pool, "default", "test", scopes, 60)
+            .await
+            .expect("Failed to create API key");
+
+        key.expose_secret().to_owned()
+    }
+
+    pub async fn create_admin(&self) -> TestAdmin {
+        self.create_admin_of(&[DEFAULT_PUBLICATION]).await
+    }
+
+    pub async fn create_admin_of(&self, publications: &[&str]) -> TestAdmin {
+        let admin = TestAdmin {
+            username: Uuid::new_v4().to_string(),
+            password: Uuid::new_v4().to_string(),
+        };
+        create_user(
+            &self.db_pool,
+            &admin.username,
+            &Secret::new(admin.password.clone()),
+            publications,
+        )
+        .await
+        .expect("Failed to create admin");
+
+        admin
+    }
+
     pub fn get_confirmation_link(&self, email_request: &wiremock::Request) -> String {
         let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
         let get_link = |s: &str| {
@@ -25,7 +75,7 @@ impl TestApp {
         };
 
         let raw_link = &get_link(&body.to_string());
-        let mut url = Url::parse(&raw_link).expect("Failed to parse url");
+        let mut url = Url::parse(raw_link).expect("Failed to parse url");
         url.set_port(Some(self.port.parse::<u16>().unwrap()))
             .expect("failed to set port");
         //TODO: figure out why the link has a trailing backslash in tests
@@ -34,16 +84,48 @@ impl TestApp {
 
         u
     }
+
+    /// Submits the form the confirmation link leads to.
+    pub async fn confirm_subscription(&self, link: &str) -> reqwest::Response {
+        let link = Url::parse(link).expect("Failed to parse url");
+        let (_, token) = link
+            .query_pairs()
+            .find(|(key, _)| key == "subscription_token")
+            .expect("The link has no token");
+
+        reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap()
+            .post(format!("{}/subscriptions/confirm", self.address))
+            .form(&[("subscription_token", token.as_ref())])
+            .send()
+            .await
+            .expect("Failed to send confirmation request")
+    }
 }
 
 pub async fn app() -> TestApp {
+    app_with_config(|_| {}).await
+}
+
+pub async fn app_with_config(customize: impl FnOnce(&mut Settings)) -> TestApp {
+    TRACING.call_once(|| {
+        let config = get_embedded_config(Enviroment::Test).expect("Failed to read config");
+        let (subscriber, guard) =
+            get_subscriber(&config.telemetry).expect("Failed to set up telemetry");
+        config.telemetry.redaction.install();
+        init_subscriber(subscriber);
+        // The guard has to outlive every test for file sinks to be flushed.
+        std::mem::forget(guard);
+    });
     let email_server = MockServer::start().await;
 
     let config = {
-        let mut c = get_config().expect("Failed to read config");
+        let mut c = get_embedded_config(Enviroment::Test).expect("Failed to read config");
         c.database.database_name = Uuid::new_v4().to_string();
-        c.application.port = 0;
         c.email_client.url = email_server.uri();
+        customize(&mut c);
 
         c
     };
@@ -54,18 +136,38 @@ pub async fn app() -> TestApp {
         .expect("Failed to build app");
     let address = format!("http://127.0.0.1:{}", app.port());
     let port = app.port();
-    let _ = tokio::spawn(app.run_until_stopped());
+    let heartbeats = app.heartbeats();
+    let metrics_address = app
+        .metrics_port()
+        .map(|port| format!("http://127.0.0.1:{}", port));
+    let shutdown = app.shutdown_handle();
+    let server = tokio::spawn(app.run_until_stopped());
 
     TestApp {
         address,
         port,
         db_pool: get_connection_pool(&config.database),
         email_server,
+        heartbeats,
+        metrics_address,
+        shutdown,
+        server,
     }
 }
 
 pub async fn configure_db(settings: &DatabaseSettings) -> PgPool {
-    let mut connection = PgConnection::connect(&settings.without_db())
+    let connection_pool = create_db(settings).await;
+
+    sqlx::migrate!("./migrations")
+        .run(&connection_pool)
+        .await
+        .expect("Failed to execute migrations");
+
+    connection_pool
+}
+
+pub async fn create_db(settings: &DatabaseSettings) -> PgPool {
+    let mut connection = PgConnection::connect_with(&settings.without_db())
         .await
         .expect("failed to connect to db");
 
@@ -74,14 +176,9 @@ pub async fn configure_db(settings: &DatabaseSettings) -> PgPool {
         .await
         .expect("Failed to create test db");
 
-    let connection_pool = PgPool::connect(&settings.connection_string())
+    let connection_pool = PgPool::connect_with(settings.with_db())
         .await
         .expect("Failed to connect to db");
 
-    sqlx::migrate!("./migrations")
-        .run(&connection_pool)
-        .await
-        .expect("Failed to execute migrations");
-
     connection_pool
 }
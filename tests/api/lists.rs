@@ -0,0 +1,220 @@
+use newsletter::preferences::link_token;
+use secrecy::Secret;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{app, TestApp};
+
+async fn add_list(app: &TestApp, slug: &str, name: &str) {
+    sqlx::query!(
+        "INSERT INTO lists (list_id, publication_id, slug, name, created_at)
+        VALUES ($1, 'default', $2, $3, now())",
+        uuid::Uuid::new_v4(),
+        slug,
+        name
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn subscribe(app: &TestApp, list: Option<&str>) -> reqwest::Response {
+    let mut form = vec![("name", "le guin"), ("email", "ursula_le_guin@gmail.com")];
+    if let Some(list) = list {
+        form.push(("list", list));
+    }
+    reqwest::Client::new()
+        .post(format!("{}/subscribe", app.address))
+        .form(&form)
+        .send()
+        .await
+        .expect("Failed to send request")
+}
+
+async fn memberships(app: &TestApp) -> Vec<(String, String)> {
+    sqlx::query!(
+        r#"SELECT l.slug, m.status FROM list_memberships m
+        JOIN lists l ON l.list_id = m.list_id ORDER BY l.slug"#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| (r.slug, r.status))
+    .collect()
+}
+
+async fn preference_token(app: &TestApp) -> String {
+    let subscriber_id = sqlx::query_scalar!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    link_token(
+        &Secret::new("test-key".to_owned()),
+        subscriber_id,
+        chrono::Utc::now() + chrono::Duration::hours(1),
+    )
+}
+
+async fn mock_email(app: &TestApp) {
+    Mock::given(path("mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+}
+
+#[tokio::test]
+async fn one_address_can_join_several_lists() {
+    let app = app().await;
+    mock_email(&app).await;
+    add_list(&app, "weekly", "Weekly digest").await;
+
+    let default = subscribe(&app, None).await;
+    let weekly = subscribe(&app, Some("weekly")).await;
+    let again = subscribe(&app, Some("weekly")).await;
+
+    assert_eq!(default.status().as_u16(), 200);
+    assert_eq!(weekly.status().as_u16(), 200);
+    assert_eq!(again.status().as_u16(), 409);
+    assert_eq!(
+        memberships(&app).await,
+        vec![
+            ("newsletter".to_owned(), "pending_confirmation".to_owned()),
+            ("weekly".to_owned(), "pending_confirmation".to_owned()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn subscribing_to_an_unknown_list_is_rejected() {
+    let app = app().await;
+
+    let response = subscribe(&app, Some("nope")).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let subscribers = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(subscribers.is_empty());
+}
+
+#[tokio::test]
+async fn each_list_is_confirmed_on_its_own() {
+    let app = app().await;
+    mock_email(&app).await;
+    add_list(&app, "weekly", "Weekly digest").await;
+    subscribe(&app, None).await;
+    subscribe(&app, Some("weekly")).await;
+    let emails = app.email_server.received_requests().await.unwrap();
+
+    let response = app
+        .confirm_subscription(&app.get_confirmation_link(&emails[1]))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        memberships(&app).await,
+        vec![
+            ("newsletter".to_owned(), "pending_confirmation".to_owned()),
+            ("weekly".to_owned(), "confirmed".to_owned()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn subscribers_can_leave_one_list_or_all_of_them() {
+    let app = app().await;
+    mock_email(&app).await;
+    add_list(&app, "weekly", "Weekly digest").await;
+    subscribe(&app, None).await;
+    subscribe(&app, Some("weekly")).await;
+    for email in app.email_server.received_requests().await.unwrap() {
+        app.confirm_subscription(&app.get_confirmation_link(&email))
+            .await;
+    }
+    let token = preference_token(&app).await;
+    let client = reqwest::Client::new();
+
+    let page = client
+        .get(format!("{}/preferences?token={}", app.address, token))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("Weekly digest"));
+    let left = client
+        .post(format!("{}/preferences/unsubscribe", app.address))
+        .form(&[("token", token.as_str()), ("list", "weekly")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(left.status().as_u16(), 200);
+    assert_eq!(
+        memberships(&app).await,
+        vec![
+            ("newsletter".to_owned(), "confirmed".to_owned()),
+            ("weekly".to_owned(), "unsubscribed".to_owned()),
+        ]
+    );
+
+    let all = client
+        .post(format!("{}/preferences/unsubscribe", app.address))
+        .form(&[("token", token.as_str())])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(all.status().as_u16(), 200);
+    assert_eq!(
+        memberships(&app).await,
+        vec![
+            ("newsletter".to_owned(), "unsubscribed".to_owned()),
+            ("weekly".to_owned(), "unsubscribed".to_owned()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn lists_are_managed_by_admins() {
+    let app = app().await;
+    let admin = app.create_admin().await;
+    let client = reqwest::Client::new();
+
+    let anonymous = client
+        .post(format!("{}/admin/lists", app.address))
+        .json(&serde_json::json!({"slug": "weekly", "name": "Weekly digest"}))
+        .send()
+        .await
+        .unwrap();
+    let created = client
+        .post(format!("{}/admin/lists", app.address))
+        .basic_auth(&admin.username, Some(&admin.password))
+        .json(&serde_json::json!({"slug": "weekly", "name": "Weekly digest"}))
+        .send()
+        .await
+        .unwrap();
+    let listed: serde_json::Value = client
+        .get(format!("{}/admin/lists", app.address))
+        .basic_auth(&admin.username, Some(&admin.password))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(anonymous.status().as_u16(), 401);
+    assert_eq!(created.status().as_u16(), 201);
+    let slugs: Vec<_> = listed
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|list| list["slug"].as_str().unwrap())
+        .collect();
+    assert_eq!(slugs, ["newsletter", "weekly"]);
+}
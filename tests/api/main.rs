@@ -1,4 +1,17 @@
+mod api_docs;
+mod api_v1;
+mod attributes;
+mod cli;
+mod cors;
 mod health_check;
 mod helpers;
+mod lists;
+mod metrics;
+mod preferences;
+mod publications;
+mod shutdown;
+mod startup;
 mod subscriptions;
 mod subscriptions_confirm;
+mod tags;
+mod webhooks;
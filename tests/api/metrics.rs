@@ -0,0 +1,97 @@
+use crate::helpers::app;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+#[tokio::test]
+async fn test_metrics_track_requests_and_the_subscription_funnel() {
+    let client = reqwest::Client::new();
+    let app = app().await;
+
+    Mock::given(path("mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    client
+        .post(format!("{}/subscribe", app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    let response = client
+        .get(format!("{}/metrics", app.metrics_address.unwrap()))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.expect("Failed to read body");
+    assert!(body.contains(
+        r#"newsletter_http_requests_total{method="POST",route="/subscribe",status="200"} 1"#
+    ));
+    assert!(body.contains(r#"newsletter_subscriptions_total{event="subscribed"} 1"#));
+    assert!(body.contains(
+        r#"newsletter_emails_sent_total{error_class="none",outcome="success",provider="127.0.0.1"} 1"#
+    ));
+    assert!(body.contains(r#"newsletter_db_pool_connections{state="max"}"#));
+}
+
+#[tokio::test]
+async fn test_email_failures_are_counted_by_error_class() {
+    let client = reqwest::Client::new();
+    let app = app().await;
+
+    Mock::given(path("mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&app.email_server)
+        .await;
+
+    client
+        .post(format!("{}/subscribe", app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    let body = client
+        .get(format!("{}/metrics", app.metrics_address.unwrap()))
+        .send()
+        .await
+        .expect("Failed to execute request")
+        .text()
+        .await
+        .expect("Failed to read body");
+
+    assert!(body.contains(
+        r#"newsletter_emails_sent_total{error_class="server_error",outcome="failure",provider="127.0.0.1"} 1"#
+    ));
+}
+
+#[tokio::test]
+async fn test_metrics_are_only_served_on_their_own_port() {
+    let client = reqwest::Client::new();
+    let app = app().await;
+    let metrics_address = app.metrics_address.expect("Metrics port is not set");
+
+    let response = client
+        .get(format!("{}/metrics", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = client
+        .get(format!("{}/metrics", metrics_address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(response.status().as_u16(), 200);
+}
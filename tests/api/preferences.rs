@@ -0,0 +1,378 @@
+use newsletter::preferences::link_token;
+use reqwest::Url;
+use secrecy::Secret;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{app, TestApp};
+
+async fn confirmed_subscriber(app: &TestApp) {
+    Mock::given(path("mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    reqwest::Client::new()
+        .post(format!("{}/subscribe", app.address))
+        .form(&[("name", "le guin"), ("email", "ursula_le_guin@gmail.com")])
+        .send()
+        .await
+        .expect("Failed to send request")
+        .error_for_status()
+        .expect("Failed to subscribe");
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    app.confirm_subscription(&app.get_confirmation_link(email_request))
+        .await;
+}
+
+async fn request_link(app: &TestApp, email: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/preferences/link", app.address))
+        .header("Accept", "text/html")
+        .form(&[("email", email)])
+        .send()
+        .await
+        .expect("Failed to send request")
+}
+
+async fn preference_link(app: &TestApp) -> String {
+    request_link(app, "ursula_le_guin@gmail.com").await;
+    let received = app.email_server.received_requests().await.unwrap();
+    app.get_confirmation_link(received.last().unwrap())
+}
+
+fn token(link: &str) -> String {
+    let link = Url::parse(link).unwrap();
+    let (_, token) = link.query_pairs().find(|(key, _)| key == "token").unwrap();
+    token.into_owned()
+}
+
+async fn add_topic(app: &TestApp, slug: &str, name: &str) {
+    sqlx::query!(
+        "INSERT INTO topics (publication_id, slug, name, created_at) VALUES ('default', $1, $2, now())",
+        slug,
+        name
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn links_are_only_emailed_to_confirmed_subscribers() {
+    let app = app().await;
+    confirmed_subscriber(&app).await;
+    let emails_before = app.email_server.received_requests().await.unwrap().len();
+
+    let unknown = request_link(&app, "someone_else@gmail.com").await;
+    let emails_after_unknown = app.email_server.received_requests().await.unwrap().len();
+    let known = request_link(&app, "ursula_le_guin@gmail.com").await;
+
+    assert_eq!(unknown.status().as_u16(), 200);
+    assert_eq!(known.status().as_u16(), 200);
+    assert_eq!(
+        unknown.text().await.unwrap(),
+        known.text().await.unwrap(),
+        "responses must not reveal who is subscribed"
+    );
+    assert_eq!(emails_after_unknown, emails_before);
+    let received = app.email_server.received_requests().await.unwrap();
+    assert_eq!(received.len(), emails_before + 1);
+    let link = app.get_confirmation_link(received.last().unwrap());
+    assert!(link.contains("/preferences?token="));
+}
+
+#[tokio::test]
+async fn preferences_can_be_changed_from_the_page() {
+    let app = app().await;
+    add_topic(&app, "essays", "Essays").await;
+    add_topic(&app, "events", "Events").await;
+    confirmed_subscriber(&app).await;
+    let link = preference_link(&app).await;
+
+    let page = reqwest::get(&link).await.unwrap();
+    assert_eq!(page.status().as_u16(), 200);
+    let body = page.text().await.unwrap();
+    assert!(body.contains(r#"value="le guin""#));
+    assert!(body.contains(r#"value="essays""#));
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/preferences", app.address))
+        .form(&[
+            ("token", token(&link).as_str()),
+            ("name", "Ursula K. Le Guin"),
+            ("frequency", "monthly"),
+            ("paused", "on"),
+            ("topic", "essays"),
+            ("topic", "events"),
+            ("topic", "unknown"),
+        ])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("Your preferences have been saved."));
+    let saved = sqlx::query!(
+        r#"SELECT s.name, p.frequency, p.paused_at FROM subscriptions s
+        JOIN subscriber_preferences p ON p.subscriber_id = s.id"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.name, "Ursula K. Le Guin");
+    assert_eq!(saved.frequency, "monthly");
+    assert!(saved.paused_at.is_some());
+    let topics = sqlx::query_scalar!("SELECT topic FROM subscriber_topics ORDER BY topic")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(topics, ["essays", "events"]);
+}
+
+#[tokio::test]
+async fn invalid_preferences_are_not_saved() {
+    let app = app().await;
+    confirmed_subscriber(&app).await;
+    let link = preference_link(&app).await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/preferences", app.address))
+        .form(&[
+            ("token", token(&link).as_str()),
+            ("name", "<script>"),
+            ("frequency", "hourly"),
+        ])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    let saved = sqlx::query!("SELECT name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.name, "le guin");
+}
+
+#[tokio::test]
+async fn forged_and_expired_links_are_rejected() {
+    let app = app().await;
+    confirmed_subscriber(&app).await;
+    let subscriber_id = sqlx::query_scalar!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let forged = link_token(
+        &Secret::new("guessed".to_owned()),
+        subscriber_id,
+        chrono::Utc::now() + chrono::Duration::hours(1),
+    );
+    let expired = link_token(
+        &Secret::new("test-key".to_owned()),
+        subscriber_id,
+        chrono::Utc::now() - chrono::Duration::hours(1),
+    );
+    let unknown_subscriber = link_token(
+        &Secret::new("test-key".to_owned()),
+        Uuid::new_v4(),
+        chrono::Utc::now() + chrono::Duration::hours(1),
+    );
+
+    for (token, status) in [(forged, 401), (expired, 410), (unknown_subscriber, 401)] {
+        let response = reqwest::get(format!("{}/preferences?token={}", app.address, token))
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), status);
+    }
+}
+
+#[tokio::test]
+async fn subscribers_can_unsubscribe() {
+    let app = app().await;
+    confirmed_subscriber(&app).await;
+    let link = preference_link(&app).await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/preferences/unsubscribe", app.address))
+        .form(&[("token", token(&link))])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("unsubscribed"));
+    let saved = sqlx::query!("SELECT status, unsubscribed_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+    assert!(saved.unsubscribed_at.is_some());
+    let page = reqwest::get(&link).await.unwrap().text().await.unwrap();
+    assert!(!page.contains("<form"));
+}
+
+#[tokio::test]
+async fn topics_are_managed_by_admins() {
+    let app = app().await;
+    let admin = app.create_admin().await;
+    let client = reqwest::Client::new();
+
+    let anonymous = client
+        .post(format!("{}/admin/topics", app.address))
+        .json(&serde_json::json!({"slug": "essays", "name": "Essays"}))
+        .send()
+        .await
+        .unwrap();
+    let created = client
+        .post(format!("{}/admin/topics", app.address))
+        .basic_auth(&admin.username, Some(&admin.password))
+        .json(&serde_json::json!({"slug": "essays", "name": "Essays"}))
+        .send()
+        .await
+        .unwrap();
+    let duplicate = client
+        .post(format!("{}/admin/topics", app.address))
+        .basic_auth(&admin.username, Some(&admin.password))
+        .json(&serde_json::json!({"slug": "essays", "name": "More essays"}))
+        .send()
+        .await
+        .unwrap();
+    let listed: serde_json::Value = client
+        .get(format!("{}/admin/topics", app.address))
+        .basic_auth(&admin.username, Some(&admin.password))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(anonymous.status().as_u16(), 401);
+    assert_eq!(created.status().as_u16(), 201);
+    assert_eq!(duplicate.status().as_u16(), 400);
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_eq!(listed[0]["name"], "Essays");
+}
+
+fn recipient(request: &wiremock::Request) -> String {
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    body["personalizations"][0]["to"][0]["email"]
+        .as_str()
+        .unwrap()
+        .to_owned()
+}
+
+async fn request_email_change(app: &TestApp, link: &str, new_email: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/preferences/email", app.address))
+        .form(&[("token", token(link).as_str()), ("new_email", new_email)])
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn confirm_email_change(app: &TestApp, link: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/preferences/email/confirm", app.address))
+        .form(&[("token", token(link))])
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn subscriber_email(app: &TestApp) -> String {
+    sqlx::query_scalar!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn the_email_address_changes_once_the_new_one_is_verified() {
+    let app = app().await;
+    confirmed_subscriber(&app).await;
+    let link = preference_link(&app).await;
+
+    let response = request_email_change(&app, &link, "ursula@earthsea.org").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let received = app.email_server.received_requests().await.unwrap();
+    let verification = received.last().unwrap();
+    assert_eq!(recipient(verification), "ursula@earthsea.org");
+    let verification_link = app.get_confirmation_link(verification);
+    assert!(verification_link.contains("/preferences/email/confirm?token="));
+
+    let page = reqwest::get(&verification_link).await.unwrap();
+    assert_eq!(page.status().as_u16(), 200);
+    assert_eq!(subscriber_email(&app).await, "ursula_le_guin@gmail.com");
+
+    let response = confirm_email_change(&app, &verification_link).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_email(&app).await, "ursula@earthsea.org");
+    let received = app.email_server.received_requests().await.unwrap();
+    let notice = received.last().unwrap();
+    assert_eq!(recipient(notice), "ursula_le_guin@gmail.com");
+    assert!(String::from_utf8_lossy(&notice.body).contains("ursula@earthsea.org"));
+
+    let reused = confirm_email_change(&app, &verification_link).await;
+    assert_eq!(reused.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn addresses_of_other_subscriptions_cannot_be_taken() {
+    let app = app().await;
+    confirmed_subscriber(&app).await;
+    let link = preference_link(&app).await;
+
+    let pending = request_email_change(&app, &link, "ursula@earthsea.org").await;
+    assert_eq!(pending.status().as_u16(), 200);
+    let received = app.email_server.received_requests().await.unwrap();
+    let verification_link = app.get_confirmation_link(received.last().unwrap());
+    reqwest::Client::new()
+        .post(format!("{}/subscribe", app.address))
+        .form(&[("name", "someone"), ("email", "ursula@earthsea.org")])
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let taken_on_request = request_email_change(&app, &link, "ursula@earthsea.org").await;
+    let taken_on_confirm = confirm_email_change(&app, &verification_link).await;
+
+    assert_eq!(taken_on_request.status().as_u16(), 409);
+    assert_eq!(taken_on_confirm.status().as_u16(), 409);
+    let owner = sqlx::query_scalar!(
+        "SELECT name FROM subscriptions WHERE email = 'ursula_le_guin@gmail.com'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(owner, "le guin");
+}
+
+#[tokio::test]
+async fn expired_email_changes_are_not_applied() {
+    let app = app().await;
+    confirmed_subscriber(&app).await;
+    let link = preference_link(&app).await;
+    request_email_change(&app, &link, "ursula@earthsea.org").await;
+    let received = app.email_server.received_requests().await.unwrap();
+    let verification_link = app.get_confirmation_link(received.last().unwrap());
+    sqlx::query!("UPDATE email_changes SET created_at = now() - interval '25 hours'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = confirm_email_change(&app, &verification_link).await;
+
+    assert_eq!(response.status().as_u16(), 410);
+    assert_eq!(subscriber_email(&app).await, "ursula_le_guin@gmail.com");
+}
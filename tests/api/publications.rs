@@ -0,0 +1,232 @@
+use newsletter::api_keys::{create_key, ApiKeyScope};
+use newsletter::config::PublicationSettings;
+use newsletter::publications::DEFAULT_PUBLICATION;
+use reqwest::Url;
+use secrecy::ExposeSecret;
+use serde_json::Value;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{app_with_config, TestApp};
+
+const ACME: &str = "acme.test";
+
+async fn app() -> TestApp {
+    let app = app_with_config(|c| {
+        let mut email_client = c.email_client.clone();
+        email_client.sender = String::from("news@acme.test");
+        c.publications.insert(
+            String::from("acme"),
+            PublicationSettings {
+                hosts: vec![ACME.to_owned()],
+                base_url: format!("http://{}", ACME),
+                email_client,
+            },
+        );
+    })
+    .await;
+    Mock::given(path("mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app
+}
+
+async fn subscribe(app: &TestApp, host: Option<&str>) -> reqwest::Response {
+    let mut request = reqwest::Client::new()
+        .post(format!("{}/subscribe", app.address))
+        .form(&[("name", "le guin"), ("email", "ursula_le_guin@gmail.com")]);
+    if let Some(host) = host {
+        request = request.header("Host", host);
+    }
+    request.send().await.expect("Failed to send request")
+}
+
+/// The confirmation link in an email, left pointing at the host it was sent for.
+fn confirmation_link(email_request: &wiremock::Request) -> Url {
+    let body: Value = serde_json::from_slice(&email_request.body).unwrap();
+    let link = linkify::LinkFinder::new()
+        .links(body["content"][0]["value"].as_str().unwrap())
+        .find(|l| *l.kind() == linkify::LinkKind::Url)
+        .unwrap()
+        .as_str()
+        .to_owned();
+    Url::parse(&link).unwrap()
+}
+
+fn token(link: &Url) -> String {
+    let (_, token) = link
+        .query_pairs()
+        .find(|(key, _)| key == "subscription_token")
+        .unwrap();
+    token.into_owned()
+}
+
+async fn confirm(app: &TestApp, host: Option<&str>, token: &str) -> reqwest::Response {
+    let mut request = reqwest::Client::new()
+        .post(format!("{}/subscriptions/confirm", app.address))
+        .form(&[("subscription_token", token)]);
+    if let Some(host) = host {
+        request = request.header("Host", host);
+    }
+    request.send().await.expect("Failed to send request")
+}
+
+#[tokio::test]
+async fn an_address_subscribes_to_each_publication_on_its_own() {
+    let app = app().await;
+
+    let default = subscribe(&app, None).await;
+    let acme = subscribe(&app, Some(ACME)).await;
+    let again = subscribe(&app, Some(ACME)).await;
+
+    assert_eq!(default.status().as_u16(), 200);
+    assert_eq!(acme.status().as_u16(), 200);
+    assert_eq!(again.status().as_u16(), 409);
+    let saved = sqlx::query!(
+        "SELECT publication_id FROM subscriptions WHERE email = 'ursula_le_guin@gmail.com'
+        ORDER BY publication_id"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    let publications: Vec<_> = saved.into_iter().map(|r| r.publication_id).collect();
+    assert_eq!(publications, vec!["acme", "default"]);
+}
+
+#[tokio::test]
+async fn emails_use_the_publications_sender_and_domain() {
+    let app = app().await;
+
+    subscribe(&app, None).await;
+    subscribe(&app, Some(ACME)).await;
+
+    let emails = app.email_server.received_requests().await.unwrap();
+    let default: Value = serde_json::from_slice(&emails[0].body).unwrap();
+    let acme: Value = serde_json::from_slice(&emails[1].body).unwrap();
+    assert_eq!(default["from"]["email"], "test@email.com");
+    assert_eq!(acme["from"]["email"], "news@acme.test");
+    assert_eq!(confirmation_link(&emails[0]).host_str(), Some("127.0.0.1"));
+    assert_eq!(confirmation_link(&emails[1]).host_str(), Some(ACME));
+}
+
+#[tokio::test]
+async fn tokens_only_confirm_on_their_publications_host() {
+    let app = app().await;
+    subscribe(&app, Some(ACME)).await;
+    let emails = app.email_server.received_requests().await.unwrap();
+    let token = token(&confirmation_link(&emails[0]));
+
+    let elsewhere = confirm(&app, None, &token).await;
+    let home = confirm(&app, Some(ACME), &token).await;
+
+    assert_eq!(elsewhere.status().as_u16(), 401);
+    assert_eq!(home.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn api_keys_only_see_their_publications_subscribers() {
+    let app = app().await;
+    subscribe(&app, Some(ACME)).await;
+    let (_, acme_key) = create_key(&app.db_pool, "acme", "acme", &[ApiKeyScope::Read], 60)
+        .await
+        .unwrap();
+    let default_key = app.create_api_key(&[ApiKeyScope::Read]).await;
+    let client = reqwest::Client::new();
+
+    let mut totals = vec![];
+    for key in [acme_key.expose_secret().as_str(), default_key.as_str()] {
+        let body: Value = client
+            .get(format!("{}/api/v1/subscribers", app.address))
+            .bearer_auth(key)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        totals.push(body.as_array().unwrap().len());
+    }
+
+    assert_eq!(totals, vec![1, 0]);
+}
+
+#[tokio::test]
+async fn admins_only_manage_the_publication_they_are_on() {
+    let app = app().await;
+    let admin = app.create_admin_of(&["acme", DEFAULT_PUBLICATION]).await;
+    let client = reqwest::Client::new();
+
+    let created = client
+        .post(format!("{}/admin/lists", app.address))
+        .header("Host", ACME)
+        .basic_auth(&admin.username, Some(&admin.password))
+        .json(&serde_json::json!({"slug": "weekly", "name": "Weekly digest"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(created.status().as_u16(), 201);
+
+    let mut slugs = vec![];
+    for host in [ACME, "127.0.0.1"] {
+        let lists: Value = client
+            .get(format!("{}/admin/lists", app.address))
+            .header("Host", host)
+            .basic_auth(&admin.username, Some(&admin.password))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let found: Vec<_> = lists
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|l| l["slug"].as_str().unwrap().to_owned())
+            .collect();
+        slugs.push(found);
+    }
+
+    assert_eq!(slugs, vec![vec!["weekly"], vec!["newsletter"]]);
+}
+
+#[tokio::test]
+async fn admins_cannot_manage_publications_they_are_not_bound_to() {
+    let app = app().await;
+    let admin = app.create_admin().await;
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/admin/lists", app.address))
+        .header("Host", ACME)
+        .basic_auth(&admin.username, Some(&admin.password))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn forwarded_hosts_from_untrusted_clients_are_ignored() {
+    let app = app().await;
+
+    reqwest::Client::new()
+        .post(format!("{}/subscribe", app.address))
+        .header("X-Forwarded-Host", ACME)
+        .header("Forwarded", format!("host={}", ACME))
+        .form(&[("name", "le guin"), ("email", "ursula_le_guin@gmail.com")])
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    let saved = sqlx::query!("SELECT publication_id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.publication_id, DEFAULT_PUBLICATION);
+}
@@ -0,0 +1,68 @@
+use std::time::Duration;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::app;
+
+#[tokio::test]
+async fn shutdown_lets_in_flight_requests_finish() {
+    let client = reqwest::Client::new();
+    let app = app().await;
+
+    Mock::given(path("mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let request = client
+        .post(format!("{}/subscribe", app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send();
+    let in_flight = tokio::spawn(request);
+    // The request is in flight once the handler is waiting on the email provider.
+    while app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .is_empty()
+    {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    app.shutdown.trigger();
+
+    let response = in_flight.await.unwrap().expect("Failed to send request");
+    assert_eq!(response.status().as_u16(), 200);
+    // Idle keep-alive connections would otherwise hold the server until the deadline.
+    drop(client);
+    tokio::time::timeout(Duration::from_secs(10), app.server)
+        .await
+        .expect("Application did not stop in time")
+        .unwrap()
+        .expect("Application failed while stopping");
+}
+
+#[tokio::test]
+async fn no_requests_are_accepted_after_shutdown() {
+    let app = app().await;
+
+    app.shutdown.trigger();
+    tokio::time::timeout(Duration::from_secs(10), app.server)
+        .await
+        .expect("Application did not stop in time")
+        .unwrap()
+        .unwrap();
+
+    let result = reqwest::Client::new()
+        .get(format!("{}/health-check", app.address))
+        .send()
+        .await;
+
+    assert!(result.is_err());
+}
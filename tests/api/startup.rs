@@ -0,0 +1,96 @@
+use newsletter::config::{get_embedded_config, Enviroment, Settings};
+use newsletter::startup::{Application, MIGRATOR};
+use uuid::Uuid;
+
+use crate::helpers::{configure_db, create_db};
+
+fn config_with_fresh_database() -> Settings {
+    let mut c = get_embedded_config(Enviroment::Test).expect("Failed to read config");
+    c.database.database_name = Uuid::new_v4().to_string();
+
+    c
+}
+
+#[tokio::test]
+async fn test_build_fails_fast_when_database_is_unreachable() {
+    let config = {
+        let mut c = get_embedded_config(Enviroment::Test).expect("Failed to read config");
+        c.database.port = 1;
+        c.database.connect_eagerly = true;
+        c.database.connect_retries = 1;
+        c.database.connect_backoff_ms = 10;
+        c.database.acquire_timeout_secs = 1;
+
+        c
+    };
+
+    let result = Application::build(config).await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_build_connects_eagerly_to_a_reachable_database() {
+    let config = {
+        let mut c = get_embedded_config(Enviroment::Test).expect("Failed to read config");
+        c.database.database_name = String::from("postgres");
+        c.database.connect_eagerly = true;
+
+        c
+    };
+
+    assert!(Application::build(config).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_build_runs_migrations_when_enabled() {
+    let mut config = config_with_fresh_database();
+    config.database.migrate_on_startup = true;
+    let pool = create_db(&config.database).await;
+
+    // Several instances starting at once must not trip over each other.
+    let (first, second) = tokio::join!(
+        Application::build(config.clone()),
+        Application::build(config.clone())
+    );
+    assert!(first.is_ok());
+    assert!(second.is_ok());
+
+    let applied: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM _sqlx_migrations")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(applied, MIGRATOR.iter().count() as i64);
+}
+
+#[tokio::test]
+async fn test_build_does_not_migrate_by_default() {
+    let config = config_with_fresh_database();
+    let pool = create_db(&config.database).await;
+
+    assert!(Application::build(config).await.is_ok());
+
+    let table: Option<String> = sqlx::query_scalar("SELECT to_regclass('subscriptions')::TEXT")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(table.is_none());
+}
+
+#[tokio::test]
+async fn test_build_refuses_a_database_with_unknown_migrations() {
+    let mut config = config_with_fresh_database();
+    config.database.connect_eagerly = true;
+    let pool = configure_db(&config.database).await;
+    sqlx::query(
+        r#"INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+        VALUES (99990101000000, 'from the future', true, '\x00', 0)"#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    assert!(Application::build(config.clone()).await.is_err());
+    config.database.migrate_on_startup = true;
+    assert!(Application::build(config).await.is_err());
+}
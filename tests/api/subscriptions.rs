@@ -1,12 +1,11 @@
 use chrono::Utc;
-use sqlx::Executor;
 use uuid::Uuid;
 use wiremock::{
     matchers::{method, path},
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::app;
+use crate::helpers::{app, app_with_config};
 
 #[tokio::test]
 async fn test_subscribe_returns_200_for_valid_form_data() {
@@ -115,15 +114,15 @@ async fn test_subscribing_to_newsletter_with_invalid_data_returns_400() {
         },
         TestCase {
             payload: format!("name={}&email=testemail", ""),
-            error: "".to_string(),
+            error: r#"{"error":"Not a valid subscriber name"}"#.to_string(),
         },
         TestCase {
             payload: format!("name={}&email=testemail", " "),
-            error: "".to_string(),
+            error: r#"{"error":"Not a valid subscriber name"}"#.to_string(),
         },
         TestCase {
             payload: format!("name=validnam&email={}", "invalidemail"),
-            error: "".to_string(),
+            error: r#"{"error":"Not a valid email address"}"#.to_string(),
         },
     ];
 
@@ -150,12 +149,13 @@ async fn test_subscribing_with_existing_email_returns_409() {
     let client = reqwest::Client::new();
     let app = app().await;
 
+    let subscriber_id = Uuid::new_v4();
     sqlx::query!(
         r#"
-        INSERT INTO subscriptions(id, email, name, subscribed_at, status)
-        VALUES($1, $2, $3, $4, 'pending_confirmation')
+        INSERT INTO subscriptions(id, publication_id, email, name, subscribed_at, status)
+        VALUES($1, 'default', $2, $3, $4, 'pending_confirmation')
         "#,
-        Uuid::new_v4(),
+        subscriber_id,
         "test@email.com",
         "test",
         Utc::now()
@@ -163,6 +163,17 @@ async fn test_subscribing_with_existing_email_returns_409() {
     .execute(&app.db_pool)
     .await
     .expect("Failed to seed data");
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships(subscriber_id, list_id, status, subscribed_at)
+        SELECT $1, list_id, 'pending_confirmation', now() FROM lists
+        WHERE publication_id = 'default' AND slug = 'newsletter'
+        "#,
+        subscriber_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to seed data");
 
     let response = client
         .post(format!("{}/subscribe", app.address))
@@ -174,3 +185,184 @@ async fn test_subscribing_with_existing_email_returns_409() {
 
     assert_eq!(response.status().as_u16(), 409);
 }
+
+#[tokio::test]
+async fn test_subscribe_sends_confirmation_email_in_accepted_language() {
+    let client = reqwest::Client::new();
+    let app = app().await;
+
+    Mock::given(path("mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = client
+        .post(format!("{}/subscribe", app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("Accept-Language", "de-AT,de;q=0.9,en;q=0.5")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert!(response.status().is_success());
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["subject"], "Newsletter-Anmeldung");
+    assert!(body.to_string().contains("Willkommen"));
+
+    let saved = sqlx::query!("SELECT locale FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions.");
+    assert_eq!(saved.locale, "de");
+}
+
+#[tokio::test]
+async fn test_subscribe_prefers_explicit_language_over_accept_language() {
+    let client = reqwest::Client::new();
+    let app = app().await;
+
+    Mock::given(path("mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = client
+        .post(format!("{}/subscribe", app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("Accept-Language", "de")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com&language=cs")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert!(response.status().is_success());
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert!(body.to_string().contains("Vítejte"));
+
+    let saved = sqlx::query!("SELECT locale FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions.");
+    assert_eq!(saved.locale, "cs");
+}
+
+#[tokio::test]
+async fn browsers_get_a_page_for_every_outcome() {
+    let client = reqwest::Client::new();
+    let app = app().await;
+
+    Mock::given(path("mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let mut outcomes = vec![];
+    for body in [
+        "name=le%20guin&email=ursula_le_guin%40gmail.com",
+        "name=le%20guin&email=ursula_le_guin%40gmail.com",
+        "name=le%20guin&email=not-an-email",
+    ] {
+        let response = client
+            .post(format!("{}/subscribe", app.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header("Accept", "text/html,application/xhtml+xml,*/*;q=0.8")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to send request");
+        outcomes.push((response.status().as_u16(), response.text().await.unwrap()));
+    }
+
+    assert_eq!(outcomes[0].0, 200);
+    assert!(outcomes[0].1.contains("Thanks for subscribing!"));
+    assert_eq!(outcomes[1].0, 409);
+    assert!(outcomes[1].1.contains("already subscribed"));
+    assert_eq!(outcomes[2].0, 400);
+    assert!(outcomes[2].1.contains("valid name and email address"));
+}
+
+#[tokio::test]
+async fn browsers_are_redirected_when_configured() {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let app = app_with_config(|c| {
+        c.confirmation.redirects.subscribed = Some("https://www.example.com/thanks".into());
+    })
+    .await;
+
+    Mock::given(path("mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = client
+        .post(format!("{}/subscribe", app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("Accept", "text/html")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(
+        response.headers()["Location"],
+        "https://www.example.com/thanks"
+    );
+}
+
+#[tokio::test]
+async fn api_clients_get_json_responses() {
+    let app = app().await;
+    Mock::given(path("mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let mut outcomes = vec![];
+    for body in [
+        "name=le%20guin&email=ursula_le_guin%40gmail.com",
+        "name=le%20guin&email=ursula_le_guin%40gmail.com",
+        "name=le%20guin&email=not-an-email",
+    ] {
+        let response = reqwest::Client::new()
+            .post(format!("{}/subscribe", app.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to send request");
+        let status = response.status().as_u16();
+        let body: serde_json::Value = response.json().await.unwrap();
+        outcomes.push((status, body));
+    }
+
+    assert_eq!(
+        outcomes,
+        vec![
+            (200, serde_json::json!({"status": "pending_confirmation"})),
+            (
+                409,
+                serde_json::json!({"error": "The email is already on the list"})
+            ),
+            (
+                400,
+                serde_json::json!({"error": "Not a valid email address"})
+            ),
+        ]
+    );
+}
@@ -1,10 +1,9 @@
-use reqwest::Url;
 use wiremock::{
     matchers::{method, path},
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::app;
+use crate::helpers::{app, app_with_config, TestApp};
 
 #[tokio::test]
 async fn test_subscription_confirm_is_rejected_without_token() {
@@ -43,17 +42,13 @@ async fn test_click_on_the_confirmation_link_confirms_the_subscriber() {
     assert!(response.status().is_success());
 
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let link = app.get_confirmation_link(&email_request);
+    let link = app.get_confirmation_link(email_request);
     assert!(link.contains(&format!(
         "http://127.0.0.1:{}/subscriptions/confirm?subscription_token=",
         app.port
     )));
 
-    client
-        .get(link)
-        .send()
-        .await
-        .expect("Failed to send confirmation request");
+    app.confirm_subscription(&link).await;
 
     let saved = sqlx::query!("SELECT email, name, status FROM subscriptions")
         .fetch_one(&app.db_pool)
@@ -88,7 +83,7 @@ async fn test_confirmation_link_returns_200_when_called() {
     assert!(response.status().is_success());
 
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let link = app.get_confirmation_link(&email_request);
+    let link = app.get_confirmation_link(email_request);
     assert!(link.contains(&format!(
         "http://127.0.0.1:{}/subscriptions/confirm?subscription_token=",
         app.port
@@ -101,3 +96,156 @@ async fn test_confirmation_link_returns_200_when_called() {
         .expect("Failed to send confirmation request");
     assert_eq!(response.status().as_u16(), 200);
 }
+
+async fn subscribe_and_get_link(app: &TestApp) -> String {
+    Mock::given(path("mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    reqwest::Client::new()
+        .post(format!("{}/subscribe", app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .expect("Failed to send request")
+        .error_for_status()
+        .expect("Failed to subscribe");
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    app.get_confirmation_link(email_request)
+}
+
+#[tokio::test]
+async fn confirmation_renders_an_html_page() {
+    let app = app().await;
+    let link = subscribe_and_get_link(&app).await;
+
+    let response = app.confirm_subscription(&link).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/html; charset=utf-8"
+    );
+    let body = response.text().await.unwrap();
+    assert!(body.starts_with("<!DOCTYPE html>"));
+    assert!(body.contains("Your subscription has been confirmed"));
+}
+
+#[tokio::test]
+async fn a_second_click_reports_the_subscription_as_already_confirmed() {
+    let app = app().await;
+    let link = subscribe_and_get_link(&app).await;
+    app.confirm_subscription(&link).await;
+
+    let response = app.confirm_subscription(&link).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("Your subscription is already confirmed"));
+}
+
+#[tokio::test]
+async fn expired_tokens_do_not_confirm_the_subscriber() {
+    let app = app().await;
+    let link = subscribe_and_get_link(&app).await;
+    sqlx::query!("UPDATE subscription_tokens SET created_at = now() - interval '73 hours'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = app.confirm_subscription(&link).await;
+
+    assert_eq!(response.status().as_u16(), 410);
+    assert!(response.text().await.unwrap().contains("has expired"));
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn unknown_tokens_render_an_error_page_in_the_requested_language() {
+    let app = app().await;
+
+    let response = reqwest::Client::new()
+        .get(format!(
+            "{}/subscriptions/confirm?subscription_token=unknown",
+            app.address
+        ))
+        .header("Accept-Language", "de")
+        .send()
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(response.status().as_u16(), 401);
+    let body = response.text().await.unwrap();
+    assert!(body.contains(r#"<html lang="de">"#));
+    assert!(body.contains("Dieser Bestätigungslink ist ungültig."));
+}
+
+#[tokio::test]
+async fn configured_redirects_replace_the_pages() {
+    let app = app_with_config(|c| {
+        c.confirmation.redirects.confirmed = Some("https://www.example.com/welcome".into());
+    })
+    .await;
+    let link = subscribe_and_get_link(&app).await;
+
+    let confirmed = app.confirm_subscription(&link).await;
+    let already_confirmed = app.confirm_subscription(&link).await;
+
+    assert_eq!(confirmed.status().as_u16(), 303);
+    assert_eq!(
+        confirmed.headers()["Location"],
+        "https://www.example.com/welcome"
+    );
+    assert_eq!(already_confirmed.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn following_the_link_only_asks_to_confirm() {
+    let app = app().await;
+    let link = subscribe_and_get_link(&app).await;
+
+    let response = reqwest::get(&link).await.expect("Failed to send request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.unwrap();
+    assert!(body.contains(r#"<form id="confirm" method="post" action="/subscriptions/confirm">"#));
+    assert!(!body.contains("<script>"));
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn the_link_tells_a_confirmed_subscriber_so() {
+    let app = app().await;
+    let link = subscribe_and_get_link(&app).await;
+    app.confirm_subscription(&link).await;
+
+    let response = reqwest::get(&link).await.expect("Failed to send request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("Your subscription is already confirmed"));
+    assert!(!body.contains("<form"));
+}
+
+#[tokio::test]
+async fn the_form_can_submit_itself() {
+    let app = app_with_config(|c| c.confirmation.auto_submit = true).await;
+    let link = subscribe_and_get_link(&app).await;
+
+    let response = reqwest::get(&link).await.expect("Failed to send request");
+
+    let body = response.text().await.unwrap();
+    assert!(body.contains(r#"document.getElementById("confirm").submit()"#));
+}
@@ -0,0 +1,259 @@
+use newsletter::cli::{export_subscribers, import_subscribers, SubscriberStatus};
+use newsletter::publications::DEFAULT_PUBLICATION;
+use serde_json::{json, Value};
+
+use crate::helpers::{app, TestAdmin, TestApp};
+
+const CSV: &str = "email,name
+ursula@example.com,Ursula Le Guin
+octavia@example.com,Octavia Butler
+samuel@example.com,Samuel Delany
+";
+
+async fn app_with_subscribers() -> (TestApp, TestAdmin) {
+    let app = app().await;
+    let admin = app.create_admin().await;
+    import_subscribers(
+        &app.db_pool,
+        DEFAULT_PUBLICATION,
+        CSV.as_bytes(),
+        SubscriberStatus::Confirmed,
+    )
+    .await
+    .unwrap();
+
+    (app, admin)
+}
+
+async fn bulk(
+    app: &TestApp,
+    admin: &TestAdmin,
+    action: &str,
+    tag: &str,
+    body: Value,
+) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/admin/tags/{}/{}", app.address, tag, action))
+        .basic_auth(&admin.username, Some(&admin.password))
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to send request")
+}
+
+/// Emails of the subscribers with the tag, and every subscriber's tags.
+async fn tagged(app: &TestApp, admin: &TestAdmin, tag: &str) -> Vec<(String, Vec<String>)> {
+    let subscribers: Value = reqwest::Client::new()
+        .get(format!("{}/admin/subscribers?tag={}", app.address, tag))
+        .basic_auth(&admin.username, Some(&admin.password))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut tagged: Vec<_> = subscribers
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| {
+            let tags = serde_json::from_value(s["tags"].clone()).unwrap();
+            (s["email"].as_str().unwrap().to_owned(), tags)
+        })
+        .collect();
+    tagged.sort();
+    tagged
+}
+
+#[tokio::test]
+async fn tags_are_applied_to_a_list_of_emails() {
+    let (app, admin) = app_with_subscribers().await;
+
+    let response = bulk(
+        &app,
+        &admin,
+        "apply",
+        "beta-tester",
+        json!({"emails": [" Ursula@Example.com", "octavia@example.com", "nobody@example.com", "not-an-email"]}),
+    )
+    .await;
+    let again = bulk(
+        &app,
+        &admin,
+        "apply",
+        "beta-tester",
+        json!({"emails": ["ursula@example.com"]}),
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.json::<Value>().await.unwrap(),
+        json!({
+            "updated": 2,
+            "invalid_emails": ["not-an-email"],
+            "unmatched_emails": ["nobody@example.com"]
+        })
+    );
+    assert_eq!(again.json::<Value>().await.unwrap()["updated"], 0);
+    assert_eq!(
+        tagged(&app, &admin, "beta-tester").await,
+        vec![
+            (
+                "octavia@example.com".to_owned(),
+                vec!["beta-tester".to_owned()]
+            ),
+            (
+                "ursula@example.com".to_owned(),
+                vec!["beta-tester".to_owned()]
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn tags_are_applied_to_uploaded_emails() {
+    let (app, admin) = app_with_subscribers().await;
+    let upload = |action: &'static str, content_type: &'static str, body: &'static str| {
+        reqwest::Client::new()
+            .post(format!("{}/admin/tags/beta-tester/{}", app.address, action))
+            .basic_auth(&admin.username, Some(&admin.password))
+            .header("Content-Type", content_type)
+            .body(body)
+            .send()
+    };
+
+    let csv = upload(
+        "apply",
+        "text/csv",
+        "name,email\nUrsula,ursula@example.com\nOctavia,octavia@example.com\n",
+    )
+    .await
+    .unwrap();
+    let text = upload(
+        "remove",
+        "text/plain",
+        "octavia@example.com\nnobody@example.com\n",
+    )
+    .await
+    .unwrap();
+    let unsupported = upload("apply", "application/pdf", "%PDF").await.unwrap();
+
+    assert_eq!(csv.json::<Value>().await.unwrap()["updated"], 2);
+    assert_eq!(
+        text.json::<Value>().await.unwrap(),
+        json!({"updated": 1, "invalid_emails": [], "unmatched_emails": ["nobody@example.com"]})
+    );
+    assert_eq!(unsupported.status().as_u16(), 400);
+    assert_eq!(
+        tagged(&app, &admin, "beta-tester").await,
+        vec![(
+            "ursula@example.com".to_owned(),
+            vec!["beta-tester".to_owned()]
+        )]
+    );
+}
+
+#[tokio::test]
+async fn tags_are_applied_and_removed_by_filter() {
+    let (app, admin) = app_with_subscribers().await;
+    bulk(
+        &app,
+        &admin,
+        "apply",
+        "beta-tester",
+        json!({"emails": ["ursula@example.com"]}),
+    )
+    .await;
+
+    let everyone = bulk(
+        &app,
+        &admin,
+        "apply",
+        "conference-2026",
+        json!({"filter": {}}),
+    )
+    .await;
+    let testers = bulk(
+        &app,
+        &admin,
+        "remove",
+        "conference-2026",
+        json!({"filter": {"tag": "beta-tester"}}),
+    )
+    .await;
+
+    assert_eq!(everyone.json::<Value>().await.unwrap()["updated"], 3);
+    assert_eq!(testers.json::<Value>().await.unwrap()["updated"], 1);
+    assert_eq!(
+        tagged(&app, &admin, "conference-2026").await,
+        vec![
+            (
+                "octavia@example.com".to_owned(),
+                vec!["conference-2026".to_owned()]
+            ),
+            (
+                "samuel@example.com".to_owned(),
+                vec!["conference-2026".to_owned()]
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn bulk_operations_need_a_valid_tag_and_selection() {
+    let (app, admin) = app_with_subscribers().await;
+
+    for (tag, body) in [
+        ("beta-tester", json!({})),
+        ("Beta Tester", json!({"filter": {}})),
+        (
+            "beta-tester",
+            json!({"filter": {"attributes": {"plan": "pro"}}}),
+        ),
+    ] {
+        let response = bulk(&app, &admin, "apply", tag, body.clone()).await;
+        assert_eq!(response.status().as_u16(), 400, "{} was accepted", body);
+    }
+}
+
+#[tokio::test]
+async fn tags_are_counted_and_exported() {
+    let (app, admin) = app_with_subscribers().await;
+    for tag in ["beta-tester", "vip"] {
+        bulk(
+            &app,
+            &admin,
+            "apply",
+            tag,
+            json!({"emails": ["ursula@example.com"]}),
+        )
+        .await;
+    }
+
+    let counts: Value = reqwest::Client::new()
+        .get(format!("{}/admin/tags", app.address))
+        .basic_auth(&admin.username, Some(&admin.password))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut output = vec![];
+    export_subscribers(&app.db_pool, DEFAULT_PUBLICATION, &mut output, None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        counts,
+        json!([
+            {"tag": "beta-tester", "subscribers": 1},
+            {"tag": "vip", "subscribers": 1}
+        ])
+    );
+    let output = String::from_utf8(output).unwrap();
+    assert!(output
+        .lines()
+        .any(|l| l.starts_with("ursula@example.com,") && l.ends_with(",beta-tester vip")));
+}
@@ -0,0 +1,239 @@
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::time::Duration;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::helpers::{app, app_with_config, TestAdmin, TestApp};
+
+async fn register_webhook(app: &TestApp, admin: &TestAdmin, url: String) -> Value {
+    let response = reqwest::Client::new()
+        .post(format!("{}/admin/webhooks", app.address))
+        .basic_auth(&admin.username, Some(&admin.password))
+        .json(&json!({
+            "url": url,
+            "events": ["subscriber.subscribed", "subscriber.confirmed"]
+        }))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status().as_u16(), 201);
+
+    response.json().await.unwrap()
+}
+
+async fn subscribe(app: &TestApp) {
+    Mock::given(path("mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let response = reqwest::Client::new()
+        .post(format!("{}/subscribe", app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+fn header(request: &wiremock::Request, name: &str) -> String {
+    request
+        .headers
+        .get(&name.into())
+        .unwrap_or_else(|| panic!("{} is missing", name))
+        .as_str()
+        .to_owned()
+}
+
+async fn wait_for_requests(server: &MockServer, count: usize) -> Vec<wiremock::Request> {
+    for _ in 0..100 {
+        let received = server.received_requests().await.unwrap();
+        if received.len() >= count {
+            return received;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("Expected {} webhook requests", count);
+}
+
+#[tokio::test]
+async fn lifecycle_events_are_delivered_with_a_signature() {
+    let app = app().await;
+    let admin = app.create_admin().await;
+    let crm = MockServer::start().await;
+    Mock::given(path("/hooks"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&crm)
+        .await;
+    let webhook = register_webhook(&app, &admin, format!("{}/hooks", crm.uri())).await;
+
+    subscribe(&app).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    app.confirm_subscription(&app.get_confirmation_link(email_request))
+        .await;
+
+    let received = wait_for_requests(&crm, 2).await;
+    let mut events = vec![];
+    for request in &received {
+        let body = String::from_utf8(request.body.clone()).unwrap();
+        let timestamp = header(request, "X-Newsletter-Timestamp");
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(webhook["secret"].as_str().unwrap().as_bytes()).unwrap();
+        mac.update(format!("{}.{}", timestamp, body).as_bytes());
+        let signature = header(request, "X-Newsletter-Signature");
+        let expected: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        assert_eq!(signature, format!("sha256={}", expected));
+
+        let payload: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload["data"]["email"], "ursula_le_guin@gmail.com");
+        events.push(payload["type"].as_str().unwrap().to_owned());
+    }
+    events.sort();
+    assert_eq!(events, ["subscriber.confirmed", "subscriber.subscribed"]);
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_logged_and_can_be_replayed() {
+    let app = app_with_config(|c| c.webhooks.max_attempts = 2).await;
+    let admin = app.create_admin().await;
+    let client = reqwest::Client::new();
+    let crm = MockServer::start().await;
+    Mock::given(path("/hooks"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(2)
+        .mount(&crm)
+        .await;
+    Mock::given(path("/hooks"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&crm)
+        .await;
+    let webhook = register_webhook(&app, &admin, format!("{}/hooks", crm.uri())).await;
+
+    subscribe(&app).await;
+    wait_for_requests(&crm, 2).await;
+
+    let deliveries_url = format!(
+        "{}/admin/webhooks/{}/deliveries",
+        app.address,
+        webhook["endpoint_id"].as_str().unwrap()
+    );
+    let mut deliveries = Value::Null;
+    for _ in 0..100 {
+        deliveries = client
+            .get(&deliveries_url)
+            .basic_auth(&admin.username, Some(&admin.password))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if deliveries[0]["status"] == "failed" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(deliveries[0]["status"], "failed");
+    assert_eq!(deliveries[0]["attempts"], 2);
+    assert_eq!(deliveries[0]["last_response_status"], 500);
+
+    let response = client
+        .post(format!(
+            "{}/admin/webhooks/deliveries/{}/replay",
+            app.address,
+            deliveries[0]["delivery_id"].as_str().unwrap()
+        ))
+        .basic_auth(&admin.username, Some(&admin.password))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 202);
+
+    let received = wait_for_requests(&crm, 3).await;
+    assert_eq!(received[2].body, received[0].body);
+}
+
+#[tokio::test]
+async fn admin_endpoints_require_valid_credentials() {
+    let app = app().await;
+    let admin = app.create_admin().await;
+    let client = reqwest::Client::new();
+
+    for password in [None, Some("wrong password")] {
+        let mut request = client.get(format!("{}/admin/webhooks", app.address));
+        if let Some(password) = password {
+            request = request.basic_auth(&admin.username, Some(password));
+        }
+        let response = request.send().await.unwrap();
+
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(
+            response.headers()["WWW-Authenticate"],
+            r#"Basic realm="admin""#
+        );
+    }
+
+    let response = client
+        .get(format!("{}/admin/webhooks", app.address))
+        .basic_auth("nobody", Some(&admin.password))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn deliveries_are_claimed_before_they_are_sent() {
+    let app = app().await;
+    let admin = app.create_admin().await;
+    let crm = MockServer::start().await;
+    Mock::given(path("/hooks"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+        .mount(&crm)
+        .await;
+    register_webhook(&app, &admin, format!("{}/hooks", crm.uri())).await;
+
+    subscribe(&app).await;
+
+    // The claim is committed while the endpoint is still answering.
+    let mut in_flight = None;
+    for _ in 0..100 {
+        let delivery = sqlx::query!(
+            "SELECT status, attempts, next_attempt_at > now() AS \"claimed!\" FROM webhook_deliveries"
+        )
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+        if delivery.attempts == 1 {
+            in_flight = Some(delivery);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let in_flight = in_flight.expect("The delivery was never claimed");
+    assert_eq!(in_flight.status, "pending");
+    assert!(in_flight.claimed);
+
+    wait_for_requests(&crm, 1).await;
+    for _ in 0..100 {
+        let status = sqlx::query_scalar!("SELECT status FROM webhook_deliveries")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+        if status == "delivered" {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("The delivery was never recorded");
+}